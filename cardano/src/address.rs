@@ -157,7 +157,7 @@ impl cbor_event::se::Serialize for StakeDistribution {
 impl cbor_event::de::Deserialize for StakeDistribution {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
         // stake distribution is an encoded cbor in bytes of a sum_type...
-        let mut raw = RawCbor::from(&raw.bytes()?);
+        let mut raw = RawCbor::from(&cbor::hs::util::checked_bytes(raw)?);
         let len = raw.array()?;
         if len != cbor_event::Len::Len(1) && len != cbor_event::Len::Len(2) {
             return Err(cbor_event::Error::CustomError(format!("Invalid Stakedistribution: recieved array of {:?} elements", len)));
@@ -197,6 +197,15 @@ impl Attributes {
             stake_distribution: StakeDistribution::new_single_key(pubk)
         }
     }
+
+    /// tell if the attributes are of the legacy format, i.e. they embed
+    /// the (encrypted) derivation path of the address as used by the
+    /// 2 Level of randomly chosen hard derivation indexes wallets.
+    ///
+    /// Addresses of the sequential (BIP44) wallets do not need it.
+    pub fn is_legacy(&self) -> bool {
+        self.derivation_path.is_some()
+    }
 }
 const ATTRIBUTE_NAME_TAG_STAKE : u64 = 0;
 const ATTRIBUTE_NAME_TAG_DERIVATION : u64 = 1;
@@ -333,20 +342,129 @@ impl ExtendedAddr {
         ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(xpub), Attributes::new_bootstrap_era(None))
     }
 
+    /// bootstrap era redeem (AVVM) address of the given redeem public key
+    pub fn new_redeem(key: redeem::PublicKey) -> Self {
+        ExtendedAddr::new(AddrType::ATRedeem, SpendingData::RedeemASD(key), Attributes::new_bootstrap_era(None))
+    }
+
+    /// tell if the address uses the legacy attributes format (see
+    /// [`Attributes::is_legacy`](./struct.Attributes.html#method.is_legacy)).
+    ///
+    /// Wallets migrating to the sequential addressing scheme may want to
+    /// regenerate their addresses.
+    pub fn uses_legacy_attributes(&self) -> bool {
+        self.attributes.is_legacy()
+    }
+
+    /// encode the address in base58, the format used to display and
+    /// share addresses. The result can be parsed back with `FromStr`.
+    pub fn to_base58(&self) -> String {
+        base58::encode(&cbor!(self).unwrap())
+    }
 }
 #[derive(Debug)]
 pub enum ParseExtendedAddrError {
     EncodingError(cbor_event::Error),
     Base58Error(base58::Error)
 }
+impl fmt::Display for ParseExtendedAddrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseExtendedAddrError::EncodingError(_) => write!(f, "invalid address encoding (corrupted or wrong checksum)"),
+            ParseExtendedAddrError::Base58Error(_) => write!(f, "invalid base58 encoding"),
+        }
+    }
+}
+impl ::std::error::Error for ParseExtendedAddrError {
+    fn cause(&self) -> Option<&dyn std::error::Error> {
+        match *self {
+            ParseExtendedAddrError::EncodingError(ref err) => Some(err),
+            ParseExtendedAddrError::Base58Error(ref err) => Some(err),
+        }
+    }
+}
+/// error when decoding an [`ExtendedAddr`](./struct.ExtendedAddr.html)
+/// with [`try_from_bytes`](./struct.ExtendedAddr.html#method.try_from_bytes).
+#[derive(Debug)]
+pub enum DecodeError {
+    /// the bytes are not a CBOR encoded address: invalid structure,
+    /// wrong CRC or invalid field
+    InvalidEncoding(cbor_event::Error),
+    /// an address was decoded but the bytes are not exactly its encoding
+    /// (e.g. trailing bytes or non canonical CBOR)
+    NonCanonical,
+}
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::InvalidEncoding(_) => write!(f, "invalid address encoding (corrupted or wrong checksum)"),
+            DecodeError::NonCanonical => write!(f, "the bytes are not the canonical encoding of the address"),
+        }
+    }
+}
+impl ::std::error::Error for DecodeError {
+    fn cause(&self) -> Option<&dyn std::error::Error> {
+        match *self {
+            DecodeError::InvalidEncoding(ref err) => Some(err),
+            DecodeError::NonCanonical => None,
+        }
+    }
+}
+impl ExtendedAddr {
+    /// decode an address from untrusted bytes (e.g. received from the
+    /// network).
+    ///
+    /// Unlike [`try_from_slice`](../util/try_from_slice/trait.TryFromSlice.html)
+    /// this also checks that the bytes are exactly the encoding of the
+    /// decoded address, so the same address cannot be given under
+    /// different encodings.
+    ///
+    /// ```
+    /// use cardano::address::{ExtendedAddr, DecodeError};
+    /// # use cardano::hdwallet::{XPrv, Seed, SEED_SIZE};
+    /// # let address = ExtendedAddr::new_simple(XPrv::generate_from_seed(&Seed::from_bytes([0;SEED_SIZE])).public());
+    ///
+    /// let mut bytes = address.to_bytes();
+    /// assert_eq!(ExtendedAddr::try_from_bytes(&bytes).unwrap(), address);
+    ///
+    /// bytes.push(0);
+    /// match ExtendedAddr::try_from_bytes(&bytes) {
+    ///     Err(DecodeError::NonCanonical) => {},
+    ///     r => panic!("unexpected result: {:?}", r),
+    /// }
+    /// ```
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let addr = Self::try_from_slice(bytes).map_err(DecodeError::InvalidEncoding)?;
+        if addr.to_bytes() != bytes { return Err(DecodeError::NonCanonical); }
+        Ok(addr)
+    }
+
+    /// the CBOR encoding of the address, as found in transactions
+    pub fn to_bytes(&self) -> Vec<u8> {
+        cbor!(self).expect("encode ExtendedAddr")
+    }
+
+    /// the number of bytes of the CBOR encoding of the address (see
+    /// [`to_bytes`](./struct.ExtendedAddr.html#method.to_bytes)), e.g. to
+    /// estimate the size of a transaction output paying to it.
+    ///
+    /// The size depends on the address attributes: a bootstrap era address
+    /// without HD payload is smaller than a single key address with one.
+    pub fn serialized_size(&self) -> usize {
+        self.to_bytes().len()
+    }
+}
 impl ::std::str::FromStr for ExtendedAddr {
     type Err = ParseExtendedAddrError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let bytes = base58::decode(s)
             .map_err(ParseExtendedAddrError::Base58Error)?;
 
-        Self::try_from_slice(&bytes)
-            .map_err(ParseExtendedAddrError::EncodingError)
+        Self::try_from_bytes(&bytes).map_err(|err| match err {
+            DecodeError::InvalidEncoding(err) => ParseExtendedAddrError::EncodingError(err),
+            DecodeError::NonCanonical => ParseExtendedAddrError::EncodingError(
+                cbor_event::Error::CustomError("trailing bytes after the address".to_owned())),
+        })
     }
 }
 impl TryFromSlice for ExtendedAddr {
@@ -375,7 +493,7 @@ impl cbor_event::de::Deserialize for ExtendedAddr {
 }
 impl fmt::Display for ExtendedAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", base58::encode(&cbor!(self).unwrap()))
+        write!(f, "{}", self.to_base58())
     }
 }
 #[cfg(feature = "generic-serialization")]
@@ -479,6 +597,8 @@ mod tests {
     use address::*;
     use hdwallet;
     use util::base58;
+    use std::str::FromStr;
+    use test_helpers::XorShift;
 
     #[test]
     fn test_make_address() {
@@ -541,6 +661,51 @@ mod tests {
         assert_eq!(ea, r);
     }
 
+    #[test]
+    fn try_from_bytes_rejects_malformed_input() {
+        let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+        let pk = hdwallet::XPrv::generate_from_seed(&seed).public();
+        let attrs = Attributes::new_single_key(&pk, Some(HDAddressPayload::from_vec(vec![1,2,3,4,5])));
+        let ea = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(pk), attrs);
+        let bytes = ea.to_bytes();
+
+        assert_eq!(ExtendedAddr::try_from_bytes(&bytes).unwrap(), ea);
+
+        // every truncation
+        for len in 0..bytes.len() {
+            assert!(ExtendedAddr::try_from_bytes(&bytes[..len]).is_err(), "truncated to {} bytes", len);
+        }
+        // every single bit flip is caught, by the CBOR structure or the CRC
+        for i in 0..bytes.len() {
+            for bit in 0..8 {
+                let mut corrupted = bytes.clone();
+                corrupted[i] ^= 1 << bit;
+                assert!(ExtendedAddr::try_from_bytes(&corrupted).is_err(), "bit {} of byte {} flipped", bit, i);
+            }
+        }
+        // trailing bytes
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        match ExtendedAddr::try_from_bytes(&trailing) {
+            Err(DecodeError::NonCanonical) => {},
+            r => panic!("expected a non canonical encoding, got {:?}", r),
+        }
+        // not the expected pair of the crc encoding
+        assert!(ExtendedAddr::try_from_bytes(&[0x83, 0xd8, 0x18, 0x40, 0x00, 0x00]).is_err());
+        assert!(ExtendedAddr::try_from_bytes(&[0x9f, 0xff]).is_err());
+
+        // random inputs, with a fixed seed
+        let mut rng = XorShift::new(0x9e3779b9);
+        for len in 0..512 {
+            let random : Vec<u8> = (0..len % 128).map(|_| rng.next_u32() as u8).collect();
+            assert!(ExtendedAddr::try_from_bytes(&random).is_err());
+            // with a valid header
+            let mut prefixed = bytes[..8].to_vec();
+            prefixed.extend_from_slice(&random);
+            assert!(ExtendedAddr::try_from_bytes(&prefixed).is_err());
+        }
+    }
+
     #[test]
     fn encode_decode_addr_type() {
         let addr_type_1 = AddrType::ATPubKey;
@@ -596,6 +761,92 @@ mod tests {
         assert_eq!(r.attributes.stake_distribution, StakeDistribution::BootstrapEraDistr);
     }
 
+    const MAINNET_ADDRESSES : [&str;2] =
+        [ "DdzFFzCqrhsyhumccfGyEj3WZzztSPr92ntRWB6UVVwzcMTpwoafVQ5vD9mdZ5Xind8ycugbmA8esxmo7NycjQFGSbDeKrxabTz8MVzf"
+        , "DdzFFzCqrhsi8XFMabbnHecVusaebqQCkXTqDnCumx5esKB1pk1zbhX5BtdAivZbQePFVujgzNCpBVXactPSmphuHRC5Xk8qmBd49QjW"
+        ];
+
+    #[test]
+    fn parse_address_from_base58() {
+        for addr_str in MAINNET_ADDRESSES.iter() {
+            let addr = ExtendedAddr::from_str(addr_str).unwrap();
+            assert_eq!(addr_str, &addr.to_base58());
+            assert_eq!(addr_str, &format!("{}", addr));
+        }
+    }
+
+    #[test]
+    fn parse_address_bad_checksum() {
+        let mut bytes = base58::decode(MAINNET_ADDRESSES[0]).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+
+        match ExtendedAddr::from_str(&base58::encode(&bytes)) {
+            Err(ParseExtendedAddrError::EncodingError(_)) => {},
+            r => panic!("expected an encoding error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_address_truncated() {
+        let bytes = base58::decode(MAINNET_ADDRESSES[1]).unwrap();
+
+        for len in [0, 1, 4, bytes.len() / 2, bytes.len() - 1].iter() {
+            match ExtendedAddr::from_str(&base58::encode(&bytes[..*len])) {
+                Err(ParseExtendedAddrError::EncodingError(_)) => {},
+                r => panic!("expected an encoding error for {} bytes, got {:?}", len, r),
+            }
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        match ExtendedAddr::from_str(&base58::encode(&trailing)) {
+            Err(ParseExtendedAddrError::EncodingError(_)) => {},
+            r => panic!("expected an encoding error for the trailing bytes, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_address_invalid_base58() {
+        // `0`, `O`, `I` and `l` are not part of the base58 alphabet
+        let invalid = MAINNET_ADDRESSES[0].replacen("D", "0", 1);
+
+        match ExtendedAddr::from_str(&invalid) {
+            Err(ParseExtendedAddrError::Base58Error(base58::Error::UnknownSymbol(0))) => {},
+            r => panic!("expected a base58 error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn legacy_attributes() {
+        let legacy = ExtendedAddr::from_str(MAINNET_ADDRESSES[0]).unwrap();
+        assert!(legacy.uses_legacy_attributes());
+
+        let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+        let pk = hdwallet::XPrv::generate_from_seed(&seed).public();
+        let current = ExtendedAddr::new_simple(pk);
+        assert!(!current.uses_legacy_attributes());
+    }
+
+    #[test]
+    fn serialized_size_of_address_variants() {
+        let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+        let pk = hdwallet::XPrv::generate_from_seed(&seed).public();
+        let hdap = HDAddressPayload::from_vec(vec![1,2,3,4,5]);
+
+        let simple = ExtendedAddr::new_simple(pk);
+        let single_key = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(pk), Attributes::new_single_key(&pk, Some(hdap)));
+        let redeem = ExtendedAddr::new_redeem(redeem::PublicKey::from_bytes([0;redeem::PUBLICKEY_SIZE]));
+        let mut addresses = vec![simple.clone(), single_key.clone(), redeem];
+        addresses.extend(MAINNET_ADDRESSES.iter().map(|s| ExtendedAddr::from_str(s).unwrap()));
+
+        for address in addresses.iter() {
+            assert_eq!(address.serialized_size(), cbor!(address).unwrap().len());
+            assert_eq!(address.serialized_size(), base58::decode(&address.to_base58()).unwrap().len());
+        }
+        assert!(single_key.serialized_size() > simple.serialized_size());
+    }
+
     #[test]
     fn decode_address_no_derivation_path() {
         let bytes     = vec![0x82, 0xd8, 0x18, 0x58, 0x21, 0x83, 0x58, 0x1c, 0x10, 0x2a, 0x74, 0xca, 0x44, 0x05, 0xb8, 0xc1, 0x8d, 0x20, 0x84, 0x1e, 0x8c, 0x66, 0x4f, 0xe1, 0xde, 0x7d, 0x66, 0x07, 0x48, 0x08, 0x70, 0x4f, 0x91, 0x79, 0xe0, 0xfa, 0xa0, 0x00, 0x1a, 0xad, 0xf7, 0x10, 0x68];
@@ -78,7 +78,7 @@ impl error::Error for Error {}
 
 pub type Result<T> = result::Result<T, Error>;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Account(u32);
 impl Account {
     pub fn new(account: u32) -> Result<Self> {
@@ -160,7 +160,7 @@ impl<'de> serde::Deserialize<'de> for Account
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Index(u32);
 impl Index {
     pub fn new(index: u32) -> Result<Self> {
@@ -168,10 +168,24 @@ impl Index {
         Ok(Index(index))
     }
     pub fn get_scheme_value(&self) -> u32 { self.0 }
+    /// the index incremented by `i`, which must remain a soft derivation
+    /// index (i.e. at most `BIP44_SOFT_UPPER_BOUND - 1`).
+    ///
+    /// Fails with `Error::IndexOutOfBound` holding the index that would
+    /// have been reached (saturated to `0xFFFFFFFF`).
+    ///
+    /// ```
+    /// use cardano::bip::bip44::{Index, Error};
+    ///
+    /// let index = Index::new(0x7FFFFFF0).unwrap();
+    /// assert_eq!(index.incr(1).unwrap().get_scheme_value(), 0x7FFFFFF1);
+    /// assert_eq!(index.incr(0xF).unwrap().get_scheme_value(), 0x7FFFFFFF);
+    /// assert_eq!(index.incr(0x10), Err(Error::IndexOutOfBound(0x80000000)));
+    /// assert_eq!(index.incr(0xFFFFFFFF), Err(Error::IndexOutOfBound(0xFFFFFFFF)));
+    /// ```
     pub fn incr(&self, i: u32) -> Result<Self> {
-        if i >= 0x80000000 { return Err(Error::IndexOutOfBound(i)); }
-        let r = self.0 + i;
-        if r >= 0x80000000 { return Err(Error::IndexOutOfBound(r)); }
+        let r = self.0.saturating_add(i);
+        if r >= BIP44_SOFT_UPPER_BOUND { return Err(Error::IndexOutOfBound(r)); }
         Ok(Index(r))
     }
 
@@ -253,7 +267,37 @@ impl Change {
 
 /// Bip44 address derivation
 ///
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Addressings are ordered by account, then chain (the external chain
+/// before the internal one) and then index, so they can be used as keys in
+/// a `BTreeMap` or sorted for display.
+///
+/// # example
+///
+/// ```
+/// use cardano::bip::bip44::{Addressing, AddrType};
+/// use std::collections::HashSet;
+///
+/// let mut addressings = vec![
+///     Addressing::new(0, AddrType::Internal, 1).unwrap(),
+///     Addressing::new(0, AddrType::External, 2).unwrap(),
+///     Addressing::new(0, AddrType::Internal, 0).unwrap(),
+///     Addressing::new(0, AddrType::External, 10).unwrap(),
+///     Addressing::new(0, AddrType::External, 0).unwrap(),
+/// ];
+/// addressings.sort();
+///
+/// let sorted : Vec<_> = addressings.iter().map(|a| (a.address_type(), a.index.get_scheme_value())).collect();
+/// assert_eq!(sorted, vec![
+///     (AddrType::External, 0), (AddrType::External, 2), (AddrType::External, 10),
+///     (AddrType::Internal, 0), (AddrType::Internal, 1),
+/// ]);
+///
+/// let set : HashSet<_> = addressings.iter().cloned().collect();
+/// assert!(set.contains(&Addressing::new(0, AddrType::External, 2).unwrap()));
+/// assert!(!set.contains(&Addressing::new(1, AddrType::External, 2).unwrap()));
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct Addressing {
     pub account: Account,
@@ -266,7 +310,7 @@ impl fmt::Display for Addressing {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub enum AddrType {
     Internal,
@@ -294,6 +338,91 @@ impl Addressing {
         Ok(Addressing { account: Account::new(account)?, change: change, index: Index::new(index)? })
     }
 
+    /// create a new `Addressing` from the components of the full BIP44
+    /// derivation path `m/44'/1815'/account'/change/index`, the purpose and
+    /// the coin type being fixed (see `BIP44_PURPOSE` and `BIP44_COIN_TYPE`).
+    ///
+    /// The account and the index are given without the hard derivation
+    /// bit, they need to be in the soft index range (below
+    /// `BIP44_SOFT_UPPER_BOUND`).
+    ///
+    /// # example
+    ///
+    /// ```
+    /// use cardano::bip::bip44::{Addressing, AddrType, Error};
+    ///
+    /// let addr = Addressing::from_bip44(0, AddrType::External, 5).unwrap();
+    /// assert_eq!(addr.to_bip44_string(), "m/44'/1815'/0'/0/5");
+    /// assert_eq!(Addressing::from_bip44(3, AddrType::Internal, 0x7fffffff).unwrap().to_bip44_string(),
+    ///            "m/44'/1815'/3'/1/2147483647");
+    ///
+    /// assert_eq!(Addressing::from_bip44(0x80000000, AddrType::External, 0), Err(Error::AccountOutOfBound(0x80000000)));
+    /// assert_eq!(Addressing::from_bip44(0, AddrType::Internal, 0x80000005), Err(Error::IndexOutOfBound(0x80000005)));
+    /// ```
+    pub fn from_bip44(account: u32, change: AddrType, index: u32) -> Result<Self> {
+        Account::new(account)?.change(change)?.index(index)
+    }
+
+    /// display the full BIP44 derivation path of the `Addressing`, the hard
+    /// derivation indices being marked with a `'`.
+    ///
+    /// See [`from_bip44`](./struct.Addressing.html#method.from_bip44).
+    pub fn to_bip44_string(&self) -> String {
+        format!("m/{}'/{}'/{}'/{}/{}",
+                BIP44_PURPOSE ^ BIP44_SOFT_UPPER_BOUND,
+                BIP44_COIN_TYPE ^ BIP44_SOFT_UPPER_BOUND,
+                self.account.get_account_number(),
+                self.change,
+                self.index.get_scheme_value())
+    }
+
+    /// pack the `Addressing` in a `u64`, e.g. to be used as the key of a
+    /// key-value store.
+    ///
+    /// The account is stored in the 31 highest bits, then the chain (1 for
+    /// `AddrType::Internal`) and the index in the 32 lowest bits. The keys
+    /// then sort like the `Addressing`s: by account, chain and index.
+    ///
+    /// Only the chain of the `Addressing` is kept (see `address_type`).
+    ///
+    /// # example
+    ///
+    /// ```
+    /// use cardano::bip::bip44::{Addressing, AddrType, Error};
+    ///
+    /// for &typ in [AddrType::External, AddrType::Internal].iter() {
+    ///     for &index in [0, 1, 42, 0x7fffffff].iter() {
+    ///         let addr = Addressing::new(7, typ, index).unwrap();
+    ///         assert_eq!(Addressing::from_db_key(addr.to_db_key()), Ok(addr));
+    ///     }
+    /// }
+    /// assert_eq!(Addressing::new(0, AddrType::Internal, 5).unwrap().to_db_key(), 0x1_0000_0005);
+    /// assert!(Addressing::new(0, AddrType::Internal, 0).unwrap().to_db_key()
+    ///       > Addressing::new(0, AddrType::External, 0x7fffffff).unwrap().to_db_key());
+    ///
+    /// // the index bits are above the soft derivation range
+    /// assert_eq!(Addressing::from_db_key(0x8000_0005), Err(Error::IndexOutOfBound(0x8000_0005)));
+    /// ```
+    pub fn to_db_key(&self) -> u64 {
+        let chain = match self.address_type() {
+            AddrType::External => 0,
+            AddrType::Internal => 1,
+        };
+        ((self.account.get_account_number() as u64) << 33)
+            | (chain << 32)
+            | self.index.get_scheme_value() as u64
+    }
+
+    /// read back an `Addressing` packed with
+    /// [`to_db_key`](./struct.Addressing.html#method.to_db_key).
+    ///
+    /// Fails with `Error::IndexOutOfBound` if the index bits are not a soft
+    /// derivation index.
+    pub fn from_db_key(key: u64) -> Result<Self> {
+        let typ = if (key >> 32) & 1 == 0 { AddrType::External } else { AddrType::Internal };
+        Addressing::new((key >> 33) as u32, typ, key as u32)
+    }
+
     fn new_from_change(change: Change, index: u32) -> Result<Self> {
         Ok(Addressing{account: change.account, change: change.change, index: Index::new(index)? })
     }
@@ -333,6 +462,7 @@ impl Addressing {
 
     /// try to generate a new `Addressing` starting from the given
     /// `Addressing`'s index incremented by the given parameter;
+    /// see [`Index::incr`](./struct.Index.html#method.incr) for the errors.
     ///
     /// # Example
     ///
@@ -345,6 +475,10 @@ impl Addressing {
     ///
     /// assert!(next.index == Index::new(42).unwrap());
     /// assert!(next.incr(0x80000000).is_err());
+    ///
+    /// // the last soft derivation index can be reached, not crossed
+    /// assert!(next.incr(0x7FFFFFFF - 42).is_ok());
+    /// assert!(next.incr(0x7FFFFFFF - 41).is_err());
     /// ```
     pub fn incr(&self, incr: u32) -> Result<Self> {
         let mut addr = self.clone();
@@ -352,6 +486,40 @@ impl Addressing {
         Ok(addr)
     }
 
+    /// generate the `count` contiguous `Addressing` of the given account
+    /// and `AddrType`, starting at index `start`.
+    ///
+    /// Unlike [`next_chunks`](./struct.Addressing.html#method.next_chunks)
+    /// the range is never truncated: if one of the indices would be a
+    /// hard derivation index (i.e. above `BIP44_SOFT_UPPER_BOUND`), an
+    /// `Error::IndexOutOfBound` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::bip::bip44::{Addressing, AddrType, Index, Error, BIP44_SOFT_UPPER_BOUND};
+    ///
+    /// let range = Addressing::range(0, AddrType::Internal, 10, 3).unwrap();
+    /// assert_eq!(range, vec![ Addressing::new(0, AddrType::Internal, 10).unwrap()
+    ///                       , Addressing::new(0, AddrType::Internal, 11).unwrap()
+    ///                       , Addressing::new(0, AddrType::Internal, 12).unwrap()
+    ///                       ]);
+    /// assert!(Addressing::range(0, AddrType::External, 0, 0).unwrap().is_empty());
+    ///
+    /// let last = BIP44_SOFT_UPPER_BOUND - 1;
+    /// assert_eq!(Addressing::range(0, AddrType::External, last, 1).unwrap().len(), 1);
+    /// assert_eq!(Addressing::range(0, AddrType::External, last, 2),
+    ///            Err(Error::IndexOutOfBound(BIP44_SOFT_UPPER_BOUND)));
+    /// ```
+    pub fn range(account: u32, typ: AddrType, start: u32, count: u32) -> Result<Vec<Self>> {
+        let first = Addressing::new(account, typ, start)?;
+        if count == 0 { return Ok(Vec::new()); }
+        let last = start as u64 + count as u64 - 1;
+        // `start` is a soft index, so the first invalid index is the bound itself
+        if last >= BIP44_SOFT_UPPER_BOUND as u64 { return Err(Error::IndexOutOfBound(BIP44_SOFT_UPPER_BOUND)); }
+        (0..count).map(|i| first.incr(i)).collect()
+    }
+
     /// generate a sequence of Addressing from the given
     /// addressing as starting point up to the `chunk_size`.
     ///
@@ -3,9 +3,26 @@
 pub mod util {
     //! CBor util and other stuff
 
-    use cbor_event::{self, Len, de::{RawCbor}, Bytes};
+    use cbor_event::{self, Len, Type, de::{RawCbor}, Bytes};
     use crc32::{crc32};
 
+    /// same as `RawCbor::bytes` but fails, instead of panicking, if the
+    /// declared length of the bytes is longer than the remaining input.
+    ///
+    /// To use when decoding untrusted input (e.g. received from the network).
+    pub fn checked_bytes<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Bytes<'a>> {
+        if raw.cbor_type()? == Type::Bytes {
+            if let (Len::Len(len), len_sz) = raw.cbor_len()? {
+                // the header (1 + len_sz bytes) has been read successfully
+                let available = raw.len() - 1 - len_sz;
+                if len > available as u64 {
+                    return Err(cbor_event::Error::NotEnough(available, len as usize));
+                }
+            }
+        }
+        raw.bytes()
+    }
+
     pub fn encode_with_crc32_<T, W>(t: &T, s: cbor_event::se::Serializer<W>) -> cbor_event::Result<cbor_event::se::Serializer<W>>
         where T: cbor_event::Serialize
             , W: ::std::io::Write + Sized
@@ -17,14 +34,13 @@ pub mod util {
             .write_unsigned_integer(crc32 as u64)
     }
     pub fn raw_with_crc32<'a, 'b>(raw: &'b mut RawCbor<'a>) -> cbor_event::Result<Bytes<'a>> {
-        let len = raw.array()?;
-        assert!(len == Len::Len(2));
+        raw.tuple(2, "value with crc32")?;
 
         let tag = raw.tag()?;
         if tag != 24 {
             return Err(cbor_event::Error::CustomError(format!("Invalid Tag: {} but expected 24", tag)));
         }
-        let bytes = raw.bytes()?;
+        let bytes = checked_bytes(raw)?;
 
         let crc = raw.unsigned_integer()?;
 
@@ -38,6 +38,35 @@ impl ::std::error::Error for Error {}
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// error when parsing a `Coin` from an ADA string, see
+/// [`Coin::from_ada_string`](./struct.Coin.html#method.from_ada_string).
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParseCoinError {
+    /// the string is not of the form `<integral>` or `<integral>.<decimals>`
+    InvalidFormat,
+    /// the string has more than the 6 decimals of a Lovelace
+    TooManyDecimals(usize),
+    /// the string represents a negative value
+    Negative,
+    /// the value is above `MAX_COIN`
+    OutOfBound,
+}
+impl fmt::Display for ParseCoinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseCoinError::InvalidFormat => write!(f, "Invalid ADA value, expected digits with an optional dot and up to 6 decimals"),
+            ParseCoinError::TooManyDecimals(ref n) => write!(f, "ADA value with {} decimals, at most 6 are supported", n),
+            ParseCoinError::Negative => write!(f, "ADA value cannot be negative"),
+            ParseCoinError::OutOfBound => write!(f, "ADA value is out of bound. Max coin value: {}.", Coin(MAX_COIN)),
+        }
+    }
+}
+impl ::std::error::Error for ParseCoinError {}
+
+const LOVELACE_PER_ADA: u64 = 1_000_000;
+const ADA_DECIMALS: usize = 6;
+
 /// A differential value between 2 coins 'a' and 'b', similar to 'a - b'
 pub enum CoinDiff {
     /// First value is greater than second value by n coins where n is not 0. // a == b + n
@@ -93,6 +122,89 @@ impl Coin {
         if v <= MAX_COIN { Ok(Coin(v)) } else { Err(Error::OutOfBound(v)) }
     }
 
+    /// add the two coins, returns `None` if the result is above `MAX_COIN`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::coin::{Coin, MAX_COIN};
+    ///
+    /// let max = Coin::new(MAX_COIN).unwrap();
+    ///
+    /// assert_eq!(Coin::zero().checked_add(max), Some(max));
+    /// assert_eq!(max.checked_add(Coin::unit()), None);
+    /// ```
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).and_then(|v| Coin::new(v).ok())
+    }
+
+    /// substract the two coins, returns `None` if the result would be negative
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::coin::{Coin};
+    ///
+    /// let coin = Coin::new(42).unwrap();
+    ///
+    /// assert_eq!(coin.checked_sub(coin), Some(Coin::zero()));
+    /// assert_eq!(Coin::zero().checked_sub(coin), None);
+    /// ```
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Coin)
+    }
+
+    /// display the coin in ADA, with the 6 decimals of the Lovelace
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::coin::{Coin};
+    ///
+    /// assert_eq!(Coin::new(1234567).unwrap().to_ada_string(), "1.234567");
+    /// assert_eq!(Coin::new(1000000).unwrap().to_ada_string(), "1.000000");
+    /// ```
+    pub fn to_ada_string(&self) -> String {
+        format!("{}.{:06}", self.0 / LOVELACE_PER_ADA, self.0 % LOVELACE_PER_ADA)
+    }
+
+    /// parse a coin from a value in ADA, e.g. `"1.234567"`.
+    ///
+    /// The decimals are optional and trailing zeros are accepted, but a
+    /// value with more decimals than the Lovelace (6) is rejected rather
+    /// than rounded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::coin::{Coin, ParseCoinError};
+    ///
+    /// assert_eq!(Coin::from_ada_string("1.5").unwrap(), Coin::new(1500000).unwrap());
+    /// assert_eq!(Coin::from_ada_string("0.0000001"), Err(ParseCoinError::TooManyDecimals(7)));
+    /// ```
+    pub fn from_ada_string(s: &str) -> result::Result<Self, ParseCoinError> {
+        if s.starts_with('-') { return Err(ParseCoinError::Negative); }
+
+        let (integral, decimals) = match s.find('.') {
+            None => (s, ""),
+            Some(dot) => (&s[..dot], &s[dot + 1..]),
+        };
+        let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+        if !is_digits(integral) || (s.len() > integral.len() && !is_digits(decimals)) {
+            return Err(ParseCoinError::InvalidFormat);
+        }
+        if decimals.len() > ADA_DECIMALS {
+            return Err(ParseCoinError::TooManyDecimals(decimals.len()));
+        }
+
+        let ada : u64 = integral.parse().map_err(|_| ParseCoinError::OutOfBound)?;
+        let lovelace : u64 = format!("{:0<width$}", decimals, width = ADA_DECIMALS).parse().map_err(|_| ParseCoinError::InvalidFormat)?;
+        ada.checked_mul(LOVELACE_PER_ADA)
+           .and_then(|v| v.checked_add(lovelace))
+           .and_then(|v| Coin::new(v).ok())
+           .ok_or(ParseCoinError::OutOfBound)
+    }
+
     pub fn differential(self, against: Self) -> CoinDiff {
         match self.0.cmp(&against.0) {
             Ordering::Equal   => CoinDiff::Zero,
@@ -191,3 +303,54 @@ pub fn sum_coins<I>(coin_iter: I) -> Result<Coin>
 {
     coin_iter.fold(Coin::new(0), |acc, ref c| acc.and_then(|v| v + *c))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ada_string_exact_conversions() {
+        let values = [ (0, "0.000000"), (1, "0.000001"), (999_999, "0.999999")
+                     , (1_000_000, "1.000000"), (1_234_567, "1.234567"), (MAX_COIN, "45000000000.000000")
+                     ];
+        for &(lovelace, ada) in values.iter() {
+            let coin = Coin::new(lovelace).unwrap();
+            assert_eq!(coin.to_ada_string(), ada);
+            assert_eq!(Coin::from_ada_string(ada), Ok(coin));
+        }
+    }
+
+    #[test]
+    fn ada_string_trailing_zeros() {
+        assert_eq!(Coin::from_ada_string("42"), Ok(Coin(42_000_000)));
+        assert_eq!(Coin::from_ada_string("42.5"), Ok(Coin(42_500_000)));
+        assert_eq!(Coin::from_ada_string("42.500"), Ok(Coin(42_500_000)));
+        assert_eq!(Coin::from_ada_string("42.500000"), Ok(Coin(42_500_000)));
+        assert_eq!(Coin::from_ada_string("0042.05"), Ok(Coin(42_050_000)));
+    }
+
+    #[test]
+    fn ada_string_rejects_rounding() {
+        assert_eq!(Coin::from_ada_string("1.0000001"), Err(ParseCoinError::TooManyDecimals(7)));
+        assert_eq!(Coin::from_ada_string("1.0000000"), Err(ParseCoinError::TooManyDecimals(7)));
+    }
+
+    #[test]
+    fn ada_string_invalid() {
+        for s in ["", ".", "1.", ".5", "1.2.3", "1,5", " 1", "1 ", "+1", "1e6", "abc"].iter() {
+            assert_eq!(Coin::from_ada_string(s), Err(ParseCoinError::InvalidFormat), "{:?}", s);
+        }
+        assert_eq!(Coin::from_ada_string("-1"), Err(ParseCoinError::Negative));
+        assert_eq!(Coin::from_ada_string("-0.5"), Err(ParseCoinError::Negative));
+    }
+
+    #[test]
+    fn ada_string_max_supply() {
+        assert_eq!(Coin::from_ada_string("45000000000"), Ok(Coin(MAX_COIN)));
+        assert_eq!(Coin::from_ada_string("45000000000.000001"), Err(ParseCoinError::OutOfBound));
+        assert_eq!(Coin::from_ada_string("45000000001"), Err(ParseCoinError::OutOfBound));
+        // above `u64::MAX` once converted to Lovelace
+        assert_eq!(Coin::from_ada_string("18446744073709551615"), Err(ParseCoinError::OutOfBound));
+        assert_eq!(Coin::from_ada_string("99999999999999999999999"), Err(ParseCoinError::OutOfBound));
+    }
+}
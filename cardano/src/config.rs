@@ -5,7 +5,9 @@
 //!
 
 use cbor_event::{self, de::RawCbor, se::{Serializer}};
-use std::fmt;
+use std::{fmt, result};
+use fee::LinearFee;
+use coin::Coin;
 
 /// this is the protocol magic number
 ///
@@ -57,21 +59,195 @@ impl cbor_event::Deserialize for ProtocolMagic {
     }
 }
 
+/// the maximum size, in bytes, of a serialized transaction (`TxAux`)
+/// accepted by the protocol.
+pub const DEFAULT_MAX_TX_SIZE: usize = 65536;
+
+#[cfg(feature = "generic-serialization")]
+fn default_max_tx_size() -> usize { DEFAULT_MAX_TX_SIZE }
+
+#[cfg(feature = "generic-serialization")]
+fn default_dust_threshold() -> Coin { Coin::zero() }
+
 /// Configuration for the wallet-crypto
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct Config {
-    pub protocol_magic: ProtocolMagic
+    pub protocol_magic: ProtocolMagic,
+    /// the maximum size of a serialized transaction, see `DEFAULT_MAX_TX_SIZE`
+    #[cfg_attr(feature = "generic-serialization", serde(default = "default_max_tx_size"))]
+    pub max_tx_size: usize,
+    /// the fee policy of the network, `LinearFee::default()` unless set
+    #[cfg_attr(feature = "generic-serialization", serde(default))]
+    pub fee_policy: LinearFee,
+    /// outputs of a value strictly below this threshold are considered dust,
+    /// `Coin::zero()` (no dust) unless set. A change below it is added to
+    /// the fee by the input selection (see `LinearFee::compute_with_limits`)
+    #[cfg_attr(feature = "generic-serialization", serde(default = "default_dust_threshold"))]
+    pub dust_threshold: Coin,
+    /// the maximum number of inputs selected for a transaction, no limit
+    /// (other than `max_tx_size`) unless set
+    #[cfg_attr(feature = "generic-serialization", serde(default))]
+    pub max_inputs: Option<usize>,
 }
 impl Config {
     pub fn new(protocol_magic: ProtocolMagic) -> Self {
         Config {
-            protocol_magic: protocol_magic
+            protocol_magic,
+            max_tx_size: DEFAULT_MAX_TX_SIZE,
+            fee_policy: LinearFee::default(),
+            dust_threshold: Coin::zero(),
+            max_inputs: None,
         }
     }
+
+    /// start building a customized configuration, see `ConfigBuilder`
+    pub fn builder() -> ConfigBuilder { ConfigBuilder::new() }
+
+    /// configuration of the mainnet
+    ///
+    /// ```
+    /// use cardano::config::{Config, ProtocolMagic, DEFAULT_MAX_TX_SIZE};
+    ///
+    /// assert_eq!(Config::mainnet(), Config::default());
+    /// assert_eq!(Config::mainnet().protocol_magic, ProtocolMagic::default());
+    /// assert_eq!(Config::mainnet().max_tx_size, DEFAULT_MAX_TX_SIZE);
+    /// ```
+    pub fn mainnet() -> Self {
+        Config::new(ProtocolMagic::default())
+    }
+
+    /// configuration of a test network with the given protocol magic
+    ///
+    /// ```
+    /// use cardano::config::{Config, ProtocolMagic};
+    ///
+    /// let config = Config::testnet(1097911063);
+    /// assert_eq!(config.protocol_magic, ProtocolMagic::from(1097911063));
+    /// assert!(config != Config::mainnet());
+    /// ```
+    pub fn testnet(protocol_magic: u32) -> Self {
+        Config::new(ProtocolMagic::from(protocol_magic))
+    }
 }
 impl Default for Config {
     fn default() -> Self {
         Config::new(ProtocolMagic::default())
     }
 }
+
+/// error when building an incoherent `Config`, see
+/// [`ConfigBuilder::build`](./struct.ConfigBuilder.html#method.build).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConfigError {
+    /// the fee policy has a zero size coefficient, so the fee would not
+    /// grow with the size of the transactions
+    ZeroFeeCoefficient,
+    /// the fee policy has a zero constant, so there would be no minimal fee
+    ZeroFeeConstant,
+    /// the maximum size of a transaction is 0
+    ZeroMaxTxSize,
+    /// the maximum number of inputs of a transaction is 0
+    ZeroMaxInputs,
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::ZeroFeeCoefficient => write!(f, "Invalid fee policy, the size coefficient is zero"),
+            ConfigError::ZeroFeeConstant => write!(f, "Invalid fee policy, the constant is zero"),
+            ConfigError::ZeroMaxTxSize => write!(f, "Invalid maximum transaction size of 0 bytes"),
+            ConfigError::ZeroMaxInputs => write!(f, "Invalid maximum number of inputs of 0"),
+        }
+    }
+}
+impl ::std::error::Error for ConfigError {}
+
+/// builder of a validated `Config`, every setting not set is the one of
+/// the mainnet (`Config::default()`).
+///
+/// ```
+/// use cardano::config::{Config, ConfigBuilder, ProtocolMagic};
+/// use cardano::coin::Coin;
+///
+/// let config = ConfigBuilder::new()
+///     .protocol_magic(1097911063)
+///     .dust_threshold(Coin::new(1000).unwrap())
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.protocol_magic, ProtocolMagic::from(1097911063));
+/// assert_eq!(config.fee_policy, Config::default().fee_policy);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+impl ConfigBuilder {
+    pub fn new() -> Self { ConfigBuilder { config: Config::default() } }
+
+    pub fn protocol_magic(mut self, protocol_magic: u32) -> Self {
+        self.config.protocol_magic = ProtocolMagic::from(protocol_magic);
+        self
+    }
+    pub fn max_tx_size(mut self, max_tx_size: usize) -> Self {
+        self.config.max_tx_size = max_tx_size;
+        self
+    }
+    pub fn fee_policy(mut self, fee_policy: LinearFee) -> Self {
+        self.config.fee_policy = fee_policy;
+        self
+    }
+    pub fn dust_threshold(mut self, dust_threshold: Coin) -> Self {
+        self.config.dust_threshold = dust_threshold;
+        self
+    }
+    pub fn max_inputs(mut self, max_inputs: usize) -> Self {
+        self.config.max_inputs = Some(max_inputs);
+        self
+    }
+
+    /// check the settings are coherent and return the configuration
+    pub fn build(self) -> result::Result<Config, ConfigError> {
+        if self.config.fee_policy.coefficient().0 == 0 { return Err(ConfigError::ZeroFeeCoefficient); }
+        if self.config.fee_policy.constant().0 == 0 { return Err(ConfigError::ZeroFeeConstant); }
+        if self.config.max_tx_size == 0 { return Err(ConfigError::ZeroMaxTxSize); }
+        if self.config.max_inputs == Some(0) { return Err(ConfigError::ZeroMaxInputs); }
+        Ok(self.config)
+    }
+}
+impl Default for ConfigBuilder {
+    fn default() -> Self { ConfigBuilder::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fee::Milli;
+
+    #[test]
+    fn build_valid_config() {
+        let fee = LinearFee::new(Milli::integral(1), Milli::integral(2));
+        let config = ConfigBuilder::new()
+            .protocol_magic(633343913)
+            .fee_policy(fee)
+            .dust_threshold(Coin::new(42).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(config.protocol_magic, ProtocolMagic::from(633343913));
+        assert_eq!(config.fee_policy, fee);
+        assert_eq!(config.dust_threshold, Coin::new(42).unwrap());
+        assert_eq!(config.max_tx_size, DEFAULT_MAX_TX_SIZE);
+
+        assert_eq!(ConfigBuilder::new().build(), Ok(Config::default()));
+    }
+
+    #[test]
+    fn build_invalid_config() {
+        let zero_slope = LinearFee::new(Milli::integral(155381), Milli(0));
+        assert_eq!(ConfigBuilder::new().fee_policy(zero_slope).build(), Err(ConfigError::ZeroFeeCoefficient));
+        let zero_constant = LinearFee::new(Milli(0), Milli::integral(43));
+        assert_eq!(ConfigBuilder::new().fee_policy(zero_constant).build(), Err(ConfigError::ZeroFeeConstant));
+        assert_eq!(ConfigBuilder::new().max_tx_size(0).build(), Err(ConfigError::ZeroMaxTxSize));
+        assert_eq!(ConfigBuilder::new().max_inputs(0).build(), Err(ConfigError::ZeroMaxInputs));
+        assert_eq!(ConfigBuilder::new().max_inputs(3).build().unwrap().max_inputs, Some(3));
+    }
+}
@@ -4,6 +4,7 @@ use std::{result, ops::{Add, Mul}};
 use coin;
 use coin::{Coin};
 use tx::{Tx, TxInWitness, TxAux, txaux_serialize};
+use txutils::{Input, Outputs};
 use cbor_event;
 
 /// A fee value that represent either a fee to pay, or a fee paid.
@@ -13,6 +14,37 @@ pub struct Fee(Coin);
 impl Fee {
     pub fn new(coin: Coin) -> Self { Fee(coin) }
     pub fn to_coin(&self) -> Coin { self.0 }
+
+    /// split the fee between the outputs, proportionally to their value.
+    ///
+    /// The shares are rounded down and what is left is attributed to the
+    /// largest output (the first one if several have the same value) so the
+    /// shares always sum up to the fee. If all the outputs are of value 0,
+    /// the whole fee is attributed to the first output.
+    ///
+    /// Returns one share per output, in the order of the outputs.
+    pub fn split_by_output(&self, outputs: &Outputs) -> Vec<Coin> {
+        let fee = *self.0 as u128;
+        let total : u128 = outputs.iter().map(|o| *o.value as u128).sum();
+
+        let mut shares : Vec<u128> = outputs.iter().map(|o| {
+            (fee * *o.value as u128).checked_div(total).unwrap_or(0)
+        }).collect();
+
+        let largest = outputs.iter().enumerate()
+            .fold(None, |acc : Option<(usize, Coin)>, (i, o)| match acc {
+                Some((_, value)) if value >= o.value => acc,
+                _ => Some((i, o.value)),
+            });
+        if let Some((i, _)) = largest {
+            let sum : u128 = shares.iter().sum();
+            shares[i] += fee - sum;
+        }
+
+        shares.into_iter()
+              .map(|share| Coin::new(share as u64).expect("share is below the fee"))
+              .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -46,7 +78,7 @@ impl ::std::fmt::Display for Error {
     }
 }
 
-#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct Milli (pub u64);
 impl Milli {
@@ -84,7 +116,7 @@ impl Mul for Milli {
 }
 
 /// Linear fee using the basic affine formula `A * bytes(txaux) + CONSTANT`
-#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct LinearFee {
     /// this is the minimal fee
@@ -97,6 +129,9 @@ impl LinearFee {
         LinearFee { constant: constant, coefficient: coefficient }
     }
 
+    pub fn constant(&self) -> Milli { self.constant }
+    pub fn coefficient(&self) -> Milli { self.coefficient }
+
     pub fn estimate(&self, sz: usize) -> Result<Fee> {
         let msz = Milli::integral(sz as u64);
         let fee = self.constant + self.coefficient * msz;
@@ -109,6 +144,17 @@ impl LinearFee {
 pub trait FeeAlgorithm {
     fn calculate_for_txaux(&self, txaux: &TxAux) -> Result<Fee>;
     fn calculate_for_txaux_component(&self, tx: &Tx, witnesses: &Vec<TxInWitness>) -> Result<Fee>;
+
+    /// estimate the fee of an already built, unsigned, transaction once
+    /// signed with `num_witnesses` witnesses.
+    ///
+    /// The witnesses are estimated with fake witnesses (see
+    /// [`TxInWitness::fake`](../tx/enum.TxInWitness.html#method.fake)),
+    /// no input selection is performed.
+    fn estimate_for_tx(&self, tx: &Tx, num_witnesses: usize) -> Result<Fee> {
+        let fake_witnesses : Vec<TxInWitness> = ::std::iter::repeat_n(TxInWitness::fake(), num_witnesses).collect();
+        self.calculate_for_txaux_component(tx, &fake_witnesses)
+    }
 }
 
 impl FeeAlgorithm for LinearFee {
@@ -129,9 +175,49 @@ impl Default for LinearFee {
     fn default() -> Self { LinearFee::new(Milli::integral(155381), Milli::new(43,946)) }
 }
 
+/// compute the fees of the same transaction with two different fee algorithms
+///
+/// The transaction is expected to be unsigned: the witnesses are estimated
+/// with one fake witness per input (see [`TxInWitness::fake`](../tx/enum.TxInWitness.html#method.fake)).
+/// This is handy to evaluate the impact of a change of the fee parameters.
+///
+pub fn compare(a: &dyn FeeAlgorithm, b: &dyn FeeAlgorithm, tx: &Tx) -> Result<(Fee, Fee)> {
+    let fee_a = a.estimate_for_tx(tx, tx.inputs.len())?;
+    let fee_b = b.estimate_for_tx(tx, tx.inputs.len())?;
+    Ok((fee_a, fee_b))
+}
+
+/// compute the fewest number of inputs needed to reach the `target` value
+///
+/// The candidates are considered from the largest value to the smallest
+/// one, so this is a lower bound for any selection algorithm. The fees
+/// are not taken into account: add the estimated fee to the target if needed.
+///
+/// Returns `None` if the total of all the candidates does not reach the target.
+///
+pub fn min_inputs_for<Addressing>(candidates: &[Input<Addressing>], target: Coin) -> Option<usize> {
+    let mut values : Vec<Coin> = candidates.iter().map(|input| input.value()).collect();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut total = Coin::zero();
+    if total >= target { return Some(0); }
+    for (i, value) in values.into_iter().enumerate() {
+        total = match total + value {
+            Ok(total) => total,
+            // the sum is above the maximum supply, so it is above the target too
+            Err(_) => return Some(i + 1),
+        };
+        if total >= target { return Some(i + 1); }
+    }
+    None
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use tx::{TxId, TxoPointer, TxOut};
+    use txutils::{Input};
+    use test_helpers::address;
 
     fn test_milli_add_eq(v1: u64, v2: u64) {
         let v = v1 + v2;
@@ -164,4 +250,96 @@ mod test {
         test_milli_mul_eq(         241, 900001_900);
         test_milli_mul_eq(         241,        400);
     }
+
+    #[test]
+    fn compare_linear_fees() {
+        let addr = address();
+
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 0));
+        tx.add_input(TxoPointer::new(TxId::new(&[1;32]), 1));
+        tx.add_output(TxOut::new(addr, Coin::new(1_000_000).unwrap()));
+
+        let current = LinearFee::default();
+        let cheaper = LinearFee::new(Milli::integral(100000), Milli::new(20, 0));
+
+        let (fee_current, fee_cheaper) = compare(&current, &cheaper, &tx).unwrap();
+
+        let witnesses = vec![TxInWitness::fake(), TxInWitness::fake()];
+        assert_eq!(fee_current, current.calculate_for_txaux_component(&tx, &witnesses).unwrap());
+        assert_eq!(fee_cheaper, cheaper.calculate_for_txaux_component(&tx, &witnesses).unwrap());
+        assert!(fee_cheaper < fee_current);
+    }
+
+    #[test]
+    fn min_inputs_for_largest_first() {
+        let addr = address();
+
+        let utxos : Vec<Input<()>> = [10, 500, 40, 200, 1_000].iter().enumerate().map(|(i, v)| {
+            let ptr = TxoPointer::new(TxId::new(&[i as u8;32]), i as u32);
+            Input::new(ptr, TxOut::new(addr.clone(), Coin::new(*v).unwrap()), ())
+        }).collect();
+
+        let min = |target| min_inputs_for(&utxos, Coin::new(target).unwrap());
+        assert_eq!(min(0), Some(0));
+        assert_eq!(min(1_000), Some(1));
+        assert_eq!(min(1_001), Some(2));
+        assert_eq!(min(1_700), Some(3));
+        assert_eq!(min(1_750), Some(5));
+        assert_eq!(min(1_751), None);
+        assert_eq!(min_inputs_for::<()>(&[], Coin::new(1).unwrap()), None);
+    }
+
+    #[test]
+    fn split_fee_by_output() {
+        let addr = address();
+        let outputs = |values: &[u64]| -> Outputs {
+            values.iter().map(|v| TxOut::new(addr.clone(), Coin::new(*v).unwrap())).collect::<Vec<_>>().into()
+        };
+        let fee = Fee::new(Coin::new(1_000).unwrap());
+        let split = |values: &[u64]| -> Vec<u64> {
+            fee.split_by_output(&outputs(values)).iter().map(|c| **c).collect()
+        };
+
+        // 285.71 + 571.43 + 142.86: the 2 lovelaces left go to the largest output
+        let shares = split(&[2_000, 4_000, 1_000]);
+        assert_eq!(shares, vec![285, 573, 142]);
+        assert_eq!(shares.iter().sum::<u64>(), 1_000);
+
+        assert_eq!(split(&[1, 1, 1]), vec![334, 333, 333]);
+        assert_eq!(split(&[0, 0]), vec![1_000, 0]);
+        assert_eq!(split(&[]), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn estimate_for_tx_is_linear() {
+        let addr = address();
+        let alg = LinearFee::default();
+
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 0));
+
+        // the fees are rounded up to the next integral value so the
+        // increments may differ from one lovelace.
+        fn assert_linear(fees: &[Fee]) {
+            let steps : Vec<u64> = fees.windows(2)
+                .map(|w| *w[1].to_coin() - *w[0].to_coin())
+                .collect();
+            assert!(steps.iter().all(|s| *s > 0));
+            let min = steps.iter().min().unwrap();
+            let max = steps.iter().max().unwrap();
+            assert!(max - min <= 1, "steps are not constant: {:?}", steps);
+        }
+
+        let mut fees = Vec::new();
+        for _ in 0..5 {
+            tx.add_output(TxOut::new(addr.clone(), Coin::new(1_000_000).unwrap()));
+            fees.push(alg.estimate_for_tx(&tx, 1).unwrap());
+        }
+        assert_linear(&fees);
+
+        let fees : Vec<Fee> = (1..6).map(|n| alg.estimate_for_tx(&tx, n).unwrap()).collect();
+        assert_linear(&fees);
+        assert_eq!(fees[0], alg.calculate_for_txaux_component(&tx, &vec![TxInWitness::fake()]).unwrap());
+    }
 }
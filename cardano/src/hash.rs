@@ -102,7 +102,7 @@ macro_rules! define_hash_object {
         }
         impl cbor_event::de::Deserialize for $hash_ty {
             fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-                let bytes = raw.bytes()?;
+                let bytes = ::cbor::hs::util::checked_bytes(raw)?;
                 match Self::try_from_slice(&bytes) {
                     Ok(digest) => Ok(digest),
                     Err(Error::InvalidHashSize(sz, expected)) => Err(cbor_event::Error::NotEnough(sz, expected)),
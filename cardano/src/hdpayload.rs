@@ -17,6 +17,7 @@ use std::{iter::repeat, ops::{Deref}, fmt};
 
 use hdwallet::{XPub};
 use cbor_event::{self, de::RawCbor, se::{self, Serializer}};
+use cbor::hs::util::checked_bytes;
 
 use util::{securemem, hex};
 
@@ -68,6 +69,10 @@ impl AsRef<[u32]> for Path {
 }
 impl Path {
     pub fn new(v: Vec<u32>) -> Self { Path(v) }
+    /// create a path from the given derivation indices, in order
+    pub fn from_indices(indices: &[u32]) -> Self { Path(indices.to_vec()) }
+    /// the derivation indices of the path, in order
+    pub fn as_indices(&self) -> &[u32] { &self.0 }
     fn from_cbor(bytes: &[u8]) -> Result<Self> {
         let mut raw = RawCbor::from(bytes);
         Ok(cbor_event::de::Deserialize::deserialize(&mut raw)?)
@@ -77,6 +82,17 @@ impl Path {
             .expect("Serialize the given Path in cbor")
     }
 }
+/// display the derivation indices, e.g. `[2147483648, 2147483653]`
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, index) in self.0.iter().enumerate() {
+            if i > 0 { write!(f, ", ")?; }
+            write!(f, "{}", index)?;
+        }
+        write!(f, "]")
+    }
+}
 impl cbor_event::se::Serialize for Path {
     fn serialize<W: ::std::io::Write>(&self, serializer: Serializer<W>) -> cbor_event::Result<Serializer<W>> {
         se::serialize_indefinite_array(self.0.iter(), serializer)
@@ -134,8 +150,8 @@ impl HDKey {
     }
 
     pub fn decrypt(&self, input: &[u8]) -> Result<Vec<u8>> {
+        if input.len() <= TAG_LEN { return Err(Error::NotEnoughEncryptedData); };
         let len = input.len() - TAG_LEN;
-        if len <= 0 { return Err(Error::NotEnoughEncryptedData); };
 
         let mut ctx = ChaCha20Poly1305::new(self.as_ref(), &NONCE[..], &[]);
 
@@ -190,8 +206,8 @@ impl cbor_event::se::Serialize for HDAddressPayload {
 }
 impl cbor_event::de::Deserialize for HDAddressPayload {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        let mut raw_encoded = RawCbor::from(&raw.bytes()?);
-        Ok(HDAddressPayload::from_bytes(&mut raw_encoded.bytes()?))
+        let mut raw_encoded = RawCbor::from(&checked_bytes(raw)?);
+        Ok(HDAddressPayload::from_bytes(&checked_bytes(&mut raw_encoded)?))
     }
 }
 impl Deref for HDAddressPayload {
@@ -221,6 +237,18 @@ mod tests {
         assert_eq!(bytes, key.decrypt(&payload).unwrap())
     }
 
+    #[test]
+    fn decrypt_too_short() {
+        let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+        let key = HDKey::new(&hdwallet::XPrv::generate_from_seed(&seed).public());
+        for len in 0..TAG_LEN + 1 {
+            match key.decrypt(&vec![0u8; len]) {
+                Err(Error::NotEnoughEncryptedData) => {},
+                r => panic!("expected NotEnoughEncryptedData for {} bytes, got {:?}", len, r),
+            }
+        }
+    }
+
     #[test]
     fn path_cbor_encoding() {
         let path = Path::new(vec![0,1,2]);
@@ -228,6 +256,15 @@ mod tests {
         assert_eq!(path, Path::from_cbor(cbor.as_ref()).unwrap());
     }
 
+    #[test]
+    fn path_display_and_indices() {
+        let path = Path::from_indices(&[44, 1815, 0, 0, 5]);
+        assert_eq!(path.to_string(), "[44, 1815, 0, 0, 5]");
+        assert_eq!(path.as_indices(), &[44, 1815, 0, 0, 5]);
+        assert_eq!(Path::from_indices(path.as_indices()), path);
+        assert_eq!(Path::from_indices(&[]).to_string(), "[]");
+    }
+
     #[test]
     fn hdpayload() {
         let path = Path::new(vec![0,1,2]);
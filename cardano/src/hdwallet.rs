@@ -10,6 +10,7 @@
 //!
 use cryptoxide::digest::Digest;
 use cryptoxide::sha2::Sha512;
+use cryptoxide::blake2b::Blake2b;
 use cryptoxide::hmac::Hmac;
 use cryptoxide::mac::Mac;
 use cryptoxide::curve25519::{GeP3, ge_scalarmult_base, sc_reduce};
@@ -25,6 +26,7 @@ use std::hash::{Hash, Hasher};
 use util::{hex, securemem};
 
 use cbor_event::{self, de::RawCbor, se::{Serializer}};
+use cbor::hs::util::checked_bytes;
 
 #[cfg(feature = "generic-serialization")]
 use serde;
@@ -45,6 +47,12 @@ pub enum Error {
     ///
     /// See `SEED_SIZE` for details about the expected size.
     InvalidSeedSize(usize),
+    /// the given seed is only made of zeros, see `Seed::from_slice_checked`
+    NullSeed,
+    /// the given entropy is of invalid size, the parameter is the given size
+    ///
+    /// See `Seed::from_entropy` for the supported sizes.
+    InvalidEntropySize(usize),
     /// the given extended private key is of invalid size. The parameter is the given size.
     ///
     /// See `XPRV_SIZE` for the expected size.
@@ -72,6 +80,12 @@ impl fmt::Display for Error {
             &Error::InvalidSeedSize(sz) => {
                write!(f, "Invalid Seed Size, expected {} bytes, but received {} bytes.", SEED_SIZE, sz)
             },
+            &Error::NullSeed => {
+               write!(f, "Invalid Seed, all the bytes are zero.")
+            },
+            &Error::InvalidEntropySize(sz) => {
+               write!(f, "Invalid Entropy Size, expected 16, 20, 24, 28 or 32 bytes, but received {} bytes.", sz)
+            },
             &Error::InvalidXPrvSize(sz) => {
                write!(f, "Invalid XPrv Size, expected {} bytes, but received {} bytes.", XPRV_SIZE, sz)
             },
@@ -158,6 +172,50 @@ impl Seed {
         v[..].clone_from_slice(buf);
         Ok(Seed::from_bytes(v))
     }
+
+    /// same as [`from_slice`](#method.from_slice) but also reject a seed
+    /// only made of zeros (e.g. an uninitialized buffer), which would give
+    /// a wallet anyone can derive.
+    ///
+    /// ```
+    /// use cardano::hdwallet::{Seed, SEED_SIZE};
+    ///
+    /// assert!(Seed::from_slice_checked(&[0x2a;SEED_SIZE]).is_ok());
+    /// assert!(Seed::from_slice_checked(&[0x2a;31]).is_err());
+    /// assert!(Seed::from_slice_checked(&[0u8;SEED_SIZE]).is_err());
+    /// ```
+    pub fn from_slice_checked(buf: &[u8]) -> Result<Self> {
+        let seed = Seed::from_slice(buf)?;
+        if seed.0.iter().all(|b| *b == 0) {
+            return Err(Error::NullSeed);
+        }
+        Ok(seed)
+    }
+
+    /// create a Seed from the given entropy (e.g. from a hardware random
+    /// number generator). The entropy is expected to be of 16, 20, 24, 28
+    /// or 32 bytes (the same sizes as the BIP39 entropy).
+    ///
+    /// The seed is the Blake2b 256 digest of the entropy, so the same
+    /// entropy always gives the same seed.
+    ///
+    /// ```
+    /// use cardano::hdwallet::{Seed, SEED_SIZE};
+    ///
+    /// assert!(Seed::from_entropy(&[0u8;16]).is_ok());
+    /// assert!(Seed::from_entropy(&[0u8;17]).is_err());
+    /// ```
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self> {
+        match entropy.len() {
+            16 | 20 | 24 | 28 | 32 => {},
+            sz => return Err(Error::InvalidEntropySize(sz)),
+        }
+        let mut b2b = Blake2b::new(SEED_SIZE);
+        let mut v = [0u8;SEED_SIZE];
+        Digest::input(&mut b2b, entropy);
+        Digest::result(&mut b2b, &mut v);
+        Ok(Seed::from_bytes(v))
+    }
 }
 impl AsRef<[u8]> for Seed {
     fn as_ref(&self) -> &[u8] { &self.0 }
@@ -491,7 +549,7 @@ impl cbor_event::se::Serialize for XPub {
 }
 impl cbor_event::de::Deserialize for XPub {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        let bytes = raw.bytes()?;
+        let bytes = checked_bytes(raw)?;
         match XPub::from_slice(&bytes) {
             Ok(pk) => Ok(pk),
             Err(Error::InvalidXPubSize(sz)) => Err(cbor_event::Error::NotEnough(sz, XPUB_SIZE)),
@@ -612,7 +670,7 @@ impl<T> cbor_event::se::Serialize for Signature<T> {
 }
 impl<T> cbor_event::de::Deserialize for Signature<T> {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        let bytes = raw.bytes()?;
+        let bytes = checked_bytes(raw)?;
         match Signature::from_slice(&bytes) {
             Ok(signature) => Ok(signature),
             Err(Error::InvalidSignatureSize(sz)) => Err(cbor_event::Error::NotEnough(sz, SIGNATURE_SIZE)),
@@ -1017,6 +1075,54 @@ mod tests {
         seed_xprv_eq(&seed, &D1);
     }
 
+    #[test]
+    fn seed_from_entropy_valid_sizes() {
+        for sz in [16, 20, 24, 28, 32].iter() {
+            let entropy = vec![0x2a;*sz];
+            let seed = Seed::from_entropy(&entropy).unwrap();
+            // deterministic
+            assert_eq!(seed.as_ref(), Seed::from_entropy(&entropy).unwrap().as_ref());
+        }
+        let s1 = Seed::from_entropy(&[0x01;16]).unwrap();
+        let s2 = Seed::from_entropy(&[0x02;16]).unwrap();
+        assert!(s1.as_ref() != s2.as_ref());
+    }
+
+    #[test]
+    fn seed_from_slice_checked() {
+        let mut bytes = [0u8;SEED_SIZE];
+        bytes[SEED_SIZE - 1] = 1;
+        let seed = Seed::from_slice_checked(&bytes).unwrap();
+        assert_eq!(seed.as_ref(), &bytes[..]);
+
+        for sz in [0, 31, 33].iter() {
+            match Seed::from_slice_checked(&vec![0x2a;*sz]) {
+                Err(Error::InvalidSeedSize(found)) => assert_eq!(found, *sz),
+                Err(err) => panic!("unexpected error: {}", err),
+                Ok(_) => panic!("seed of {} bytes should be rejected", sz),
+            }
+        }
+        match Seed::from_slice_checked(&[0u8;SEED_SIZE]) {
+            Err(Error::NullSeed) => {},
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("null seed should be rejected"),
+        }
+        // the unchecked constructors still accept it
+        assert!(Seed::from_slice(&[0u8;SEED_SIZE]).is_ok());
+    }
+
+    #[test]
+    fn seed_from_entropy_invalid_sizes() {
+        for sz in [0, 1, 15, 17, 31, 33, 64].iter() {
+            let entropy = vec![0x2a;*sz];
+            match Seed::from_entropy(&entropy) {
+                Err(Error::InvalidEntropySize(found)) => assert_eq!(found, *sz),
+                Err(err) => panic!("unexpected error: {}", err),
+                Ok(_) => panic!("entropy of {} bytes should be rejected", sz),
+            }
+        }
+    }
+
     fn derive_xprv_eq(parent_xprv: &XPrv, idx: DerivationIndex, expected_xprv: [u8; 96]) {
         let child_xprv = derive_private(parent_xprv, idx, DerivationScheme::V2);
         compare_xprv(child_xprv.as_ref(), &expected_xprv);
@@ -1,29 +1,44 @@
-use std::{fmt, result};
+use std::{fmt, result, collections::BTreeSet};
 use coin::{self, Coin};
-use tx::{TxOut, Tx};
-use txutils::{Input, OutputPolicy, output_sum};
+use tx::{TxOut, Tx, TxAttributes, TxInWitness, TxoPointer, txaux_serialize_size};
+use txutils::{self, Input, OutputPolicy};
 use cbor_event;
-use fee::{self, Fee, LinearFee};
+use fee::{self, Fee, FeeAlgorithm, LinearFee};
+use config::Config;
 
 #[derive(Debug)]
 pub enum Error {
     NoInputs,
     NoOutputs,
     NotEnoughInput,
+    /// the sum of the inputs or of the outputs is above `MAX_COIN`
+    CoinOverflow,
     CoinError(coin::Error),
     FeeError(fee::Error),
     CborError(cbor_event::Error),
+    /// the transaction, once signed, would be above the maximum
+    /// transaction size (see `config::Config::max_tx_size`)
+    TransactionTooLarge { size: usize, max: usize },
+    /// the same input is given more than once, it cannot be spent twice
+    /// by a transaction
+    DuplicateInput(TxoPointer),
+    /// the input of the transaction to rebuild is not in the given inputs
+    MissingInput(TxoPointer),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            &Error::NoInputs => write!(f, "No inputs given for fee estimation"),
-            &Error::NoOutputs => write!(f, "No outputs given for fee estimation"),
-            &Error::NotEnoughInput => write!(f, "Not enough funds to cover outputs and fees"),
-            &Error::CoinError(_) => write!(f, "Error on coin operations"),
-            &Error::CborError(_) => write!(f, "Error while performing cbor serialization"),
-            &Error::FeeError(_) => write!(f, "Error on fee operations"),
+            Error::NoInputs => write!(f, "No inputs given for fee estimation"),
+            Error::NoOutputs => write!(f, "No outputs given for fee estimation"),
+            Error::NotEnoughInput => write!(f, "Not enough funds to cover outputs and fees"),
+            Error::CoinOverflow => write!(f, "Total value of the coins is above the maximum supply"),
+            Error::CoinError(_) => write!(f, "Error on coin operations"),
+            Error::CborError(_) => write!(f, "Error while performing cbor serialization"),
+            Error::FeeError(_) => write!(f, "Error on fee operations"),
+            Error::TransactionTooLarge { size, max } => write!(f, "Transaction of {} bytes is above the maximum transaction size of {} bytes", size, max),
+            Error::DuplicateInput(ref ptr) => write!(f, "The input {} is given more than once", ptr),
+            Error::MissingInput(ref ptr) => write!(f, "The input {} of the transaction is not in the given inputs", ptr),
         }
     }
 }
@@ -53,6 +68,21 @@ impl ::std::error::Error for Error {
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// check that no input is given twice, returning `Error::DuplicateInput`
+/// with the first repeated input otherwise.
+pub fn check_duplicate_inputs<'a, I, Addressing>(inputs: I) -> Result<()>
+    where I: Iterator<Item = &'a Input<Addressing>>
+        , Addressing: 'a
+{
+    let mut seen = BTreeSet::new();
+    for input in inputs {
+        if !seen.insert(&input.ptr) {
+            return Err(Error::DuplicateInput(input.ptr.clone()));
+        }
+    }
+    Ok(())
+}
+
 /// Algorithm trait for input selections
 pub trait SelectionAlgorithm {
     /// This takes from input:
@@ -77,10 +107,24 @@ pub trait SelectionAlgorithm {
             , O : 'b + Iterator<Item = &'b TxOut> + Clone
             , Addressing: 'a
     ;
+
+    /// same as `compute` but within the limits of the given configuration:
+    /// the transaction cannot be larger than `config.max_tx_size` and at
+    /// most `config.max_inputs` inputs are selected, if given.
+    fn compute_with_config<'a, 'b, I, O, Addressing>( &self
+                                                    , policy: SelectionPolicy
+                                                    , inputs: I
+                                                    , outputs: O
+                                                    , output_policy: &OutputPolicy
+                                                    , config: &Config
+                                                    )
+            -> Result<(Fee, Vec<&'a Input<Addressing>>, Coin)>
+        where I : 'a + Iterator<Item = &'a Input<Addressing>> + ExactSizeIterator
+            , O : 'b + Iterator<Item = &'b TxOut> + Clone
+            , Addressing: 'a
+    ;
 }
 
-const TX_IN_WITNESS_CBOR_SIZE: usize = 140;
-const CBOR_TXAUX_OVERHEAD: usize = 51;
 impl SelectionAlgorithm for LinearFee {
     fn compute<'a, 'b, I, O, Addressing>( &self
                                         , policy: SelectionPolicy
@@ -92,16 +136,122 @@ impl SelectionAlgorithm for LinearFee {
         where I : 'a + Iterator<Item = &'a Input<Addressing>> + ExactSizeIterator
             , O : 'b + Iterator<Item = &'b TxOut> + Clone
             , Addressing: 'a
+    {
+        self.compute_with_attributes(policy, inputs, outputs, output_policy, &TxAttributes::new())
+    }
+
+    fn compute_with_config<'a, 'b, I, O, Addressing>( &self
+                                                    , policy: SelectionPolicy
+                                                    , inputs: I
+                                                    , outputs: O
+                                                    , output_policy: &OutputPolicy
+                                                    , config: &Config
+                                                    )
+            -> Result<(Fee, Vec<&'a Input<Addressing>>, Coin)>
+        where I : 'a + Iterator<Item = &'a Input<Addressing>> + ExactSizeIterator
+            , O : 'b + Iterator<Item = &'b TxOut> + Clone
+            , Addressing: 'a
+    {
+        self.compute_with_limits(policy, inputs, outputs, output_policy, &TxAttributes::new(), config)
+    }
+}
+
+impl LinearFee {
+    /// perform the input selection of
+    /// [`SelectionAlgorithm::compute`](./trait.SelectionAlgorithm.html#tymethod.compute)
+    /// for a transaction with the given attributes (see
+    /// [`TxAttributes`](../tx/struct.TxAttributes.html)).
+    ///
+    /// the fee is computed from the size of the transaction signed with
+    /// fake witnesses (see [`TxInWitness::fake`](../tx/enum.TxInWitness.html#method.fake),
+    /// of the same size as the actual witnesses) and including the change
+    /// output.
+    ///
+    /// The change is estimated first, the fee is then computed with this
+    /// estimated change. As the actual change can only be smaller than the
+    /// estimation, the change output can only be smaller once serialized
+    /// too: the fee covers the size of the final transaction, overpaying at
+    /// most the few bytes of the change value encoding.
+    pub fn compute_with_attributes<'a, 'b, I, O, Addressing>( &self
+                                                            , policy: SelectionPolicy
+                                                            , inputs: I
+                                                            , outputs: O
+                                                            , output_policy: &OutputPolicy
+                                                            , attributes: &TxAttributes
+                                                            )
+            -> Result<(Fee, Vec<&'a Input<Addressing>>, Coin)>
+        where I : 'a + Iterator<Item = &'a Input<Addressing>> + ExactSizeIterator
+            , O : 'b + Iterator<Item = &'b TxOut> + Clone
+            , Addressing: 'a
+    {
+        self.compute_with_max_size(policy, inputs, outputs, output_policy, attributes, usize::MAX)
+    }
+
+    /// same as [`compute_with_attributes`](./struct.LinearFee.html#method.compute_with_attributes)
+    /// but the signed transaction cannot be larger than `max_tx_size` bytes
+    /// (see `config::Config::max_tx_size`).
+    ///
+    /// The inputs are no longer selected as soon as the transaction is too
+    /// large and an `Error::TransactionTooLarge` is returned: adding more
+    /// inputs would only make it larger.
+    ///
+    /// Returns `Error::NoInputs` or `Error::NoOutputs` if there are no
+    /// inputs or no outputs. All the inputs are checked for duplicates
+    /// first, see [`check_duplicate_inputs`](./fn.check_duplicate_inputs.html).
+    pub fn compute_with_max_size<'a, 'b, I, O, Addressing>( &self
+                                                          , policy: SelectionPolicy
+                                                          , inputs: I
+                                                          , outputs: O
+                                                          , output_policy: &OutputPolicy
+                                                          , attributes: &TxAttributes
+                                                          , max_tx_size: usize
+                                                          )
+            -> Result<(Fee, Vec<&'a Input<Addressing>>, Coin)>
+        where I : 'a + Iterator<Item = &'a Input<Addressing>> + ExactSizeIterator
+            , O : 'b + Iterator<Item = &'b TxOut> + Clone
+            , Addressing: 'a
+    {
+        let config = Config { max_tx_size, ..Config::default() };
+        self.compute_with_limits(policy, inputs, outputs, output_policy, attributes, &config)
+    }
+
+    /// same as [`compute_with_max_size`](./struct.LinearFee.html#method.compute_with_max_size)
+    /// with the limits of the given configuration: the transaction cannot
+    /// be larger than `config.max_tx_size` and at most `config.max_inputs`
+    /// inputs are selected, if given. The fee policy of the configuration
+    /// is not used, the fees are computed with `self`.
+    ///
+    /// A change below `config.dust_threshold` is not worth an output of its
+    /// own: it is added to the fee and the returned change is zero.
+    ///
+    /// Returns `Error::NotEnoughInput` if the first `max_inputs` inputs
+    /// selected do not cover the outputs and the fee.
+    pub fn compute_with_limits<'a, 'b, I, O, Addressing>( &self
+                                                        , policy: SelectionPolicy
+                                                        , inputs: I
+                                                        , outputs: O
+                                                        , output_policy: &OutputPolicy
+                                                        , attributes: &TxAttributes
+                                                        , config: &Config
+                                                        )
+            -> Result<(Fee, Vec<&'a Input<Addressing>>, Coin)>
+        where I : 'a + Iterator<Item = &'a Input<Addressing>> + ExactSizeIterator
+            , O : 'b + Iterator<Item = &'b TxOut> + Clone
+            , Addressing: 'a
     {
         if inputs.len() == 0 { return Err(Error::NoInputs); }
+        if outputs.clone().next().is_none() { return Err(Error::NoOutputs); }
+        let inputs : Vec<&'a Input<Addressing>> = inputs.collect();
+        check_duplicate_inputs(inputs.iter().cloned())?;
 
-        let output_value = output_sum(outputs.clone())?;
+        let output_value = txutils::output_sum(outputs.clone()).map_err(|_| Error::CoinOverflow)?;
         let mut fee = self.estimate(0)?;
         let mut input_value = Coin::zero();
         let mut selected_inputs = Vec::new();
 
         // create the Tx on the fly
         let mut txins = Vec::new();
+        let mut fake_witnesses = Vec::new();
         let     txouts : Vec<TxOut> = outputs.cloned().collect();
 
         // for now we only support this selection algorithm
@@ -109,30 +259,33 @@ impl SelectionAlgorithm for LinearFee {
         // granulated selection policy
         assert!(policy == SelectionPolicy::FirstMatchFirst);
 
-        for input in inputs {
-            input_value = (input_value + input.value())?;
+        let max_tx_size = config.max_tx_size;
+        for input in inputs.into_iter().take(config.max_inputs.unwrap_or(usize::MAX)) {
+            input_value = input_value.checked_add(input.value()).ok_or(Error::CoinOverflow)?;
             selected_inputs.push(input);
             txins.push(input.ptr.clone());
+            fake_witnesses.push(TxInWitness::fake());
 
-            // calculate fee from the Tx serialised + estimated size for signing
+            // calculate fee from the Tx serialised with the fake witnesses
             let mut tx = Tx::new_with(txins.clone(), txouts.clone());
-            let txbytes = cbor!(&tx)?;
-
-            let estimated_fee = (self.estimate(txbytes.len() + CBOR_TXAUX_OVERHEAD + (TX_IN_WITNESS_CBOR_SIZE * selected_inputs.len())))?;
+            tx.set_attributes(attributes.clone());
+            let estimated_fee = self.calculate_for_txaux_component(&tx, &fake_witnesses)?;
 
             // add the change in the estimated fee
-            if let Ok(change_value) = output_value - input_value - estimated_fee.to_coin() {
-                if change_value > Coin::zero() {
+            if let Ok(change_value) = input_value - output_value - estimated_fee.to_coin() {
+                if change_value > Coin::zero() && change_value >= config.dust_threshold {
                     match output_policy {
                         OutputPolicy::One(change_addr) => tx.add_output(TxOut::new(change_addr.clone(), change_value)),
                     }
                 }
             };
 
-            let txbytes = cbor!(&tx)?;
-            let corrected_fee = self.estimate(txbytes.len() + CBOR_TXAUX_OVERHEAD + (TX_IN_WITNESS_CBOR_SIZE * selected_inputs.len()));
+            fee = self.calculate_for_txaux_component(&tx, &fake_witnesses)?;
 
-            fee = corrected_fee?;
+            let size = txaux_serialize_size(&tx, &fake_witnesses);
+            if size > max_tx_size {
+                return Err(Error::TransactionTooLarge { size, max: max_tx_size });
+            }
 
             if Ok(input_value) >= (output_value + fee.to_coin()) { break; }
         }
@@ -141,7 +294,13 @@ impl SelectionAlgorithm for LinearFee {
             return Err(Error::NotEnoughInput);
         }
 
-        Ok((fee, selected_inputs, (input_value - output_value - fee.to_coin())?))
+        let change = (input_value - output_value - fee.to_coin())?;
+        if change < config.dust_threshold {
+            let fee = Fee::new((input_value - output_value)?);
+            return Ok((fee, selected_inputs, Coin::zero()));
+        }
+
+        Ok((fee, selected_inputs, change))
     }
 }
 
@@ -156,3 +315,108 @@ pub enum SelectionPolicy {
 impl Default for SelectionPolicy {
     fn default() -> Self { SelectionPolicy::FirstMatchFirst }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coin::MAX_COIN;
+    use test_helpers::{address, input};
+
+    #[test]
+    fn inputs_overflow() {
+        let max = Coin::new(MAX_COIN).unwrap();
+        let inputs = [input(0, max), input(1, max)];
+        let outputs = [TxOut::new(address(), max)];
+        let output_policy = OutputPolicy::One(address());
+
+        match LinearFee::default().compute(SelectionPolicy::default(), inputs.iter(), outputs.iter(), &output_policy) {
+            Err(Error::CoinOverflow) => {},
+            r => panic!("expected coin overflow, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn outputs_overflow() {
+        let max = Coin::new(MAX_COIN).unwrap();
+        let inputs = [input(0, max)];
+        let outputs = [TxOut::new(address(), max), TxOut::new(address(), Coin::unit())];
+        let output_policy = OutputPolicy::One(address());
+
+        match LinearFee::default().compute(SelectionPolicy::default(), inputs.iter(), outputs.iter(), &output_policy) {
+            Err(Error::CoinOverflow) => {},
+            r => panic!("expected coin overflow, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn no_inputs_or_no_outputs() {
+        let inputs = [input(0, Coin::new(1_000_000).unwrap())];
+        let outputs = [TxOut::new(address(), Coin::new(500_000).unwrap())];
+        let output_policy = OutputPolicy::One(address());
+
+        match LinearFee::default().compute(SelectionPolicy::default(), inputs[..0].iter(), outputs.iter(), &output_policy) {
+            Err(Error::NoInputs) => {},
+            r => panic!("expected no inputs, got {:?}", r),
+        }
+        match LinearFee::default().compute(SelectionPolicy::default(), inputs.iter(), outputs[..0].iter(), &output_policy) {
+            Err(Error::NoOutputs) => {},
+            r => panic!("expected no outputs, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn max_inputs_caps_the_selection() {
+        let inputs : Vec<_> = (0..20).map(|i| input(i, Coin::new(1_000_000).unwrap())).collect();
+        let output_policy = OutputPolicy::One(address());
+        let compute = |value: u64, max_inputs| {
+            let outputs = [TxOut::new(address(), Coin::new(value).unwrap())];
+            let config = Config { max_tx_size: usize::MAX, max_inputs, ..Config::default() };
+            LinearFee::default().compute_with_limits(SelectionPolicy::default(), inputs.iter(), outputs.iter(), &output_policy, &TxAttributes::new(), &config)
+        };
+
+        let (_, selected, _) = compute(2_500_000, Some(3)).unwrap();
+        assert_eq!(selected.len(), 3);
+        let (_, selected, _) = compute(1_500_000, Some(3)).unwrap();
+        assert_eq!(selected.len(), 2);
+
+        match compute(3_500_000, Some(3)) {
+            Err(Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r),
+        }
+        let (_, selected, _) = compute(3_500_000, None).unwrap();
+        assert_eq!(selected.len(), 4);
+    }
+
+    #[test]
+    fn dust_change_goes_to_the_fee() {
+        let inputs = [input(0, Coin::new(1_000_000).unwrap())];
+        let outputs = [TxOut::new(address(), Coin::new(800_000).unwrap())];
+        let output_policy = OutputPolicy::One(address());
+        let compute = |dust_threshold| {
+            let config = Config { dust_threshold, ..Config::default() };
+            LinearFee::default().compute_with_limits(SelectionPolicy::default(), inputs.iter(), outputs.iter(), &output_policy, &TxAttributes::new(), &config).unwrap()
+        };
+
+        let (fee, _, change) = compute(Coin::zero());
+        assert!(change > Coin::zero());
+        let (same_fee, _, same_change) = compute(change);
+        assert_eq!((same_fee, same_change), (fee, change));
+
+        let (fee, _, change) = compute((change + Coin::unit()).unwrap());
+        assert_eq!(change, Coin::zero());
+        assert_eq!(fee.to_coin(), Coin::new(200_000).unwrap());
+    }
+
+    #[test]
+    fn duplicate_inputs() {
+        let inputs = [input(0, Coin::new(1_000_000).unwrap()), input(1, Coin::new(1_000_000).unwrap()), input(0, Coin::new(1_000_000).unwrap())];
+        let outputs = [TxOut::new(address(), Coin::new(1_500_000).unwrap())];
+        let output_policy = OutputPolicy::One(address());
+
+        match LinearFee::default().compute(SelectionPolicy::default(), inputs.iter(), outputs.iter(), &output_policy) {
+            Err(Error::DuplicateInput(ref ptr)) if *ptr == inputs[0].ptr => {},
+            r => panic!("expected duplicate input, got {:?}", r),
+        }
+        assert!(check_duplicate_inputs(inputs[..2].iter()).is_ok());
+    }
+}
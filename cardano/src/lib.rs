@@ -30,6 +30,20 @@ extern crate test;
 extern crate cryptoxide;
 #[macro_use]
 extern crate cbor_event;
+#[cfg(feature = "logging")]
+#[macro_use]
+extern crate log;
+
+/// log at `debug` level with the `log` crate when the `logging` feature is
+/// enabled, otherwise the arguments are only type checked.
+#[cfg(feature = "logging")]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { debug!($($arg)*) }
+}
+#[cfg(not(feature = "logging"))]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { if false { let _ = format!($($arg)*); } }
+}
 
 mod crc32;
 pub mod util;
@@ -55,3 +69,6 @@ pub mod block;
 pub mod vss;
 pub mod merkle;
 pub mod tags;
+
+#[cfg(test)]
+mod test_helpers;
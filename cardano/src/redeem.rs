@@ -10,6 +10,7 @@
 use cryptoxide::{ed25519};
 use util::{hex};
 use cbor_event::{self, de::RawCbor, se::{Serializer}};
+use cbor::hs::util::checked_bytes;
 #[cfg(feature = "generic-serialization")]
 use serde;
 
@@ -127,13 +128,19 @@ impl PrivateKey {
         Self::from_slice(&bytes)
     }
 
+    /// the private key is the 32 bytes seed followed by the public key,
+    /// as expected by `ed25519::signature`.
     pub fn generate(seed: &[u8]) -> Self {
         let (sk, _) = ed25519::keypair(seed);
         Self::from_bytes(sk)
     }
 
+    /// the public key is derived from the seed (the first 32 bytes), the
+    /// same way `ed25519::keypair` does, so signatures made with `sign`
+    /// verify with it.
     pub fn public(&self) -> PublicKey {
-        PublicKey::from_bytes(ed25519::to_public(&self.0))
+        let (_, pk) = ed25519::keypair(&self.0[0..32]);
+        PublicKey::from_bytes(pk)
     }
 
     pub fn sign(&self, bytes: &[u8]) -> Signature {
@@ -207,7 +214,7 @@ impl cbor_event::se::Serialize for PublicKey {
 }
 impl cbor_event::de::Deserialize for PublicKey {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        match PublicKey::from_slice(&raw.bytes()?) {
+        match PublicKey::from_slice(&checked_bytes(raw)?) {
             Ok(digest) => Ok(digest),
             Err(Error::InvalidPublicKeySize(sz)) => Err(cbor_event::Error::NotEnough(sz, PUBLICKEY_SIZE)),
             Err(err) => Err(cbor_event::Error::CustomError(format!("unexpected error: {:?}", err))),
@@ -222,7 +229,7 @@ impl cbor_event::se::Serialize for PrivateKey {
 }
 impl cbor_event::de::Deserialize for PrivateKey {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        match PrivateKey::from_slice(&raw.bytes()?) {
+        match PrivateKey::from_slice(&checked_bytes(raw)?) {
             Ok(digest) => Ok(digest),
             Err(Error::InvalidPrivateKeySize(sz)) => Err(cbor_event::Error::NotEnough(sz, PRIVATEKEY_SIZE)),
             Err(err) => Err(cbor_event::Error::CustomError(format!("unexpected error: {:?}", err))),
@@ -237,7 +244,7 @@ impl cbor_event::se::Serialize for Signature {
 }
 impl cbor_event::de::Deserialize for Signature {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        match Signature::from_slice(&raw.bytes()?) {
+        match Signature::from_slice(&checked_bytes(raw)?) {
             Ok(digest) => Ok(digest),
             Err(Error::InvalidSignatureSize(sz)) => Err(cbor_event::Error::NotEnough(sz, SIGNATURE_SIZE)),
             Err(err) => Err(cbor_event::Error::CustomError(format!("unexpected error: {:?}", err))),
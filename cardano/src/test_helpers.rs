@@ -0,0 +1,55 @@
+//! fixtures shared by the unit tests of the different modules.
+
+use hdwallet::{self, XPrv, DerivationScheme};
+use address::ExtendedAddr;
+use coin::Coin;
+use tx::{TxId, TxoPointer, TxOut};
+use txutils::Input;
+use wallet::{bip44, rindex};
+
+/// the root key generated from the all-zero seed
+pub fn root_key() -> XPrv {
+    let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+    XPrv::generate_from_seed(&seed)
+}
+
+pub fn bip44_wallet() -> bip44::Wallet {
+    bip44::Wallet::from_root_key(root_key(), DerivationScheme::V2)
+}
+
+pub fn rindex_wallet() -> rindex::Wallet {
+    rindex::Wallet::from_root_key(DerivationScheme::V2, rindex::RootKey::new(root_key(), DerivationScheme::V2))
+}
+
+/// the simple address of the public key of `root_key`
+pub fn address() -> ExtendedAddr {
+    ExtendedAddr::new_simple(root_key().public())
+}
+
+/// an input of the given value to `address`, at the given index of the
+/// all-zero transaction id
+pub fn input(index: u32, value: Coin) -> Input<()> {
+    let ptr = TxoPointer::new(TxId::new(&[0;32]), index);
+    Input::new(ptr, TxOut::new(address(), value), ())
+}
+
+/// a simple address of a key generated from the seed filled with `b`,
+/// `b` not being 0 the address is owned by none of the wallets above.
+pub fn foreign_address(b: u8) -> ExtendedAddr {
+    let seed = hdwallet::Seed::from_bytes([b;hdwallet::SEED_SIZE]);
+    ExtendedAddr::new_simple(XPrv::generate_from_seed(&seed).public())
+}
+
+/// xorshift pseudo random generator, for the tests needing reproducible
+/// random values
+pub struct XorShift(u32);
+impl XorShift {
+    pub fn new(seed: u32) -> Self { XorShift(seed) }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
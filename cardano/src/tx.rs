@@ -6,17 +6,19 @@
 //! `TxInWitness`: Witness providing for TxoPointer (e.g. cryptographic signature)
 //! `TxAux` : Signed Tx (Tx + Witness)
 //!
-use std::{fmt};
+use std::{fmt, error, collections::{BTreeMap, btree_map}};
 
 use hash::{Blake2b256};
 
 use cbor_event::{self, de::RawCbor, se::{Serializer}};
-use config::{ProtocolMagic};
+use cbor::hs::util::checked_bytes;
+use config::{Config, ProtocolMagic};
 use redeem;
+use util::{hex, text_envelope::{self, TextEnvelope}};
 use tags::{SigningTag};
 
 use hdwallet::{Signature, XPub, XPrv, XPUB_SIZE, SIGNATURE_SIZE};
-use address::{ExtendedAddr, SpendingData};
+use address::{AddrType, Attributes, ExtendedAddr, SpendingData};
 use coin::{self, Coin};
 
 // TODO: this seems to be the hash of the serialisation CBOR of a given Tx.
@@ -85,6 +87,17 @@ impl fmt::Display for TxInWitness {
         write!(f, "{:?}", self)
     }
 }
+/// the data signed by a witness: the signing tag, which separates the
+/// transaction signatures from the other signatures of the protocol, the
+/// protocol magic and the transaction id, each CBOR encoded.
+fn sign_data(tag: SigningTag, protocol_magic: ProtocolMagic, txid: &TxId) -> Vec<u8> {
+    Serializer::new_vec()
+        .write_unsigned_integer(tag as u64).expect("write sign tag")
+        .serialize(&protocol_magic).expect("serialize protocol magic")
+        .serialize(txid).expect("serialize Tx's Id")
+        .finalize()
+}
+
 impl TxInWitness {
     /// this is used to create a fake signature useful for fee evaluation
     pub fn fake() -> Self {
@@ -93,15 +106,57 @@ impl TxInWitness {
     }
 
     /// create a TxInWitness from a given private key `XPrv` for the given transaction id `TxId`.
+    ///
+    /// The id must be the one of the transaction's canonical encoding (see
+    /// [`Tx::id`](./struct.Tx.html#method.id)) for the witness to be valid.
     pub fn new(protocol_magic: ProtocolMagic, key: &XPrv, txid: &TxId) -> Self {
-        let vec = Serializer::new_vec()
-            .write_unsigned_integer(1).expect("write byte 0x01")
-            .serialize(&protocol_magic).expect("serialize protocol magic")
-            .serialize(&txid).expect("serialize Tx's Id")
-            .finalize();
+        let vec = sign_data(SigningTag::Tx, protocol_magic, txid);
         TxInWitness::PkWitness(key.public(), key.sign(&vec))
     }
 
+    /// create the bootstrap (Byron) witness of an input spending an address
+    /// of `key` with the given `attributes`, for the network described by
+    /// `config`: the `Tx` is signed with its id and the protocol magic of
+    /// the network.
+    ///
+    /// The attributes are not stored in the witness: a `PkWitness` holds
+    /// the extended public key, with its chain code, which together with
+    /// the attributes of the spent output's address is everything needed
+    /// to rebuild this address (see
+    /// [`reconstruct_address`](./enum.TxInWitness.html#method.reconstruct_address)).
+    /// Adding them would change the encoding of the witness, which would
+    /// then be rejected by the network.
+    pub fn bootstrap(config: &Config, key: &XPrv, tx: &Tx, _attributes: &Attributes) -> Self {
+        TxInWitness::new(config.protocol_magic, key, &tx.id())
+    }
+
+    /// create a TxInWitness spending a redeem (AVVM) address from the given
+    /// redeem private key for the given transaction id `TxId`.
+    pub fn new_redeem(protocol_magic: ProtocolMagic, key: &redeem::PrivateKey, txid: &TxId) -> Self {
+        let vec = sign_data(SigningTag::RedeemTx, protocol_magic, txid);
+        TxInWitness::RedeemWitness(key.public(), key.sign(&vec))
+    }
+
+    /// reconstruct the address spent by the witness from its public key
+    /// and the attributes of the address (e.g. its HD payload).
+    ///
+    /// The public key of a `PkWitness` is the extended public key (the
+    /// key and its chain code) the address root is computed from, so the
+    /// witness carries everything needed to check it against the address
+    /// of the spent output. Script witnesses are not supported and return
+    /// `None`.
+    pub fn reconstruct_address(&self, attributes: &Attributes) -> Option<ExtendedAddr> {
+        match *self {
+            TxInWitness::PkWitness(ref pk, _) => {
+                Some(ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(*pk), attributes.clone()))
+            },
+            TxInWitness::ScriptWitness(_, _) => None,
+            TxInWitness::RedeemWitness(ref pk, _) => {
+                Some(ExtendedAddr::new(AddrType::ATRedeem, SpendingData::RedeemASD(*pk), attributes.clone()))
+            },
+        }
+    }
+
     /// verify a given extended address is associated to the witness.
     ///
     pub fn verify_address(&self, address: &ExtendedAddr) -> bool {
@@ -125,11 +180,7 @@ impl TxInWitness {
     /// verify the signature against the given transation `Tx`
     ///
     pub fn verify_tx(&self, protocol_magic: ProtocolMagic, tx: &Tx) -> bool {
-        let vec = Serializer::new_vec()
-            .write_unsigned_integer(self.get_sign_tag() as u64).expect("write sign tag")
-            .serialize(&protocol_magic).expect("serialize protocol magic")
-            .serialize(&tx.id()).expect("serialize Tx's Id")
-            .finalize();
+        let vec = sign_data(self.get_sign_tag(), protocol_magic, &tx.id());
         match self {
             &TxInWitness::PkWitness(ref pk, ref sig)     => pk.verify(&vec, sig),
             &TxInWitness::ScriptWitness(_, _)            => unimplemented!(),
@@ -182,7 +233,7 @@ impl cbor_event::de::Deserialize for TxInWitness {
                 if tag != 24 {
                     return Err(cbor_event::Error::CustomError(format!("Invalid Tag: {} but expected 24", tag)));
                 }
-                let bytes = raw.bytes()?;
+                let bytes = checked_bytes(raw)?;
                 let mut raw = RawCbor::from(&bytes);
                 raw.tuple(2, "TxInWitness::PkWitness")?;
                 let pk  = cbor_event::de::Deserialize::deserialize(&mut raw)?;
@@ -194,7 +245,7 @@ impl cbor_event::de::Deserialize for TxInWitness {
                 if tag != 24 {
                     return Err(cbor_event::Error::CustomError(format!("Invalid Tag: {} but expected 24", tag)));
                 }
-                let bytes = raw.bytes()?;
+                let bytes = checked_bytes(raw)?;
                 let mut raw = RawCbor::from(&bytes);
                 raw.tuple(2, "TxInWitness::PkRedeemWitness")?;
                 let pk  = cbor_event::de::Deserialize::deserialize(&mut raw)?;
@@ -249,7 +300,7 @@ impl cbor_event::de::Deserialize for TxoPointer {
         if tag != 24 {
             return Err(cbor_event::Error::CustomError(format!("Invalid Tag: {} but expected 24", tag)));
         }
-        let bytes = raw.bytes()?;
+        let bytes = checked_bytes(raw)?;
         let mut raw = RawCbor::from(&bytes);
         raw.tuple(2, "TxoPointer")?;
         let id  = cbor_event::de::Deserialize::deserialize(&mut raw)?;
@@ -264,10 +315,75 @@ impl cbor_event::de::Deserialize for TxoPointer {
 pub struct Tx {
     pub inputs: Vec<TxoPointer>,
     pub outputs: Vec<TxOut>,
-    // attributes: TxAttributes
-    //
-    // So far, there is no TxAttributes... the structure contains only the unparsed/unknown stuff
+    /// the attributes of the transaction, see
+    /// [`TxAttributes`](./struct.TxAttributes.html).
+    pub attributes: TxAttributes,
+}
+
+/// The attributes map of a transaction.
+///
+/// There are no known transaction attributes so far: every attribute is
+/// kept unparsed, as the key and the raw bytes of its value, so a
+/// transaction is encoded back with the attributes it was decoded with.
+/// Being part of the transaction the attributes change its id and are
+/// signed by the witnesses.
+///
+/// The attributes are encoded in the order of their keys, as the
+/// canonical CBOR of the network requires.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub struct TxAttributes(BTreeMap<u8, Vec<u8>>);
+impl TxAttributes {
+    pub fn new() -> Self { TxAttributes(BTreeMap::new()) }
+
+    /// set the value of the attribute of the given key, returning the
+    /// previous value if any.
+    pub fn insert(&mut self, key: u8, value: Vec<u8>) -> Option<Vec<u8>> {
+        self.0.insert(key, value)
+    }
+    pub fn get(&self, key: u8) -> Option<&[u8]> {
+        self.0.get(&key).map(|value| value.as_ref())
+    }
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+    pub fn len(&self) -> usize { self.0.len() }
+    pub fn iter(&self) -> btree_map::Iter<'_, u8, Vec<u8>> { self.0.iter() }
+}
+impl cbor_event::se::Serialize for TxAttributes {
+    fn serialize<W: ::std::io::Write>(&self, serializer: Serializer<W>) -> cbor_event::Result<Serializer<W>> {
+        let mut serializer = serializer.write_map(cbor_event::Len::Len(self.0.len() as u64))?;
+        for (key, value) in self.0.iter() {
+            serializer = serializer.write_unsigned_integer(*key as u64)?
+                                   .write_bytes(value)?;
+        }
+        Ok(serializer)
+    }
+}
+impl cbor_event::de::Deserialize for TxAttributes {
+    fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
+        let len = match raw.map()? {
+            cbor_event::Len::Len(len) => len,
+            cbor_event::Len::Indefinite => {
+                return Err(cbor_event::Error::CustomError("Invalid TxAttributes: expected a definite length map".to_owned()));
+            }
+        };
+        let mut attributes = TxAttributes::new();
+        let mut previous = None;
+        for _ in 0..len {
+            let key = raw.unsigned_integer()?;
+            if key > u8::MAX as u64 {
+                return Err(cbor_event::Error::CustomError(format!("Invalid TxAttributes: invalid key {}", key)));
+            }
+            // keys in increasing order only, so the encoding is the canonical one
+            if previous.map(|previous| key <= previous).unwrap_or(false) {
+                return Err(cbor_event::Error::CustomError(format!("Invalid TxAttributes: key {} is not in canonical order", key)));
+            }
+            previous = Some(key);
+            attributes.insert(key as u8, Vec::from(checked_bytes(raw)?.as_ref()));
+        }
+        Ok(attributes)
+    }
 }
+
 impl fmt::Display for Tx {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for input in self.inputs.iter() {
@@ -282,11 +398,34 @@ impl fmt::Display for Tx {
 impl Tx {
     pub fn new() -> Self { Tx::new_with(Vec::new(), Vec::new()) }
     pub fn new_with(ins: Vec<TxoPointer>, outs: Vec<TxOut>) -> Self {
-        Tx { inputs: ins, outputs: outs }
+        Tx { inputs: ins, outputs: outs, attributes: TxAttributes::new() }
     }
     pub fn id(&self) -> TxId {
-        let buf = cbor!(self).expect("encode Tx");
-        TxId::new(&buf)
+        TxId::new(&self.to_canonical_cbor())
+    }
+    /// the encoding of the transaction the network expects, and the one its
+    /// id (hence the signature of the witnesses, see
+    /// [`TxInWitness::new`](./enum.TxInWitness.html#method.new)) is computed
+    /// over: the inputs and the outputs as indefinite arrays and the
+    /// attributes as a definite map.
+    ///
+    /// The encoding only depends on the content of the transaction, so
+    /// decoding and encoding it again gives the same bytes.
+    pub fn to_canonical_cbor(&self) -> Vec<u8> {
+        cbor!(self).expect("encode Tx")
+    }
+    /// write the canonical CBOR encoding of the transaction to `w`.
+    ///
+    /// The inputs and outputs are encoded one by one straight into the
+    /// writer, the bytes are the same as
+    /// [`to_canonical_cbor`](./struct.Tx.html#method.to_canonical_cbor)
+    /// without holding the whole encoding in memory.
+    pub fn write_cbor(&self, w: &mut dyn std::io::Write) -> ::std::io::Result<()> {
+        match cbor_event::se::Serializer::new(w).serialize(self) {
+            Ok(_) => Ok(()),
+            Err(cbor_event::Error::IoError(err)) => Err(err),
+            Err(err) => Err(::std::io::Error::other(format!("{:?}", err))),
+        }
     }
     pub fn add_input(&mut self, i: TxoPointer) {
         self.inputs.push(i)
@@ -294,6 +433,11 @@ impl Tx {
     pub fn add_output(&mut self, o: TxOut) {
         self.outputs.push(o)
     }
+    /// set the attributes of the transaction, see
+    /// [`TxAttributes`](./struct.TxAttributes.html).
+    pub fn set_attributes(&mut self, attrs: TxAttributes) {
+        self.attributes = attrs
+    }
     pub fn get_output_total(&self) -> coin::Result<Coin> {
         let mut total = Coin::zero();
         for ref o in self.outputs.iter() {
@@ -307,7 +451,7 @@ impl cbor_event::se::Serialize for Tx {
         let serializer = serializer.write_array(cbor_event::Len::Len(3))?;
         let serializer = cbor_event::se::serialize_indefinite_array(self.inputs.iter(), serializer)?;
         let serializer = cbor_event::se::serialize_indefinite_array(self.outputs.iter(), serializer)?;
-        serializer.write_map(cbor_event::Len::Len(0))
+        serializer.serialize(&self.attributes)
     }
 }
 impl cbor_event::de::Deserialize for Tx {
@@ -318,11 +462,9 @@ impl cbor_event::de::Deserialize for Tx {
         let inputs = cbor_event::de::Deserialize::deserialize(raw)?;
         let outputs = cbor_event::de::Deserialize::deserialize(raw)?;
 
-        let map_len = raw.map()?;
-        if ! map_len.is_null() {
-            return Err(cbor_event::Error::CustomError(format!("Invalid Tx: we do not support Tx extra data... {:?} elements", map_len)));
-        }
-        Ok(Tx::new_with(inputs, outputs))
+        let mut tx = Tx::new_with(inputs, outputs);
+        tx.set_attributes(cbor_event::de::Deserialize::deserialize(raw)?);
+        Ok(tx)
     }
 }
 
@@ -397,6 +539,20 @@ impl cbor_event::se::Serialize for TxWitnesses
     }
 }
 
+/// error returned by [`TxAux::new_checked`](./struct.TxAux.html#method.new_checked)
+/// when the number of witnesses is not the number of inputs of the `Tx`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WitnessCountMismatch {
+    pub inputs: usize,
+    pub witnesses: usize,
+}
+impl fmt::Display for WitnessCountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid number of witnesses, expected {} (one per input) but received {}", self.inputs, self.witnesses)
+    }
+}
+impl error::Error for WitnessCountMismatch {}
+
 /// Tx with the vector of witnesses
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
@@ -414,6 +570,124 @@ impl TxAux {
     pub fn new(tx: Tx, witness: TxWitness) -> Self {
         TxAux { tx: tx, witness: witness }
     }
+
+    /// same as `TxAux::new` but check there is exactly one witness per
+    /// input of the transaction.
+    pub fn new_checked(tx: Tx, witness: TxWitness) -> Result<Self, WitnessCountMismatch> {
+        if witness.len() != tx.inputs.len() {
+            return Err(WitnessCountMismatch { inputs: tx.inputs.len(), witnesses: witness.len() });
+        }
+        Ok(TxAux::new(tx, witness))
+    }
+
+    /// append a witness to the transaction, e.g. when the witnesses of a
+    /// multi-party transaction are provided by different wallets.
+    ///
+    /// The witnesses need to be added in the order of the transaction's
+    /// inputs.
+    pub fn add_witness(&mut self, witness: TxInWitness) {
+        self.witness.push(witness)
+    }
+
+    /// test that both `TxAux` have the same transaction and the same
+    /// witnesses, in any order (e.g. when assembled by different parties).
+    ///
+    /// Both must also have exactly one witness per input of the transaction
+    /// and every witness must be a valid signature of the transaction for
+    /// the given protocol magic (see
+    /// [`TxInWitness::verify_tx`](./enum.TxInWitness.html#method.verify_tx)).
+    /// The witnesses are not checked against the addresses of the spent
+    /// outputs, which are not known here. Script witnesses are not supported
+    /// and never match.
+    pub fn semantically_eq(&self, other: &TxAux, protocol_magic: ProtocolMagic) -> bool {
+        if self.tx != other.tx
+            || self.witness.len() != self.tx.inputs.len()
+            || other.witness.len() != self.witness.len() {
+            return false;
+        }
+        let valid = |witness: &TxInWitness| match *witness {
+            TxInWitness::ScriptWitness(_, _) => false,
+            _ => witness.verify_tx(protocol_magic, &self.tx),
+        };
+        if !self.witness.iter().all(valid) { return false; }
+        // every witness of `self` matches a distinct witness of `other`
+        let mut matched = vec![false; other.witness.len()];
+        self.witness.iter().all(|witness| {
+            let found = other.witness.iter().enumerate().position(|(i, w)| !matched[i] && w == witness);
+            match found {
+                Some(i) => { matched[i] = true; true },
+                None => false,
+            }
+        })
+    }
+
+    /// compute the fee per byte of the transaction, a metric to order
+    /// transactions by priority (e.g. in a mempool).
+    ///
+    /// `resolved_inputs` are the `TxOut` referenced by the transaction's
+    /// inputs, the fee being the difference between the total of the
+    /// resolved inputs and the total of the outputs.
+    pub fn priority<'a, I>(&self, resolved_inputs: I) -> coin::Result<f64>
+        where I: Iterator<Item = &'a TxOut>
+    {
+        let mut input_total = Coin::zero();
+        for txout in resolved_inputs {
+            input_total = (input_total + txout.value)?;
+        }
+        let fee = (input_total - self.tx.get_output_total()?)?;
+        let size = txaux_serialize_size(&self.tx, &self.witness);
+        Ok(*fee as f64 / size as f64)
+    }
+
+    /// the hexadecimal encoding of the CBOR serialization of the `TxAux`,
+    /// as sent to the network. Useful to compare with the output of other
+    /// tools.
+    pub fn to_cbor_hex(&self) -> String {
+        hex::encode(&cbor!(self).expect("encode TxAux"))
+    }
+
+    /// human readable summary of the transaction: its id, the inputs,
+    /// the outputs and the number of witnesses.
+    ///
+    /// ```text
+    /// TxAux <txid>
+    ///   inputs (1):
+    ///     <txid>@<index>
+    ///   outputs (1):
+    ///     <address> -> <value>
+    ///   witnesses: 1
+    /// ```
+    pub fn describe(&self) -> String {
+        let mut s = format!("TxAux {}\n", self.tx.id());
+        s.push_str(&format!("  inputs ({}):\n", self.tx.inputs.len()));
+        for input in self.tx.inputs.iter() {
+            s.push_str(&format!("    {}\n", input));
+        }
+        s.push_str(&format!("  outputs ({}):\n", self.tx.outputs.len()));
+        for output in self.tx.outputs.iter() {
+            s.push_str(&format!("    {}\n", output));
+        }
+        s.push_str(&format!("  witnesses: {}\n", self.witness.len()));
+        s
+    }
+
+    /// the `cardano-cli` JSON text envelope of the transaction, with the
+    /// [`TX_TEXT_ENVELOPE_TYPE`](./constant.TX_TEXT_ENVELOPE_TYPE.html)
+    /// type and the CBOR hexadecimal encoding of the `TxAux`.
+    pub fn to_text_envelope(&self, description: &str) -> String {
+        TextEnvelope::new(TX_TEXT_ENVELOPE_TYPE, description, &cbor!(self).expect("encode TxAux")).to_json()
+    }
+
+    /// decode the transaction from a `cardano-cli` JSON text envelope,
+    /// the description is ignored.
+    pub fn from_text_envelope(json: &str) -> Result<Self, TextEnvelopeError> {
+        let envelope = TextEnvelope::parse(json).map_err(TextEnvelopeError::InvalidEnvelope)?;
+        if envelope.envelope_type != TX_TEXT_ENVELOPE_TYPE {
+            return Err(TextEnvelopeError::UnexpectedType(envelope.envelope_type));
+        }
+        let bytes = hex::decode(&envelope.cbor_hex).map_err(TextEnvelopeError::InvalidHex)?;
+        RawCbor::from(&bytes).deserialize().map_err(TextEnvelopeError::InvalidCbor)
+    }
 }
 impl cbor_event::de::Deserialize for TxAux {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
@@ -429,6 +703,42 @@ impl cbor_event::se::Serialize for TxAux {
     }
 }
 
+/// the `type` of the text envelope of a [`TxAux`](./struct.TxAux.html)
+pub const TX_TEXT_ENVELOPE_TYPE : &str = "TxSignedByron";
+
+/// error returned by [`TxAux::from_text_envelope`](./struct.TxAux.html#method.from_text_envelope)
+#[derive(Debug)]
+pub enum TextEnvelopeError {
+    /// the text is not a valid JSON text envelope
+    InvalidEnvelope(text_envelope::Error),
+    /// the envelope does not contain a transaction, contains its `type`
+    UnexpectedType(String),
+    /// the `cborHex` field is not hexadecimal
+    InvalidHex(hex::Error),
+    /// the `cborHex` field is not the CBOR encoding of a `TxAux`
+    InvalidCbor(cbor_event::Error),
+}
+impl fmt::Display for TextEnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextEnvelopeError::InvalidEnvelope(_) => write!(f, "Invalid text envelope"),
+            TextEnvelopeError::UnexpectedType(ref t) => write!(f, "Unexpected text envelope type `{}`, expected `{}`", t, TX_TEXT_ENVELOPE_TYPE),
+            TextEnvelopeError::InvalidHex(_) => write!(f, "Invalid hexadecimal `cborHex`"),
+            TextEnvelopeError::InvalidCbor(_) => write!(f, "The `cborHex` is not an encoded transaction"),
+        }
+    }
+}
+impl error::Error for TextEnvelopeError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            TextEnvelopeError::InvalidEnvelope(ref err) => Some(err),
+            TextEnvelopeError::UnexpectedType(_) => None,
+            TextEnvelopeError::InvalidHex(ref err) => Some(err),
+            TextEnvelopeError::InvalidCbor(ref err) => Some(err),
+        }
+    }
+}
+
 pub fn txaux_serialize<W>(tx: &Tx, in_witnesses: &Vec<TxInWitness>, serializer: Serializer<W>)
     -> cbor_event::Result<Serializer<W>>
     where W: ::std::io::Write
@@ -446,6 +756,55 @@ pub fn txaux_serialize_size(tx: &Tx, in_witnesses: &Vec<TxInWitness>) -> usize {
     bytes.len()
 }
 
+/// error returned by [`total_fees`](./fn.total_fees.html)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TotalFeesError {
+    /// the value of the given input could not be found by the lookup
+    UnknownInput(TxoPointer),
+    /// the sum of the inputs or outputs is out of bound, or a transaction
+    /// spends more than its inputs
+    CoinError(coin::Error),
+}
+impl From<coin::Error> for TotalFeesError {
+    fn from(e: coin::Error) -> Self { TotalFeesError::CoinError(e) }
+}
+impl fmt::Display for TotalFeesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TotalFeesError::UnknownInput(ref ptr) => write!(f, "Cannot resolve the value of the input {}", ptr),
+            TotalFeesError::CoinError(_) => write!(f, "Invalid coin operation while computing the fees"),
+        }
+    }
+}
+impl error::Error for TotalFeesError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            TotalFeesError::CoinError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// compute the sum of the fees paid by the given transactions.
+///
+/// The fee of a transaction is the sum of the values of its inputs, as
+/// returned by `utxo_lookup`, minus the sum of its outputs.
+pub fn total_fees<F>(txs: &[TxAux], utxo_lookup: F) -> Result<Coin, TotalFeesError>
+    where F: Fn(&TxoPointer) -> Option<Coin>
+{
+    let mut total = Coin::zero();
+    for txaux in txs {
+        let mut inputs = Coin::zero();
+        for ptr in txaux.tx.inputs.iter() {
+            let value = utxo_lookup(ptr).ok_or_else(|| TotalFeesError::UnknownInput(ptr.clone()))?;
+            inputs = (inputs + value)?;
+        }
+        let fee = (inputs - txaux.tx.get_output_total()?)?;
+        total = (total + fee)?;
+    }
+    Ok(total)
+}
+
 #[derive(Debug, Clone)]
 pub struct TxProof {
     pub number: u32,
@@ -491,6 +850,7 @@ mod tests {
     use hdpayload;
     use hdwallet;
     use cbor_event::{self, de::RawCbor};
+    use std::str::FromStr;
 
     const SEED: [u8;hdwallet::SEED_SIZE] = [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0];
 
@@ -585,6 +945,34 @@ mod tests {
         assert!(cbor_event::test_encode_decode(&tx).expect("encode/decode Tx"));
     }
 
+    #[test]
+    fn tx_id_golden() {
+        let tx : Tx = RawCbor::from(TX).deserialize().expect("to decode a `Tx`");
+        // the id is the hash of the encoded transaction, so the encoding must be stable
+        assert_eq!(cbor!(&tx).unwrap(), TX);
+        let expected = TxId::from_str("63d6c46119c0b695360315093c90dd2ec609575113b7ed1c1928a967698ea9c5").unwrap();
+        assert_eq!(tx.id(), expected);
+        assert_eq!(tx.id(), TxId::new(TX));
+    }
+
+    #[test]
+    fn tx_id_differs() {
+        let tx : Tx = RawCbor::from(TX).deserialize().expect("to decode a `Tx`");
+
+        let mut other_input = tx.clone();
+        other_input.inputs[0].index += 1;
+        let mut other_value = tx.clone();
+        other_value.outputs[0].value = Coin::new(43).unwrap();
+        let mut more_outputs = tx.clone();
+        more_outputs.add_output(tx.outputs[0].clone());
+
+        let ids = [tx.id(), other_input.id(), other_value.id(), more_outputs.id()];
+        for (i, id) in ids.iter().enumerate() {
+            for other in ids[i + 1..].iter() { assert!(id != other); }
+        }
+        assert_eq!(tx.clone().id(), tx.id());
+    }
+
     #[test]
     fn txinwitness_decode() {
         let protocol_magic = ProtocolMagic::default();
@@ -647,6 +1035,120 @@ mod tests {
         assert!(txinwitness.verify(protocol_magic, &ea, &tx));
     }
 
+    #[test]
+    fn txinwitness_protocol_magic() {
+        use config::Config;
+
+        let mainnet = Config::mainnet();
+        let testnet = Config::testnet(1097911063);
+
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let ea = address::ExtendedAddr::new_simple(sk.public());
+
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 0));
+        tx.add_output(TxOut::new(ea.clone(), Coin::new(42).unwrap()));
+
+        let mainnet_witness = TxInWitness::new(mainnet.protocol_magic, &sk, &tx.id());
+        let testnet_witness = TxInWitness::new(testnet.protocol_magic, &sk, &tx.id());
+        assert!(mainnet_witness != testnet_witness);
+
+        assert!(mainnet_witness.verify(mainnet.protocol_magic, &ea, &tx));
+        assert!(!mainnet_witness.verify(testnet.protocol_magic, &ea, &tx));
+        assert!(testnet_witness.verify(testnet.protocol_magic, &ea, &tx));
+        assert!(!testnet_witness.verify(mainnet.protocol_magic, &ea, &tx));
+    }
+
+    #[test]
+    fn txinwitness_verify_rejects_tampered_tx_and_other_address() {
+        let protocol_magic = ProtocolMagic::default();
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let ea = address::ExtendedAddr::new_simple(sk.public());
+
+        let other_seed = hdwallet::Seed::from_bytes([1;hdwallet::SEED_SIZE]);
+        let other_ea = address::ExtendedAddr::new_simple(hdwallet::XPrv::generate_from_seed(&other_seed).public());
+
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 666));
+        tx.add_output(TxOut::new(other_ea.clone(), Coin::new(42).unwrap()));
+
+        let txinwitness = TxInWitness::new(protocol_magic, &sk, &tx.id());
+        assert!(txinwitness.verify(protocol_magic, &ea, &tx));
+
+        // the witness' public key is not the one of the address
+        assert!(!txinwitness.verify_address(&other_ea));
+        assert!(!txinwitness.verify(protocol_magic, &other_ea, &tx));
+
+        // the transaction has been modified after signing
+        let mut tampered = tx.clone();
+        tampered.outputs[0].value = Coin::new(43).unwrap();
+        assert!(!txinwitness.verify_tx(protocol_magic, &tampered));
+        assert!(!txinwitness.verify(protocol_magic, &ea, &tampered));
+    }
+
+    #[test]
+    fn txinwitness_redeem_sign_verify() {
+        let protocol_magic = ProtocolMagic::default();
+        let key = redeem::PrivateKey::generate(&[7;32]);
+        let address = ExtendedAddr::new_redeem(key.public());
+        let other = ExtendedAddr::new_redeem(redeem::PrivateKey::generate(&[8;32]).public());
+
+        let tx : Tx = RawCbor::from(TX).deserialize().expect("to decode a `Tx`");
+        let witness = TxInWitness::new_redeem(protocol_magic, &key, &tx.id());
+
+        assert!(witness.verify(protocol_magic, &address, &tx));
+        assert!(!witness.verify(protocol_magic, &other, &tx));
+        assert!(!witness.verify(ProtocolMagic::from(1), &address, &tx));
+    }
+
+    #[test]
+    fn txaux_priority() {
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let pk = hdwallet::XPrv::generate_from_seed(&seed).public();
+        let ea = address::ExtendedAddr::new_simple(pk);
+
+        let resolved = [TxOut::new(ea.clone(), Coin::new(2_000_000).unwrap())];
+        let txaux = |output: u64| {
+            let mut tx = Tx::new();
+            tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 0));
+            tx.add_output(TxOut::new(ea.clone(), Coin::new(output).unwrap()));
+            TxAux::new(tx, TxWitness::from(vec![TxInWitness::fake()]))
+        };
+        let low_fee = txaux(1_000_001);
+        let high_fee = txaux(1_000_000);
+        assert_eq!(cbor!(&low_fee).unwrap().len(), cbor!(&high_fee).unwrap().len());
+
+        let low_priority = low_fee.priority(resolved.iter()).unwrap();
+        let high_priority = high_fee.priority(resolved.iter()).unwrap();
+        assert!(high_priority > low_priority);
+
+        // outputs above the inputs
+        assert!(txaux(3_000_000).priority(resolved.iter()).is_err());
+    }
+
+    #[test]
+    fn txaux_new_checked() {
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 0));
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 1));
+        tx.add_output(TxOut::new(address::ExtendedAddr::new_simple(sk.public()), Coin::new(42).unwrap()));
+        let witness = TxInWitness::new(ProtocolMagic::default(), &sk, &tx.id());
+
+        let one = TxWitness::from(vec![witness.clone()]);
+        assert_eq!(TxAux::new_checked(tx.clone(), one), Err(WitnessCountMismatch { inputs: 2, witnesses: 1 }));
+
+        let three = TxWitness::from(vec![witness.clone(), witness.clone(), witness.clone()]);
+        assert_eq!(TxAux::new_checked(tx.clone(), three), Err(WitnessCountMismatch { inputs: 2, witnesses: 3 }));
+
+        let two = TxWitness::from(vec![witness.clone(), witness]);
+        assert_eq!(TxAux::new_checked(tx.clone(), two.clone()), Ok(TxAux::new(tx, two)));
+    }
+
     #[test]
     fn txaux_decode() {
         let _txaux : TxAux = RawCbor::from(TX_AUX).deserialize().expect("to decode a TxAux");
@@ -663,6 +1165,272 @@ mod tests {
 
         assert!(cbor_event::test_encode_decode(&txaux).expect("encode/decode TxAux"));
     }
+
+    #[test]
+    fn txaux_cbor_hex_and_describe() {
+        let txaux : TxAux = RawCbor::from(TX_AUX).deserialize().expect("to decode a TxAux");
+
+        let cbor_hex = txaux.to_cbor_hex();
+        // array(2) of the `Tx`, an array(3) starting with the indefinite array of inputs
+        assert!(cbor_hex.starts_with("82839f"));
+        assert_eq!(cbor_hex, hex::encode(TX_AUX));
+
+        let description = txaux.describe();
+        assert!(description.starts_with(&format!("TxAux {}\n", txaux.tx.id())));
+        assert!(description.contains(&format!("  inputs (1):\n    {}\n", txaux.tx.inputs[0])));
+        assert!(description.contains(&format!("  outputs (1):\n    {}\n", txaux.tx.outputs[0])));
+        assert!(description.ends_with(&format!("  witnesses: {}\n", txaux.witness.len())));
+    }
+
+    #[test]
+    fn txaux_text_envelope_round_trip() {
+        let txaux : TxAux = RawCbor::from(TX_AUX).deserialize().expect("to decode a TxAux");
+
+        let json = txaux.to_text_envelope("a \"signed\" tx");
+        assert!(json.contains("\"type\": \"TxSignedByron\""));
+        assert!(json.contains(&format!("\"cborHex\": \"{}\"", hex::encode(TX_AUX))));
+        assert_eq!(TxAux::from_text_envelope(&json).unwrap(), txaux);
+    }
+
+    #[test]
+    fn txaux_text_envelope_rejects_invalid_cbor() {
+        let json = "{\"type\": \"TxSignedByron\", \"description\": \"\", \"cborHex\": \"8200ff\"}";
+        match TxAux::from_text_envelope(json) {
+            Err(TextEnvelopeError::InvalidCbor(_)) => {},
+            r => panic!("expected an invalid CBOR error, got {:?}", r),
+        }
+
+        let json = TextEnvelope::new("TxWitnessByron", "", TX_AUX).to_json();
+        match TxAux::from_text_envelope(&json) {
+            Err(TextEnvelopeError::UnexpectedType(ref t)) if t == "TxWitnessByron" => {},
+            r => panic!("expected an unexpected type error, got {:?}", r),
+        }
+        match TxAux::from_text_envelope("{\"type\": \"TxSignedByron\", \"description\": \"\", \"cborHex\": \"8z\"}") {
+            Err(TextEnvelopeError::InvalidHex(_)) => {},
+            r => panic!("expected an invalid hex error, got {:?}", r),
+        }
+    }
+
+    fn test_attributes(value: &[u8]) -> TxAttributes {
+        let mut attributes = TxAttributes::new();
+        attributes.insert(0, value.to_vec());
+        attributes
+    }
+
+    #[test]
+    fn tx_attributes_round_trip() {
+        let mut raw = RawCbor::from(TX);
+        let tx : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+        assert!(tx.attributes.is_empty());
+
+        let mut tagged = tx.clone();
+        let mut attributes = TxAttributes::new();
+        attributes.insert(2, b"invoice 42".to_vec());
+        tagged.set_attributes(attributes);
+        let bytes = cbor!(&tagged).unwrap();
+        assert_eq!(&bytes[..TX.len() - 1], &TX[..TX.len() - 1]);
+        assert_eq!(&bytes[TX.len() - 1..], &[0xa1, 0x02, 0x4a, 0x69, 0x6e, 0x76, 0x6f, 0x69, 0x63, 0x65, 0x20, 0x34, 0x32][..]);
+
+        let mut raw = RawCbor::from(&bytes[..]);
+        let decoded : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+        assert_eq!(decoded, tagged);
+        assert_eq!(decoded.attributes.get(2), Some(&b"invoice 42"[..]));
+        assert!(decoded.id() != tx.id());
+
+        // every attribute is kept and encoded back as found
+        let mut unknown = TX[..TX.len() - 1].to_vec();
+        unknown.extend_from_slice(&[0xa2, 0x01, 0x41, 0x00, 0x18, 0x2a, 0x40]);
+        let mut raw = RawCbor::from(&unknown[..]);
+        let decoded : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+        assert_eq!(decoded.attributes.len(), 2);
+        assert_eq!(decoded.attributes.get(42), Some(&[][..]));
+        assert_eq!(decoded.to_canonical_cbor(), unknown);
+
+        // keys out of the canonical order, or not fitting a byte, are rejected
+        for attributes in [&[0xa2, 0x18, 0x2a, 0x40, 0x01, 0x41, 0x00][..], &[0xa2, 0x01, 0x40, 0x01, 0x40][..], &[0xa1, 0x19, 0x01, 0x00, 0x40][..]].iter() {
+            let mut invalid = TX[..TX.len() - 1].to_vec();
+            invalid.extend_from_slice(attributes);
+            let mut raw = RawCbor::from(&invalid[..]);
+            assert!(<Tx as cbor_event::de::Deserialize>::deserialize(&mut raw).is_err());
+        }
+    }
+
+    #[test]
+    fn tx_canonical_cbor_is_stable() {
+        let mut raw = RawCbor::from(TX);
+        let tx : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+
+        let bytes = tx.to_canonical_cbor();
+        assert_eq!(&bytes[..], TX);
+        let mut raw = RawCbor::from(&bytes[..]);
+        let decoded : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+        assert_eq!(decoded.to_canonical_cbor(), bytes);
+
+        let mut tagged = tx.clone();
+        tagged.set_attributes(test_attributes(&[1, 2, 3]));
+        let bytes = tagged.to_canonical_cbor();
+        let mut raw = RawCbor::from(&bytes[..]);
+        let decoded : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+        assert_eq!(decoded.to_canonical_cbor(), bytes);
+    }
+
+    #[test]
+    fn tx_write_cbor_matches_canonical_cbor() {
+        let mut tx : Tx = RawCbor::from(TX).deserialize().unwrap();
+        let txout : TxOut = RawCbor::from(TX_OUT).deserialize().unwrap();
+
+        let mut bytes = Vec::new();
+        tx.write_cbor(&mut bytes).unwrap();
+        assert_eq!(&bytes[..], TX);
+
+        for i in 0..5_000 {
+            tx.add_output(TxOut::new(txout.address.clone(), Coin::new(i).unwrap()));
+        }
+        tx.set_attributes(test_attributes(&[1, 2, 3]));
+        let mut bytes = Vec::new();
+        tx.write_cbor(&mut bytes).unwrap();
+        assert_eq!(bytes, tx.to_canonical_cbor());
+    }
+
+    #[test]
+    fn txaux_semantically_eq_ignores_witness_order() {
+        let txout : TxOut = RawCbor::from(TX_OUT).deserialize().unwrap();
+        let ptr = |n| TxoPointer::new(TxId::new(&[n]), 0);
+        let tx = Tx::new_with(vec![ptr(1), ptr(2)], vec![txout]);
+
+        let protocol_magic = ProtocolMagic::default();
+        let witness = |i| {
+            let sk = hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([i;hdwallet::SEED_SIZE]));
+            TxInWitness::new(protocol_magic, &sk, &tx.id())
+        };
+        let txaux = TxAux::new(tx.clone(), TxWitness::from(vec![witness(1), witness(2)]));
+        let swapped = TxAux::new(tx.clone(), TxWitness::from(vec![witness(2), witness(1)]));
+
+        assert!(txaux != swapped);
+        assert!(txaux.semantically_eq(&swapped, protocol_magic));
+        assert!(swapped.semantically_eq(&txaux, protocol_magic));
+        assert!(txaux.semantically_eq(&txaux, protocol_magic));
+
+        // same witnesses count but not the same witnesses
+        let duplicated = TxAux::new(tx.clone(), TxWitness::from(vec![witness(1), witness(1)]));
+        assert!(!txaux.semantically_eq(&duplicated, protocol_magic));
+        assert!(!duplicated.semantically_eq(&txaux, protocol_magic));
+        // not one witness per input
+        let missing = TxAux::new(tx.clone(), TxWitness::from(vec![witness(1)]));
+        assert!(!missing.semantically_eq(&missing, protocol_magic));
+        // not the same transaction
+        let mut other = tx.clone();
+        other.set_attributes(test_attributes(&[1]));
+        assert!(!txaux.semantically_eq(&TxAux::new(other, TxWitness::from(vec![witness(1), witness(2)])), protocol_magic));
+
+        // the same witnesses, but not valid signatures of the transaction
+        assert!(!txaux.semantically_eq(&swapped, ProtocolMagic::from(1097911063)));
+        let fake = TxAux::new(tx.clone(), TxWitness::from(vec![TxInWitness::fake(), TxInWitness::fake()]));
+        assert!(!fake.semantically_eq(&fake, protocol_magic));
+    }
+
+    #[test]
+    fn tx_witness_signs_the_canonical_cbor() {
+        let mut raw = RawCbor::from(TX);
+        let tx : Tx = cbor_event::de::Deserialize::deserialize(&mut raw).unwrap();
+        let txid = TxId::new(&tx.to_canonical_cbor());
+        assert_eq!(tx.id(), txid);
+
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let protocol_magic = ProtocolMagic::default();
+        let witness = TxInWitness::new(protocol_magic, &sk, &txid);
+        assert!(witness.verify_tx(protocol_magic, &tx));
+
+        let mut other = tx.clone();
+        other.set_attributes(test_attributes(&[0]));
+        assert!(!witness.verify_tx(protocol_magic, &other));
+    }
+
+    #[test]
+    fn tx_witness_signs_the_tagged_txid() {
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let txid = TxId::new(&[0;32]);
+        let witness = TxInWitness::new(ProtocolMagic::default(), &sk, &txid);
+
+        // tag `Tx` || protocol magic 764824073 || txid, each CBOR encoded
+        let mut expected = vec![0x01, 0x1a, 0x2d, 0x96, 0x4a, 0x09, 0x58, 0x20];
+        expected.extend_from_slice(txid.as_ref());
+
+        match witness {
+            TxInWitness::PkWitness(ref pk, ref sig) => {
+                assert_eq!(pk, &sk.public());
+                assert_eq!(sig, &sk.sign::<Tx>(&expected));
+                assert!(pk.verify(&expected, sig));
+                // no domain separation, or the redeem one
+                assert!(!pk.verify(txid.as_ref(), sig));
+                assert!(!pk.verify(&expected[1..], sig));
+                let mut redeem = expected.clone();
+                redeem[0] = SigningTag::RedeemTx as u8;
+                assert!(!pk.verify(&redeem, sig));
+            },
+            _ => panic!("expected a public key witness"),
+        }
+    }
+
+    #[test]
+    fn txinwitness_reconstruct_the_input_address() {
+        let protocol_magic = ProtocolMagic::default();
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let pk = sk.public();
+
+        let hdap = hdpayload::HDAddressPayload::from_bytes(HDPAYLOAD);
+        let attrs = address::Attributes::new_single_key(&pk, Some(hdap));
+        let ea = address::ExtendedAddr::new(address::AddrType::ATPubKey, address::SpendingData::PubKeyASD(pk), attrs.clone());
+
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(TxId::new(&[0;32]), 0));
+        tx.add_output(TxOut::new(ea.clone(), Coin::new(42).unwrap()));
+        let witness = TxInWitness::bootstrap(&Config::default(), &sk, &tx, &attrs);
+        assert_eq!(witness, TxInWitness::new(protocol_magic, &sk, &tx.id()));
+
+        // the extended public key, including the chain code, is in the witness
+        match witness {
+            TxInWitness::PkWitness(ref xpub, _) => assert_eq!(xpub.as_ref(), pk.as_ref()),
+            _ => panic!("expected a public key witness"),
+        }
+        assert_eq!(witness.reconstruct_address(&ea.attributes), Some(ea.clone()));
+        assert!(witness.reconstruct_address(&address::Attributes::new_bootstrap_era(None)) != Some(ea.clone()));
+        assert!(witness.verify(protocol_magic, &ea, &tx));
+
+        let testnet = Config::testnet(1097911063);
+        let witness = TxInWitness::bootstrap(&testnet, &sk, &tx, &attrs);
+        assert_eq!(witness.reconstruct_address(&ea.attributes), Some(ea.clone()));
+        assert!(witness.verify(testnet.protocol_magic, &ea, &tx));
+        assert!(!witness.verify(protocol_magic, &ea, &tx));
+    }
+
+    #[test]
+    fn total_fees_of_transactions() {
+        let txout : TxOut = RawCbor::from(TX_OUT).deserialize().unwrap();
+        let output = |v| TxOut::new(txout.address.clone(), Coin::new(v).unwrap());
+        let ptr = |n| TxoPointer::new(TxId::new(&[n]), 0);
+
+        let tx1 = TxAux::new(Tx::new_with(vec![ptr(1), ptr(2)], vec![output(1_500)]), TxWitness::new());
+        let tx2 = TxAux::new(Tx::new_with(vec![ptr(3)], vec![output(800), output(150)]), TxWitness::new());
+
+        let lookup = |p: &TxoPointer| {
+            [(ptr(1), 1_000), (ptr(2), 700), (ptr(3), 1_000)].iter()
+                .find(|(x, _)| x == p)
+                .map(|&(_, v)| Coin::new(v).unwrap())
+        };
+
+        // fee of tx1: 1700 - 1500, fee of tx2: 1000 - 950
+        assert_eq!(total_fees(::std::slice::from_ref(&tx1), lookup), Ok(Coin::new(200).unwrap()));
+        assert_eq!(total_fees(::std::slice::from_ref(&tx2), lookup), Ok(Coin::new(50).unwrap()));
+        assert_eq!(total_fees(&[tx1.clone(), tx2.clone()], lookup), Ok(Coin::new(250).unwrap()));
+        assert_eq!(total_fees(&[], lookup), Ok(Coin::zero()));
+
+        let tx3 = TxAux::new(Tx::new_with(vec![ptr(4)], vec![output(1)]), TxWitness::new());
+        assert_eq!(total_fees(&[tx1, tx3], lookup), Err(TotalFeesError::UnknownInput(ptr(4))));
+    }
 }
 
 
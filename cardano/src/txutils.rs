@@ -23,6 +23,32 @@ pub enum OutputPolicy {
     One(ExtendedAddr),
 }
 
+/// Order of the outputs of a built transaction.
+///
+/// The order is applied to all the outputs, including the change, before
+/// the transaction is signed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OutputOrdering {
+    /// keep the outputs in the given order, the change being last
+    #[default]
+    AsGiven,
+    /// sort the outputs by ascending value
+    ByValue,
+    /// sort the outputs by their address' binary encoding
+    ByAddress,
+}
+impl OutputOrdering {
+    /// reorder the given outputs, the sort is stable so outputs comparing
+    /// equal keep their relative order.
+    pub fn apply(&self, outputs: &mut [TxOut]) {
+        match *self {
+            OutputOrdering::AsGiven => {},
+            OutputOrdering::ByValue => outputs.sort_by_key(|output| output.value),
+            OutputOrdering::ByAddress => outputs.sort_by_key(|output| output.address.to_bytes()),
+        }
+    }
+}
+
 /// This is a Resolved version of a `TxoPointer`.
 ///
 /// It contains the `TxoPointer` which is the value we need to put in the
@@ -48,3 +74,143 @@ impl<Addressing> Input<Addressing> {
 pub fn output_sum<'a, O: 'a + Iterator<Item = &'a TxOut>>(o: O) -> coin::Result<Coin> {
     o.fold(Coin::new(0), |acc, ref c| acc.and_then(|v| v + c.value))
 }
+
+pub fn input_sum<'a, Addressing: 'a, I: 'a + Iterator<Item = &'a Input<Addressing>>>(i: I) -> coin::Result<Coin> {
+    output_sum(i.map(|input| &input.value))
+}
+
+/// An ordered list of resolved transaction inputs.
+///
+/// ```
+/// use cardano::txutils::{Inputs, Input};
+/// use cardano::tx::{TxOut, TxoPointer, TxId};
+/// use cardano::coin::{self, Coin, MAX_COIN};
+/// # use cardano::address::ExtendedAddr;
+/// # use cardano::hdwallet::{XPrv, Seed, SEED_SIZE};
+/// # let address = ExtendedAddr::new_simple(XPrv::generate_from_seed(&Seed::from_bytes([0;SEED_SIZE])).public());
+///
+/// let mut inputs = Inputs::new();
+/// let ptr = TxoPointer::new(TxId::new(&[0;32]), 0);
+/// inputs.push(Input::new(ptr.clone(), TxOut::new(address.clone(), Coin::new(MAX_COIN).unwrap()), ()));
+/// assert_eq!(inputs.total_value(), Coin::new(MAX_COIN));
+///
+/// inputs.push(Input::new(ptr, TxOut::new(address, Coin::unit()), ()));
+/// assert_eq!(inputs.total_value(), Err(coin::Error::OutOfBound(MAX_COIN + 1)));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub struct Inputs<Addressing>(Vec<Input<Addressing>>);
+impl<Addressing> Inputs<Addressing> {
+    pub fn new() -> Self { Inputs(Vec::new()) }
+
+    /// build the inputs from UTxOs (e.g. the result of a blockchain query)
+    /// with the addressing of their address.
+    ///
+    /// see [`rindex::Wallet::inputs_from_utxos`](../wallet/rindex/struct.Wallet.html#method.inputs_from_utxos)
+    /// to recover the addressing from the wallet.
+    pub fn from_utxos<I>(utxos: I) -> Self
+        where I: IntoIterator<Item = (TxoPointer, TxOut, Addressing)>
+    {
+        Inputs(utxos.into_iter().map(|(ptr, value, addressing)| Input::new(ptr, value, addressing)).collect())
+    }
+
+    pub fn push(&mut self, input: Input<Addressing>) { self.0.push(input) }
+
+    /// the sum of all the inputs' values, fails with `coin::Error::OutOfBound`
+    /// if it is above `MAX_COIN`.
+    pub fn total_value(&self) -> coin::Result<Coin> { input_sum(self.0.iter()) }
+
+    pub fn into_vec(self) -> Vec<Input<Addressing>> { self.0 }
+}
+impl<Addressing> Default for Inputs<Addressing> {
+    fn default() -> Self { Inputs::new() }
+}
+impl<Addressing> ::std::ops::Deref for Inputs<Addressing> {
+    type Target = Vec<Input<Addressing>>;
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+impl<Addressing> From<Vec<Input<Addressing>>> for Inputs<Addressing> {
+    fn from(inputs: Vec<Input<Addressing>>) -> Self { Inputs(inputs) }
+}
+impl<Addressing> From<Inputs<Addressing>> for Vec<Input<Addressing>> {
+    fn from(inputs: Inputs<Addressing>) -> Self { inputs.0 }
+}
+
+/// An ordered list of transaction outputs.
+///
+/// The outputs are kept as given: two outputs to the same address are
+/// not merged, so they appear as distinct `TxOut`s in the transaction.
+///
+/// ```
+/// use cardano::txutils::Outputs;
+/// use cardano::tx::TxOut;
+/// use cardano::coin::Coin;
+/// # use cardano::address::ExtendedAddr;
+/// # use cardano::hdwallet::{XPrv, Seed, SEED_SIZE};
+/// # let address = ExtendedAddr::new_simple(XPrv::generate_from_seed(&Seed::from_bytes([0;SEED_SIZE])).public());
+///
+/// let mut outputs = Outputs::new();
+/// outputs.push(TxOut::new(address.clone(), Coin::new(1_000).unwrap()));
+/// outputs.push(TxOut::new(address, Coin::new(42).unwrap()));
+///
+/// assert_eq!(outputs.len(), 2);
+/// assert_eq!(outputs.total_value().unwrap(), Coin::new(1_042).unwrap());
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub struct Outputs(Vec<TxOut>);
+impl Outputs {
+    pub fn new() -> Self { Outputs(Vec::new()) }
+
+    pub fn push(&mut self, output: TxOut) { self.0.push(output) }
+
+    /// the sum of all the outputs' values, fails with `coin::Error::OutOfBound`
+    /// if it is above `MAX_COIN`.
+    pub fn total_value(&self) -> coin::Result<Coin> { output_sum(self.0.iter()) }
+
+    pub fn into_vec(self) -> Vec<TxOut> { self.0 }
+}
+impl ::std::ops::Deref for Outputs {
+    type Target = Vec<TxOut>;
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+impl From<Vec<TxOut>> for Outputs {
+    fn from(outputs: Vec<TxOut>) -> Self { Outputs(outputs) }
+}
+impl From<Outputs> for Vec<TxOut> {
+    fn from(outputs: Outputs) -> Self { outputs.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coin::MAX_COIN;
+    use test_helpers::{address, input};
+
+    #[test]
+    fn inputs_total_value_near_cap() {
+        let coin = |value| Coin::new(value).unwrap();
+        let inputs = Inputs::from(vec![input(0, coin(MAX_COIN - 10)), input(1, coin(10))]);
+        assert_eq!(inputs.total_value(), Coin::new(MAX_COIN));
+
+        let inputs = Inputs::from(vec![input(0, coin(MAX_COIN - 10)), input(1, coin(10)), input(2, coin(1))]);
+        assert_eq!(inputs.total_value(), Err(coin::Error::OutOfBound(MAX_COIN + 1)));
+
+        let inputs = Inputs::from(vec![input(0, coin(MAX_COIN)), input(1, coin(MAX_COIN))]);
+        assert_eq!(inputs.total_value(), Err(coin::Error::OutOfBound(2 * MAX_COIN)));
+        assert_eq!(Inputs::<()>::new().total_value(), Ok(Coin::zero()));
+    }
+
+    #[test]
+    fn outputs_total_value_near_cap() {
+        let output = |value| TxOut::new(address(), Coin::new(value).unwrap());
+
+        let outputs = Outputs::from(vec![output(MAX_COIN / 2), output(MAX_COIN / 2)]);
+        assert_eq!(outputs.total_value(), Coin::new(MAX_COIN / 2 * 2));
+
+        let mut outputs = Outputs::from(vec![output(MAX_COIN), output(MAX_COIN)]);
+        assert_eq!(outputs.total_value(), Err(coin::Error::OutOfBound(2 * MAX_COIN)));
+        outputs.push(output(MAX_COIN));
+        assert!(outputs.total_value().is_err());
+    }
+}
@@ -3,3 +3,4 @@ pub mod hex;
 pub mod base58;
 pub mod bits;
 pub mod try_from_slice;
+pub mod text_envelope;
@@ -0,0 +1,242 @@
+//! the JSON text envelope of the `cardano-cli` tooling, wrapping a CBOR
+//! encoded object with its type and a description:
+//!
+//! ```text
+//! {
+//!     "type": "TxSignedByron",
+//!     "description": "",
+//!     "cborHex": "8283..."
+//! }
+//! ```
+//!
+//! Only the flat objects of string values used by the envelopes are
+//! supported, this is not a generic JSON parser.
+//!
+//! # Example
+//!
+//! ```
+//! use cardano::util::text_envelope::TextEnvelope;
+//!
+//! let envelope = TextEnvelope::new("TxSignedByron", "my \"payment\"", &[0x82, 0x01]);
+//! let parsed = TextEnvelope::parse(&envelope.to_json()).unwrap();
+//! assert_eq!(parsed, envelope);
+//! assert_eq!(parsed.cbor_hex, "8201");
+//! ```
+//!
+use std::{result, fmt, char};
+use util::hex;
+
+/// text envelope parsing errors
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// the text is not a JSON object of string values, contains the index
+    /// of the faulty byte
+    InvalidJson(usize),
+    /// the field is given more than once
+    DuplicateField(String),
+    /// one of the `type`, `description` or `cborHex` fields is missing
+    MissingField(&'static str),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidJson(idx) => write!(f, "Invalid text envelope JSON at byte index {}", idx),
+            Error::DuplicateField(ref field) => write!(f, "Field `{}` is given more than once", field),
+            Error::MissingField(field) => write!(f, "Missing field `{}` in the text envelope", field),
+        }
+    }
+}
+impl ::std::error::Error for Error {}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// a text envelope, the fields are kept as found in the JSON
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEnvelope {
+    /// the `type` of the object, e.g. `TxSignedByron`
+    pub envelope_type: String,
+    pub description: String,
+    /// the hexadecimal CBOR encoding of the object
+    pub cbor_hex: String,
+}
+impl TextEnvelope {
+    pub fn new(envelope_type: &str, description: &str, cbor: &[u8]) -> Self {
+        TextEnvelope {
+            envelope_type: envelope_type.to_owned(),
+            description: description.to_owned(),
+            cbor_hex: hex::encode(cbor),
+        }
+    }
+
+    /// parse the JSON text envelope, the unknown fields are ignored
+    pub fn parse(json: &str) -> Result<Self> {
+        let mut parser = Parser { input: json.as_bytes(), pos: 0 };
+        let fields = parser.object()?;
+
+        let field = |name: &'static str| {
+            fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone()).ok_or(Error::MissingField(name))
+        };
+        Ok(TextEnvelope {
+            envelope_type: field("type")?,
+            description: field("description")?,
+            cbor_hex: field("cborHex")?,
+        })
+    }
+
+    /// the JSON text envelope, indented as written by `cardano-cli`
+    pub fn to_json(&self) -> String {
+        format!("{{\n    \"type\": {},\n    \"description\": {},\n    \"cborHex\": {}\n}}\n",
+                json_string(&self.envelope_type),
+                json_string(&self.description),
+                json_string(&self.cbor_hex))
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+impl<'a> Parser<'a> {
+    fn skip_whitespaces(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.input.get(self.pos).cloned() {
+            self.pos += 1;
+        }
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let byte = self.input.get(self.pos).cloned().ok_or(Error::InvalidJson(self.pos))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        self.skip_whitespaces();
+        if self.input.get(self.pos) != Some(&expected) { return Err(Error::InvalidJson(self.pos)); }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn object(&mut self) -> Result<Vec<(String, String)>> {
+        let mut fields : Vec<(String, String)> = Vec::new();
+        self.expect(b'{')?;
+        self.skip_whitespaces();
+        if self.input.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                let key = self.string()?;
+                self.expect(b':')?;
+                let value = self.string()?;
+                if fields.iter().any(|(k, _)| k == &key) { return Err(Error::DuplicateField(key)); }
+                fields.push((key, value));
+
+                self.skip_whitespaces();
+                match self.next()? {
+                    b',' => continue,
+                    b'}' => break,
+                    _ => return Err(Error::InvalidJson(self.pos - 1)),
+                }
+            }
+        }
+        self.skip_whitespaces();
+        if self.pos != self.input.len() { return Err(Error::InvalidJson(self.pos)); }
+        Ok(fields)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        let mut out = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"'  => '"',
+                        b'\\' => '\\',
+                        b'/'  => '/',
+                        b'b'  => '\u{8}',
+                        b'f'  => '\u{c}',
+                        b'n'  => '\n',
+                        b'r'  => '\r',
+                        b't'  => '\t',
+                        b'u'  => self.unicode_escape()?,
+                        _ => return Err(Error::InvalidJson(self.pos - 1)),
+                    };
+                    let mut buf = [0u8;4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                },
+                byte if byte < 0x20 => return Err(Error::InvalidJson(self.pos - 1)),
+                byte => out.push(byte),
+            }
+        }
+        String::from_utf8(out).map_err(|_| Error::InvalidJson(start))
+    }
+
+    // the 4 hexadecimal digits following `\u`, the surrogate pairs are not
+    // supported: they are not expected in the envelopes.
+    fn unicode_escape(&mut self) -> Result<char> {
+        let start = self.pos;
+        let digits = self.input.get(start..start + 4).ok_or(Error::InvalidJson(start))?;
+        let mut code = 0u32;
+        for &digit in digits {
+            let value = match digit {
+                b'0'..=b'9' => digit - b'0',
+                b'a'..=b'f' => digit - b'a' + 10,
+                b'A'..=b'F' => digit - b'A' + 10,
+                _ => return Err(Error::InvalidJson(start)),
+            };
+            code = code << 4 | value as u32;
+        }
+        self.pos += 4;
+        char::from_u32(code).ok_or(Error::InvalidJson(start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cardano_cli_envelope() {
+        let json = "{\n    \"type\": \"TxSignedByron\",\n    \"description\": \"a \\\"tx\\\"\\n\\u00e9\",\n    \"cborHex\": \"8201\"\n}\n";
+        let envelope = TextEnvelope::parse(json).unwrap();
+        assert_eq!(envelope.envelope_type, "TxSignedByron");
+        assert_eq!(envelope.description, "a \"tx\"\n\u{e9}");
+        assert_eq!(envelope.cbor_hex, "8201");
+
+        let reordered = "{\"cborHex\":\"8201\",\"extra\":\"\",\"description\":\"\",\"type\":\"TxSignedByron\"}";
+        assert_eq!(TextEnvelope::parse(reordered).unwrap(), TextEnvelope::new("TxSignedByron", "", &[0x82, 0x01]));
+
+        let envelope = TextEnvelope::new("TxSignedByron", "tab\tand \\ \u{1}", &[0xff]);
+        assert_eq!(TextEnvelope::parse(&envelope.to_json()).unwrap(), envelope);
+    }
+
+    #[test]
+    fn parse_invalid_envelopes() {
+        assert_eq!(TextEnvelope::parse(""), Err(Error::InvalidJson(0)));
+        assert_eq!(TextEnvelope::parse("{\"type\": 1}"), Err(Error::InvalidJson(9)));
+        assert_eq!(TextEnvelope::parse("{\"type\": \"a\"} x"), Err(Error::InvalidJson(14)));
+        assert_eq!(TextEnvelope::parse("{\"type\": \"a\", \"type\": \"b\"}"), Err(Error::DuplicateField("type".to_owned())));
+        assert_eq!(TextEnvelope::parse("{\"type\": \"a\", \"description\": \"\"}"), Err(Error::MissingField("cborHex")));
+        assert_eq!(TextEnvelope::parse("{\"type\": \"\\x\"}"), Err(Error::InvalidJson(11)));
+    }
+}
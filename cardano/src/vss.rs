@@ -1,4 +1,5 @@
 use cbor_event::{self, de::RawCbor, se::{Serializer}};
+use cbor::hs::util::checked_bytes;
 use std::{fmt, result};
 use util::hex;
 
@@ -34,7 +35,7 @@ impl cbor_event::se::Serialize for PublicKey {
 }
 impl cbor_event::de::Deserialize for PublicKey {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        let bytes = raw.bytes()?;
+        let bytes = checked_bytes(raw)?;
         Ok(PublicKey(Vec::from(bytes.as_ref())))
     }
 }
@@ -86,7 +87,7 @@ impl cbor_event::se::Serialize for Signature {
 }
 impl cbor_event::de::Deserialize for Signature {
     fn deserialize<'a>(raw: &mut RawCbor<'a>) -> cbor_event::Result<Self> {
-        match Self::from_slice(raw.bytes()?.as_ref()) {
+        match Self::from_slice(checked_bytes(raw)?.as_ref()) {
             Ok(sig) => Ok(sig),
             Err(Error::InvalidSignatureSize(sz)) => {
                 Err(cbor_event::Error::NotEnough(SIGNATURE_SIZE, sz))
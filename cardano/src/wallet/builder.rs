@@ -0,0 +1,253 @@
+//! incremental construction of wallet transactions
+//!
+//! Where [`Wallet::new_transaction`](../scheme/trait.Wallet.html#method.new_transaction)
+//! takes all the inputs and outputs at once, the
+//! [`TransactionBuilder`](./struct.TransactionBuilder.html) allows to
+//! add them one by one, to inspect the running totals and to finally
+//! select the inputs, compute the fee and the change and sign the
+//! transaction with the wallet.
+//!
+//! For a lower level, wallet agnostic, builder see
+//! [`txbuild::TxBuilder`](../../txbuild/struct.TxBuilder.html).
+//!
+
+use std::{fmt, error, result};
+use tx::{TxAux, TxOut};
+use txutils::{self, Input, OutputPolicy};
+use coin::{self, Coin};
+use fee::{Fee, LinearFee};
+use input_selection::{self, SelectionPolicy};
+use address::{ExtendedAddr};
+use config::{Config, ProtocolMagic};
+
+use super::scheme::{Wallet};
+
+#[derive(Debug)]
+pub enum Error {
+    /// no change address has been set, see
+    /// [`TransactionBuilder::set_change_address`](./struct.TransactionBuilder.html#method.set_change_address)
+    NoChangeAddress,
+    SelectionError(input_selection::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoChangeAddress => write!(f, "No change address set in the transaction builder"),
+            Error::SelectionError(_) => write!(f, "Error while building the transaction"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::SelectionError(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+impl From<input_selection::Error> for Error {
+    fn from(e: input_selection::Error) -> Error { Error::SelectionError(e) }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// transaction builder, collects the available inputs and the outputs
+/// of a transaction before building it with a
+/// [`Wallet`](../scheme/trait.Wallet.html).
+///
+/// The inputs are the candidates for the input selection: not all of
+/// them will necessarily be spent by the built transaction.
+///
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder<Addressing> {
+    inputs: Vec<Input<Addressing>>,
+    outputs: Vec<TxOut>,
+    fee_algorithm: LinearFee,
+    selection_policy: SelectionPolicy,
+    change_address: Option<ExtendedAddr>,
+}
+impl<Addressing> Default for TransactionBuilder<Addressing> {
+    fn default() -> Self { TransactionBuilder::new() }
+}
+impl<Addressing> TransactionBuilder<Addressing> {
+    /// create an empty builder using the default fee algorithm and
+    /// selection policy.
+    pub fn new() -> Self {
+        TransactionBuilder {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee_algorithm: LinearFee::default(),
+            selection_policy: SelectionPolicy::default(),
+            change_address: None,
+        }
+    }
+
+    /// add an input available to the input selection
+    pub fn add_input(&mut self, input: Input<Addressing>) -> &mut Self {
+        self.inputs.push(input);
+        self
+    }
+
+    /// add an output to the transaction
+    pub fn add_output(&mut self, output: TxOut) -> &mut Self {
+        self.outputs.push(output);
+        self
+    }
+
+    pub fn set_fee_algorithm(&mut self, fee_algorithm: LinearFee) -> &mut Self {
+        self.fee_algorithm = fee_algorithm;
+        self
+    }
+
+    pub fn set_selection_policy(&mut self, selection_policy: SelectionPolicy) -> &mut Self {
+        self.selection_policy = selection_policy;
+        self
+    }
+
+    /// set the address receiving the change of the transaction, if any.
+    pub fn set_change_address(&mut self, change_address: ExtendedAddr) -> &mut Self {
+        self.change_address = Some(change_address);
+        self
+    }
+
+    pub fn fee_algorithm(&self) -> LinearFee { self.fee_algorithm }
+    pub fn selection_policy(&self) -> SelectionPolicy { self.selection_policy }
+
+    pub fn inputs(&self) -> &[Input<Addressing>] { &self.inputs }
+    pub fn outputs(&self) -> &[TxOut] { &self.outputs }
+
+    /// the sum of the values of all the inputs added so far
+    pub fn total_input(&self) -> coin::Result<Coin> {
+        txutils::input_sum(self.inputs.iter())
+    }
+
+    /// the sum of the values of all the outputs added so far
+    pub fn total_output(&self) -> coin::Result<Coin> {
+        txutils::output_sum(self.outputs.iter())
+    }
+
+    /// select the inputs, compute the fee and the change and sign the
+    /// resulting transaction with the given wallet.
+    ///
+    /// The protocol magic is only needed to sign the transaction, so the same
+    /// builder can be built for different networks (see
+    /// [`Config`](../../config/struct.Config.html)).
+    ///
+    /// The limits of the default configuration apply (see
+    /// [`Wallet::new_transaction_with_config`](../scheme/trait.Wallet.html#method.new_transaction_with_config)).
+    ///
+    /// The builder is left untouched so it can be amended and built
+    /// again.
+    ///
+    pub fn build<W>(&self, wallet: &W, protocol_magic: ProtocolMagic) -> Result<(TxAux, Fee)>
+        where W: Wallet<Addressing = Addressing>
+            , Addressing: Clone
+    {
+        let output_policy = match self.change_address {
+            Some(ref change_address) => OutputPolicy::One(change_address.clone()),
+            None => return Err(Error::NoChangeAddress),
+        };
+
+        let config = Config { protocol_magic, fee_policy: self.fee_algorithm, ..Config::default() };
+        let r = wallet.new_transaction_with_config( &config
+                                                  , self.selection_policy
+                                                  , self.inputs.iter()
+                                                  , self.outputs.clone()
+                                                  , &output_policy
+                                                  )?;
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tx::{TxId, TxoPointer};
+    use wallet::rindex;
+    use wallet::scheme::{Wallet as WalletScheme};
+    use test_helpers::{rindex_wallet, foreign_address};
+
+    fn make_inputs(wallet: &rindex::Wallet) -> Vec<Input<rindex::Addressing>> {
+        let generator = wallet.address_generator();
+        [rindex::Addressing::new(0, 1), rindex::Addressing::new(0, 2)]
+            .iter().enumerate()
+            .map(|(idx, addressing)| {
+                let ptr = TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = TxOut::new(generator.address(addressing), Coin::new(1_000_000).unwrap());
+                Input::new(ptr, value, *addressing)
+            }).collect()
+    }
+
+    #[test]
+    fn two_outputs_same_as_new_transaction() {
+        let wallet = rindex_wallet();
+        let inputs = make_inputs(&wallet);
+        let outputs = vec![
+            TxOut::new(foreign_address(1), Coin::new(300_000).unwrap()),
+            TxOut::new(foreign_address(2), Coin::new(400_000).unwrap()),
+        ];
+        let change_address = wallet.address_generator().address(&rindex::Addressing::new(0, 3));
+
+        let mut builder = TransactionBuilder::new();
+        for input in inputs.iter() { builder.add_input(input.clone()); }
+        builder.add_output(outputs[0].clone())
+               .add_output(outputs[1].clone())
+               .set_change_address(change_address.clone());
+
+        assert_eq!(builder.total_input(), Coin::new(2_000_000));
+        assert_eq!(builder.total_output(), Coin::new(700_000));
+
+        let (txaux, fee) = builder.build(&wallet, ProtocolMagic::default()).unwrap();
+        let (expected_txaux, expected_fee) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &OutputPolicy::One(change_address)
+        ).unwrap();
+
+        assert_eq!(fee, expected_fee);
+        assert_eq!(txaux, expected_txaux);
+    }
+
+    #[test]
+    fn build_for_another_network() {
+        let wallet = rindex_wallet();
+        let inputs = make_inputs(&wallet);
+        let change_address = wallet.address_generator().address(&rindex::Addressing::new(0, 3));
+
+        let mut builder = TransactionBuilder::new();
+        for input in inputs.iter() { builder.add_input(input.clone()); }
+        builder.add_output(TxOut::new(foreign_address(1), Coin::new(300_000).unwrap()))
+               .set_change_address(change_address)
+               .set_selection_policy(SelectionPolicy::FirstMatchFirst);
+        assert_eq!(builder.selection_policy(), SelectionPolicy::FirstMatchFirst);
+        assert_eq!(builder.fee_algorithm(), LinearFee::default());
+
+        let mainnet = Config::mainnet();
+        let testnet = Config::testnet(1097911063);
+        let (mainnet_txaux, mainnet_fee) = builder.build(&wallet, mainnet.protocol_magic).unwrap();
+        let (testnet_txaux, testnet_fee) = builder.build(&wallet, testnet.protocol_magic).unwrap();
+
+        assert_eq!(mainnet_txaux.tx, testnet_txaux.tx);
+        assert_eq!(mainnet_fee, testnet_fee);
+        let witness = &testnet_txaux.witness[0];
+        let address = &inputs[0].value.address;
+        assert!(witness.verify(testnet.protocol_magic, address, &testnet_txaux.tx));
+        assert!(!witness.verify(mainnet.protocol_magic, address, &testnet_txaux.tx));
+        assert!(mainnet_txaux.witness[0].verify(mainnet.protocol_magic, address, &mainnet_txaux.tx));
+    }
+
+    #[test]
+    fn no_change_address() {
+        let wallet = rindex_wallet();
+        let mut builder = TransactionBuilder::new();
+        for input in make_inputs(&wallet) { builder.add_input(input); }
+        builder.add_output(TxOut::new(foreign_address(1), Coin::new(300_000).unwrap()));
+
+        match builder.build(&wallet, ProtocolMagic::default()) {
+            Err(Error::NoChangeAddress) => {},
+            _ => panic!("expected a missing change address error"),
+        }
+    }
+}
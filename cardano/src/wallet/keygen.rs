@@ -1,5 +1,9 @@
 use bip::bip39;
+use hdwallet::{XPrv, XPRV_SIZE};
 
+use cbor_event;
+use cryptoxide::digest::{Digest};
+use cryptoxide::blake2b::{Blake2b};
 use cryptoxide::hmac::{Hmac};
 use cryptoxide::sha2::{Sha512};
 use cryptoxide::pbkdf2::{pbkdf2};
@@ -14,4 +18,47 @@ pub fn generate_seed(entropy: &Entropy, password: &[u8], output: &mut [u8]) {
     const ITER : u32 = 4096;
     let mut mac = Hmac::new(Sha512::new(), password);
     pbkdf2(&mut mac, entropy.as_ref(), ITER, output)
-}
\ No newline at end of file
+}
+
+/// the scheme used to stretch a wallet's entropy into its root key.
+///
+/// The wallets of the different Cardano eras used different schemes, a
+/// wallet restored with the wrong scheme derives entirely different keys
+/// and addresses.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub enum SeedScheme {
+    /// Daedalus (random index) wallets: the root key is generated from the
+    /// CBOR encoded Blake2b 256 digest of the CBOR encoded entropy, the
+    /// password is not used.
+    V1,
+    /// current wallets: the root key is the PBKDF2 (HMAC-SHA512, 4096
+    /// iterations) of the entropy and the password, see `generate_seed`.
+    #[default]
+    V2,
+}
+
+/// compute the root key of the wallet of the given entropy and password
+/// with the given seed scheme.
+pub fn root_key(seed_scheme: SeedScheme, entropy: &Entropy, password: &[u8]) -> XPrv {
+    match seed_scheme {
+        SeedScheme::V1 => {
+            let entropy_cbor = cbor_event::se::Serializer::new_vec()
+                .write_bytes(entropy.as_ref()).expect("serialize entropy")
+                .finalize();
+            let mut digest = [0;32];
+            let mut blake2b = Blake2b::new(32);
+            blake2b.input(&entropy_cbor);
+            blake2b.result(&mut digest);
+            let seed = cbor_event::se::Serializer::new_vec()
+                .write_bytes(&digest[..]).expect("serialize seed")
+                .finalize();
+            XPrv::generate_from_daedalus_seed(&seed)
+        },
+        SeedScheme::V2 => {
+            let mut seed = [0u8; XPRV_SIZE];
+            generate_seed(entropy, password, &mut seed);
+            XPrv::normalize_bytes(seed)
+        },
+    }
+}
@@ -1,4 +1,5 @@
 pub mod bip44;
 pub mod rindex;
 pub mod scheme;
+pub mod builder;
 pub mod keygen;
\ No newline at end of file
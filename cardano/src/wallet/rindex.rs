@@ -1,10 +1,8 @@
 /// 2 Level of randomly chosen hard derivation indexes Wallet
 ///
 
-use std::{ops::Deref, iter, fmt, error};
+use std::{ops::Deref, iter, fmt, error, collections::BTreeMap};
 use cbor_event;
-use cryptoxide;
-use cryptoxide::digest::{Digest};
 use bip::bip39;
 use hdwallet::{self, XPrv, XPub, DerivationScheme};
 use hdpayload;
@@ -12,13 +10,14 @@ use fee::{self, FeeAlgorithm};
 use coin::{self, Coin};
 use txutils::{self, OutputPolicy};
 use tx::{self, TxAux, Tx, TxId, TxInWitness};
-use address::{ExtendedAddr, Attributes, AddrType, SpendingData};
+use address::{ExtendedAddr, Attributes, AddrType, SpendingData, StakeDistribution};
 use config::ProtocolMagic;
 use input_selection;
 
 use super::scheme::{self};
+use super::keygen;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct Addressing(u32, u32);
 impl Addressing {
@@ -69,6 +68,73 @@ impl Wallet {
         Ok(Wallet::from_root_key(derivation_scheme, root_key))
     }
 
+    fn hdkey(&self) -> hdpayload::HDKey {
+        hdpayload::HDKey::new(&self.root_key.public())
+    }
+
+    /// encrypt the given addressing with the wallet's root public key,
+    /// the same way it is done to build the wallet's addresses.
+    ///
+    /// This is useful to build the addresses outside of the wallet (e.g.
+    /// with a public key only) while still being recognized by the wallet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cardano::hdwallet::{Seed, XPrv, SEED_SIZE, DerivationScheme};
+    /// # use cardano::wallet::rindex::{Wallet, RootKey, Addressing};
+    /// let xprv = XPrv::generate_from_seed(&Seed::from_bytes([0;SEED_SIZE]));
+    /// let wallet = Wallet::from_root_key(DerivationScheme::V1, RootKey::new(xprv, DerivationScheme::V1));
+    ///
+    /// let addressing = Addressing::new(0, 42);
+    /// let payload = wallet.encrypt_addressing(&addressing);
+    ///
+    /// assert_eq!(wallet.decrypt_addressing(&payload), Some(addressing));
+    /// ```
+    pub fn encrypt_addressing(&self, addressing: &Addressing) -> hdpayload::HDAddressPayload {
+        self.hdkey().encrypt_path(&hdpayload::Path::new(vec![addressing.0, addressing.1]))
+    }
+
+    /// decrypt the given payload with the wallet's root public key.
+    ///
+    /// Returns `None` if the payload was not encrypted by this wallet or if
+    /// it does not contain a valid addressing. Note that this does not prove
+    /// the address holding this payload belongs to the wallet, see
+    /// [`check_address`](./struct.Wallet.html#method.check_address).
+    pub fn decrypt_addressing(&self, payload: &hdpayload::HDAddressPayload) -> Option<Addressing> {
+        let path = self.hdkey().decrypt_path(payload).ok()?;
+        if path.len() == 2 {
+            Some(Addressing(path[0], path[1]))
+        } else {
+            None
+        }
+    }
+
+    /// rebuild the address of the wallet at the given addressing with the
+    /// given attributes, the derivation path being replaced by the
+    /// encrypted addressing.
+    ///
+    /// This is how [`check_address`](./struct.Wallet.html#method.check_address)
+    /// verifies an address: e.g. a stored address still belongs to the
+    /// wallet if it is rebuilt unchanged from its own attributes.
+    pub fn reconstruct_address(&self, addressing: &Addressing, attributes: &Attributes) -> ExtendedAddr {
+        self.root_key.address_generator().reconstruct_address(addressing, attributes)
+    }
+
+    /// create the address of the wallet at the given addressing with the
+    /// given attributes, e.g. to choose the stake distribution of the
+    /// address instead of the bootstrap era one of
+    /// [`AddressGenerator::address`](./struct.AddressGenerator.html#method.address).
+    ///
+    /// This is an alias of
+    /// [`reconstruct_address`](./struct.Wallet.html#method.reconstruct_address)
+    /// taking the attributes by value: the derivation path of `attributes`
+    /// is replaced by the encrypted addressing, so the address is
+    /// recognized by [`check_address`](./struct.Wallet.html#method.check_address).
+    pub fn new_address_with_attributes(&self, addressing: &Addressing, attributes: Attributes) -> ExtendedAddr {
+        self.reconstruct_address(addressing, &attributes)
+    }
+
     /// test that the given address belongs to the wallet.
     ///
     /// This only possible because addresses from this wallet contain
@@ -79,24 +145,19 @@ impl Wallet {
     /// to this wallet, otherwise it returns `None`
     pub fn check_address(&self, address: &ExtendedAddr) -> Option<Addressing>
     {
-        let hdkey = hdpayload::HDKey::new(&self.root_key.public());
-
         // This wallet has has only one account
         let account : &RootKey = scheme::Wallet::list_accounts(self);
         if let &Some(ref hdpa) = &address.attributes.derivation_path {
-            if let Ok(path) = hdkey.decrypt_path(hdpa) {
-                let addressing = Addressing(path.as_ref()[0], path.as_ref()[1]);
-
+            if let Some(addressing) = self.decrypt_addressing(hdpa) {
                 // regenerate the address to prevent HDAddressPayload reuse
                 //
                 // i.e. it is possible to a mean player to reuse existing
                 // payload in their own addresses to make recipient believe
                 // they have received funds. This check prevents that to happen.
-                let addresses = scheme::Account::generate_addresses(account, [addressing].iter());
-
-                debug_assert!(addresses.len() == 1, "we expect to generate only one address here...");
-
-                if address == &addresses[0] {
+                //
+                // the address is regenerated with its own stake distribution
+                // (see `AddressGenerator::address_with_stake_distribution`).
+                if account.address_generator().compare_address(address, &addressing).is_ok() {
                     return Some(addressing);
                 }
             }
@@ -105,6 +166,174 @@ impl Wallet {
         None
     }
 
+    /// the detailed outcome of [`check_address`](./struct.Wallet.html#method.check_address)
+    /// for each of the given addresses (e.g. the outputs of a block), with
+    /// the position of the address, to find out why an address is not
+    /// recognized.
+    ///
+    /// An address of the wallet gives its addressing, otherwise the error
+    /// tells at which step the recognition failed: no derivation path
+    /// (`Error::NoDerivationPath`), a derivation path of another wallet
+    /// (`Error::NotMyPayload`), an invalid decrypted path
+    /// (`Error::InvalidPayloadAddressing`) or an address which cannot be
+    /// rebuilt from the wallet's keys (e.g. `Error::CannotReconstructAddress`).
+    pub fn diagnose_addresses(&self, addrs: &[ExtendedAddr]) -> Vec<(usize, Result<Addressing>)> {
+        let account : &RootKey = scheme::Wallet::list_accounts(self);
+        let generator = account.address_generator();
+        addrs.iter().enumerate().map(|(i, address)| {
+            let diagnosis = if address.attributes.derivation_path.is_none() {
+                Err(Error::NoDerivationPath)
+            } else {
+                generator.try_get_addressing(address)
+                    .and_then(|found| found.ok_or(Error::NotMyPayload))
+                    .and_then(|addressing| generator.compare_address(address, &addressing).map(|()| addressing))
+            };
+            (i, diagnosis)
+        }).collect()
+    }
+
+    /// test that the output is sent to an address of the wallet (see
+    /// [`check_address`](./struct.Wallet.html#method.check_address)), e.g.
+    /// to reconcile a UTxO set against the wallet.
+    pub fn owns_output(&self, out: &tx::TxOut) -> bool {
+        self.check_address(&out.address).is_some()
+    }
+
+    /// compute the balance of the wallet from a given UTxO set
+    ///
+    /// Only the outputs recognised by the wallet (see
+    /// [`check_address`](./struct.Wallet.html#method.check_address)) are
+    /// accounted for, the others are simply ignored.
+    pub fn balance<'a, I>(&self, utxos: I) -> coin::Result<Coin>
+        where I: Iterator<Item = &'a tx::TxOut>
+    {
+        let mut total = Coin::zero();
+        for txout in utxos {
+            if self.owns_output(txout) {
+                total = (total + txout.value)?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// compute the balance of every address of the wallet present in a
+    /// given UTxO set, sorted by addressing.
+    ///
+    /// The outputs not recognised by the wallet (see
+    /// [`check_address`](./struct.Wallet.html#method.check_address)) are
+    /// ignored. Fails if the balance of an address is above `MAX_COIN`.
+    pub fn address_balances<'a, I>(&self, utxos: I) -> coin::Result<Vec<(Addressing, ExtendedAddr, Coin)>>
+        where I: Iterator<Item = &'a tx::TxOut>
+    {
+        let mut balances : Vec<(Addressing, ExtendedAddr, Coin)> = Vec::new();
+        for txout in utxos {
+            let addressing = match self.check_address(&txout.address) {
+                None => continue,
+                Some(addressing) => addressing,
+            };
+            match balances.iter().position(|(_, address, _)| address == &txout.address) {
+                Some(i) => balances[i].2 = (balances[i].2 + txout.value)?,
+                None => balances.push((addressing, txout.address.clone(), txout.value)),
+            }
+        }
+        balances.sort_by_key(|&(addressing, _, _)| addressing);
+        Ok(balances)
+    }
+
+    /// load the UTxOs from a snapshot (see [`utxo_snapshot`](./fn.utxo_snapshot.html))
+    ///
+    /// every UTxO's address is checked against the wallet (see
+    /// [`check_address`](./struct.Wallet.html#method.check_address)) to
+    /// retrieve its addressing. If one of the UTxO does not belong to the
+    /// wallet, an `Error::UtxoNotRecognized` is returned.
+    pub fn load_utxo_snapshot(&self, snapshot: &[u8]) -> Result<Vec<txutils::Input<Addressing>>> {
+        let mut raw = cbor_event::de::RawCbor::from(snapshot);
+        let len = match raw.array()? {
+            cbor_event::Len::Len(len) => len,
+            cbor_event::Len::Indefinite => {
+                return Err(Error::CBorEncoding(cbor_event::Error::CustomError("Invalid UTxO snapshot: expected a definite length array".to_owned())));
+            }
+        };
+
+        // the length is not trusted: every UTxO takes more than one byte of
+        // the snapshot, don't reserve more than the snapshot can hold
+        let mut inputs = Vec::with_capacity(::std::cmp::min(len, raw.len() as u64) as usize);
+        for _ in 0..len {
+            raw.tuple(2, "UTxO")?;
+            let ptr : tx::TxoPointer = cbor_event::de::Deserialize::deserialize(&mut raw)?;
+            let value : tx::TxOut = cbor_event::de::Deserialize::deserialize(&mut raw)?;
+
+            match self.check_address(&value.address) {
+                None => return Err(Error::UtxoNotRecognized(ptr)),
+                Some(addressing) => inputs.push(txutils::Input::new(ptr, value, addressing)),
+            }
+        }
+        Ok(inputs)
+    }
+
+    /// build the inputs of a transaction from UTxOs (e.g. the result of a
+    /// blockchain query), the addressing of every UTxO is retrieved with
+    /// [`check_address`](./struct.Wallet.html#method.check_address).
+    ///
+    /// If one of the UTxO does not belong to the wallet, an
+    /// `Error::UtxoNotRecognized` is returned.
+    pub fn inputs_from_utxos<I>(&self, utxos: I) -> Result<txutils::Inputs<Addressing>>
+        where I: IntoIterator<Item = (tx::TxoPointer, tx::TxOut)>
+    {
+        let mut inputs = Vec::new();
+        for (ptr, value) in utxos {
+            match self.check_address(&value.address) {
+                None => return Err(Error::UtxoNotRecognized(ptr)),
+                Some(addressing) => inputs.push((ptr, value, addressing)),
+            }
+        }
+        Ok(txutils::Inputs::from_utxos(inputs))
+    }
+
+    /// check every output of the transaction against the wallet (see
+    /// [`check_address`](./struct.Wallet.html#method.check_address)), e.g.
+    /// to verify the change of a transaction is actually spendable by the
+    /// wallet before broadcasting it.
+    ///
+    /// Returns, for each output index, the addressing of the output or an
+    /// `Error::UtxoNotRecognized` with the pointer the output would have
+    /// once the transaction is in the blockchain.
+    pub fn verify_self_outputs(&self, tx: &Tx) -> Vec<(usize, Result<Addressing>)> {
+        let txid = tx.id();
+        tx.outputs.iter().enumerate().map(|(index, output)| {
+            match self.check_address(&output.address) {
+                None => (index, Err(Error::UtxoNotRecognized(tx::TxoPointer::new(txid, index as u32)))),
+                Some(addressing) => (index, Ok(addressing)),
+            }
+        }).collect()
+    }
+
+    /// sign a single input of a transaction, e.g. when the transaction
+    /// spends inputs from different wallets (multi-party spend) and
+    /// every party provides the witnesses of its own inputs.
+    ///
+    /// The input's address is checked against the wallet (see
+    /// [`check_address`](./struct.Wallet.html#method.check_address)) to
+    /// retrieve the key to sign with. If it does not belong to the wallet
+    /// an `Error::UtxoNotRecognized` is returned, if the transaction does
+    /// not spend the given input an `Error::InputNotInTransaction` is
+    /// returned.
+    ///
+    /// The witnesses can then be collected in the `TxAux` with
+    /// `TxAux::add_witness`, in the order of the transaction's inputs.
+    pub fn sign_input_for(&self, protocol_magic: ProtocolMagic, tx: &Tx, ptr: &tx::TxoPointer, utxo: &tx::TxOut) -> Result<TxInWitness> {
+        if ! tx.inputs.contains(ptr) {
+            return Err(Error::InputNotInTransaction(ptr.clone()));
+        }
+        match self.check_address(&utxo.address) {
+            None => Err(Error::UtxoNotRecognized(ptr.clone())),
+            Some(addressing) => {
+                let mut witnesses = scheme::Wallet::sign_tx(self, protocol_magic, &tx.id(), iter::once(&addressing));
+                Ok(witnesses.remove(0))
+            }
+        }
+    }
+
     pub fn move_transaction(&self, protocol_magic: ProtocolMagic, inputs: &Vec<txutils::TxoPointerInfo<Addressing>>, output_policy: &txutils::OutputPolicy) -> input_selection::Result<(TxAux, fee::Fee)> {
 
         if inputs.len() == 0 {
@@ -118,8 +347,7 @@ impl Wallet {
         let total_input : Coin = {
             let mut total = Coin::zero();
             for ref i in inputs.iter() {
-                let acc = total + i.value;
-                total = acc?
+                total = total.checked_add(i.value).ok_or(input_selection::Error::CoinOverflow)?;
             }
             total
         };
@@ -203,13 +431,19 @@ impl scheme::Wallet for Wallet {
         where I: Iterator<Item = &'a Self::Addressing>
     {
         let mut witnesses = vec![];
+        // inputs often share the same address, derive each key only once
+        let mut keys = BTreeMap::new();
 
         for addressing in addresses {
-            let key = self.root_key
-                          .derive(self.derivation_scheme, addressing.0)
-                          .derive(self.derivation_scheme, addressing.1);
+            log_debug!("signing transaction {} with the key of {:?}", txid, addressing);
+            let key = keys.entry(*addressing).or_insert_with(|| {
+                log_debug!("deriving the key of {:?}", addressing);
+                self.root_key
+                    .derive(self.derivation_scheme, addressing.0)
+                    .derive(self.derivation_scheme, addressing.1)
+            });
 
-            let tx_witness = TxInWitness::new(protocol_magic, &key, txid);
+            let tx_witness = TxInWitness::new(protocol_magic, key, txid);
             witnesses.push(tx_witness);
         }
         witnesses
@@ -244,7 +478,25 @@ pub enum Error {
     ///    put it in one of its address);
     /// 3. that the software needs to be updated.
     ///
-    CannotReconstructAddress
+    CannotReconstructAddress,
+
+    /// the UTxO at the given pointer does not belong to the wallet
+    UtxoNotRecognized(tx::TxoPointer),
+
+    /// the input at the given pointer is not spent by the transaction
+    InputNotInTransaction(tx::TxoPointer),
+
+    /// the address is not a public key address (e.g. a redeem address),
+    /// it cannot be derived from the wallet's keys.
+    UnsupportedSpendingData(AddrType),
+
+    /// the address has no derivation path in its attributes, it is not an
+    /// address of a random index wallet
+    NoDerivationPath,
+
+    /// the derivation path of the address was not encrypted with the
+    /// wallet's root public key
+    NotMyPayload,
 }
 impl From<bip39::Error> for Error {
     fn from(e: bip39::Error) -> Self { Error::Bip39Error(e) }
@@ -263,10 +515,15 @@ impl fmt::Display for Error {
         match self {
             Error::Bip39Error(_) => write!(f, "Wallet's Mnemonic Error"),
             Error::DerivationError(_) => write!(f, "Invalid key derivation"),
-            Error::PayloadError(_) => write!(f, "Error while deocoding an address' payload"),
+            Error::PayloadError(_) => write!(f, "Error while decoding an address' payload"),
             Error::CBorEncoding(_) => write!(f, "Error while encoding address in binary format"),
             Error::InvalidPayloadAddressing => write!(f, "Payload has been decoded but is corrupted or of unexpected format"),
-            Error::CannotReconstructAddress => write!(f, "The address cannot be reconstructuted: the payload has been decoded but the public key hash seems different.")
+            Error::CannotReconstructAddress => write!(f, "The address cannot be reconstructed: the payload has been decoded but the public key hash seems different."),
+            Error::UtxoNotRecognized(ref ptr) => write!(f, "The UTxO {} does not belong to this wallet", ptr),
+            Error::InputNotInTransaction(ref ptr) => write!(f, "The input {} is not spent by the transaction", ptr),
+            Error::UnsupportedSpendingData(ref addr_type) => write!(f, "Unsupported address type `{}`, only public key addresses are derived from the wallet", addr_type),
+            Error::NoDerivationPath => write!(f, "The address has no derivation path"),
+            Error::NotMyPayload => write!(f, "The address' derivation path was not encrypted by this wallet"),
         }
     }
 }
@@ -279,12 +536,35 @@ impl error::Error for Error {
             Error::CBorEncoding(ref err) => Some(err),
             Error::InvalidPayloadAddressing => None,
             Error::CannotReconstructAddress => None,
+            Error::UtxoNotRecognized(_) => None,
+            Error::InputNotInTransaction(_) => None,
+            Error::UnsupportedSpendingData(_) => None,
+            Error::NoDerivationPath => None,
+            Error::NotMyPayload => None,
         }
     }
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
+/// serialize the given UTxOs in a snapshot, to be reloaded with
+/// [`Wallet::load_utxo_snapshot`](./struct.Wallet.html#method.load_utxo_snapshot).
+///
+/// Only the `TxoPointer` and the `TxOut` are saved, the addressing is
+/// recovered from the address when loading the snapshot.
+pub fn utxo_snapshot<'a, I>(utxos: I) -> Result<Vec<u8>>
+    where I: ExactSizeIterator<Item = &'a txutils::Input<Addressing>>
+{
+    let mut serializer = cbor_event::se::Serializer::new_vec()
+        .write_array(cbor_event::Len::Len(utxos.len() as u64))?;
+    for utxo in utxos {
+        serializer = serializer.write_array(cbor_event::Len::Len(2))?
+                               .serialize(&utxo.ptr)?
+                               .serialize(&utxo.value)?;
+    }
+    Ok(serializer.finalize())
+}
+
 #[derive(Clone)]
 pub struct RootKey {
     root_key: XPrv,
@@ -303,17 +583,7 @@ impl RootKey {
         let mnemonics = bip39::Mnemonics::from_string(dic, &mnemonics_phrase)?;
         let entropy = bip39::Entropy::from_mnemonics(&mnemonics)?;
 
-        let entropy_bytes = cbor_event::Value::Bytes(Vec::from(entropy.as_ref()));
-        let entropy_cbor = cbor!(&entropy_bytes)?;
-        let seed : Vec<u8> = {
-            let mut blake2b = cryptoxide::blake2b::Blake2b::new(32);
-            blake2b.input(&entropy_cbor);
-            let mut out = [0;32];
-            blake2b.result(&mut out);
-            cbor_event::se::Serializer::new_vec().write_bytes(&Vec::from(&out[..]))?.finalize()
-        };
-
-        let xprv = XPrv::generate_from_daedalus_seed(&seed);
+        let xprv = keygen::root_key(keygen::SeedScheme::V1, &entropy, &[]);
         Ok(RootKey::new(xprv, derivation_scheme))
     }
 
@@ -376,13 +646,23 @@ impl<K> AddressGenerator<K> {
         } else { Ok(None) }
     }
 
-    fn compare_address_with_pubkey(&self, address: &ExtendedAddr, path: &Addressing, key: XPub) -> Result<()> {
+    /// the address of the given public key with the given attributes, the
+    /// derivation path being replaced by the encrypted `path`.
+    fn address_with_pubkey(&self, path: &Addressing, key: XPub, attributes: &Attributes) -> ExtendedAddr {
         let payload = self.hdkey.encrypt_path(&hdpayload::Path::new(vec![path.0, path.1]));
 
-        let mut attributes = address.attributes.clone();
+        let mut attributes = attributes.clone();
         attributes.derivation_path = Some(payload);
 
-        let expected = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key), attributes);
+        ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key), attributes)
+    }
+
+    fn compare_address_with_pubkey(&self, address: &ExtendedAddr, path: &Addressing, key: XPub) -> Result<()> {
+        if address.addr_type != AddrType::ATPubKey {
+            return Err(Error::UnsupportedSpendingData(address.addr_type));
+        }
+
+        let expected = self.address_with_pubkey(path, key, &address.attributes);
         if &expected == address {
             Ok(())
         } else {
@@ -418,10 +698,7 @@ impl AddressGenerator<XPub> {
     /// create an address with the given addressing
     pub fn address(&self, path: &Addressing) -> Result<ExtendedAddr> {
         let key = self.key(path)?;
-
-        let payload = self.hdkey.encrypt_path(&hdpayload::Path::new(vec![path.0, path.1]));
-        let attributes = Attributes::new_bootstrap_era(Some(payload));
-        Ok(ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key), attributes))
+        Ok(self.address_with_pubkey(path, key, &Attributes::new_bootstrap_era(None)))
     }
 }
 impl AddressGenerator<XPrv> {
@@ -451,11 +728,22 @@ impl AddressGenerator<XPrv> {
 
     /// create an address with the given addressing
     pub fn address(&self, path: &Addressing) -> ExtendedAddr {
-        let key = self.key(path).public();
+        self.address_with_stake_distribution(path, StakeDistribution::BootstrapEraDistr)
+    }
 
-        let payload = self.hdkey.encrypt_path(&hdpayload::Path::new(vec![path.0, path.1]));
-        let attributes = Attributes::new_bootstrap_era(Some(payload));
-        ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key), attributes)
+    /// create an address with the given addressing and stake distribution
+    /// (e.g. `StakeDistribution::new_single_key`) instead of the bootstrap
+    /// era one used by [`address`](#method.address).
+    pub fn address_with_stake_distribution(&self, path: &Addressing, stake_distribution: StakeDistribution) -> ExtendedAddr {
+        let attributes = Attributes { derivation_path: None, stake_distribution };
+        self.reconstruct_address(path, &attributes)
+    }
+
+    /// rebuild the address of the given addressing with the given
+    /// attributes (e.g. the attributes of a stored address), only the
+    /// derivation path is replaced by the one of `path`.
+    pub fn reconstruct_address(&self, path: &Addressing, attributes: &Attributes) -> ExtendedAddr {
+        self.address_with_pubkey(path, self.key(path).public(), attributes)
     }
 
     /// attempt the reconstruct the address with the same metadata
@@ -494,3 +782,1287 @@ impl<'a, I> Iterator for AddressIterator<XPub, I>
         self.iter.next().map(|path| { self.generator.address(path) })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hdwallet;
+    use test_helpers::{rindex_wallet, foreign_address, XorShift};
+
+    #[test]
+    fn balance_only_sums_owned_outputs() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let utxos = [
+            tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(1000).unwrap()),
+            tx::TxOut::new(foreign_address(1), Coin::new(5000).unwrap()),
+            tx::TxOut::new(generator.address(&Addressing::new(0, 2)), Coin::new(42).unwrap()),
+        ];
+
+        assert_eq!(wallet.balance(utxos.iter()), Ok(Coin::new(1042).unwrap()));
+        assert_eq!(wallet.balance(utxos[1..2].iter()), Ok(Coin::zero()));
+    }
+
+    #[test]
+    fn utxo_snapshot_round_trip() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let inputs : Vec<_> = [Addressing::new(0, 1), Addressing::new(0, 2), Addressing::new(1, 1)]
+            .iter().enumerate()
+            .map(|(idx, addressing)| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(addressing), Coin::new(1000).unwrap());
+                txutils::Input::new(ptr, value, *addressing)
+            })
+            .collect();
+
+        let snapshot = utxo_snapshot(inputs.iter()).unwrap();
+        assert_eq!(wallet.load_utxo_snapshot(&snapshot).unwrap(), inputs);
+    }
+
+    #[test]
+    fn utxo_snapshot_foreign_utxo() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[1;32]), 0);
+        let inputs = [
+            txutils::Input::new(tx::TxoPointer::new(TxId::new(&[0;32]), 0), tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::unit()), Addressing::new(0, 1)),
+            txutils::Input::new(ptr.clone(), tx::TxOut::new(foreign_address(1), Coin::unit()), Addressing::new(0, 2)),
+        ];
+
+        let snapshot = utxo_snapshot(inputs.iter()).unwrap();
+        match wallet.load_utxo_snapshot(&snapshot) {
+            Err(Error::UtxoNotRecognized(p)) => assert_eq!(p, ptr),
+            r => panic!("expected the foreign UTxO to be reported, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn utxo_snapshot_oversized_length() {
+        let wallet = rindex_wallet();
+        // an array announcing 2^64-1 UTxOs, followed by nothing
+        let snapshot = [0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert!(wallet.load_utxo_snapshot(&snapshot).is_err());
+    }
+
+    #[test]
+    fn balance_overflow() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let max = Coin::new(coin::MAX_COIN).unwrap();
+
+        let utxos = [
+            tx::TxOut::new(generator.address(&Addressing::new(0, 1)), max),
+            tx::TxOut::new(generator.address(&Addressing::new(0, 2)), Coin::unit()),
+        ];
+
+        assert_eq!(wallet.balance(utxos.iter()), Err(coin::Error::OutOfBound(coin::MAX_COIN + 1)));
+    }
+
+    #[test]
+    fn address_balances_per_address() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let lovelace = |v| Coin::new(v).unwrap();
+        let first = generator.address(&Addressing::new(0, 1));
+        let second = generator.address(&Addressing::new(0, 2));
+
+        let utxos = [
+            tx::TxOut::new(second.clone(), lovelace(5)),
+            tx::TxOut::new(first.clone(), lovelace(1_000)),
+            tx::TxOut::new(foreign_address(1), lovelace(1_000_000)),
+            tx::TxOut::new(second.clone(), lovelace(37)),
+            tx::TxOut::new(first.clone(), lovelace(2)),
+        ];
+        assert_eq!(wallet.address_balances(utxos.iter()), Ok(vec![
+            (Addressing::new(0, 1), first.clone(), lovelace(1_002)),
+            (Addressing::new(0, 2), second.clone(), lovelace(42)),
+        ]));
+
+        let max = lovelace(coin::MAX_COIN);
+        let utxos = [tx::TxOut::new(first.clone(), max), tx::TxOut::new(second, max), tx::TxOut::new(first, Coin::unit())];
+        assert_eq!(wallet.address_balances(utxos.iter()), Err(coin::Error::OutOfBound(coin::MAX_COIN + 1)));
+    }
+
+    #[test]
+    fn owns_output_mixed_ownership() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let value = Coin::new(1_000).unwrap();
+        // an address of another random index wallet
+        let other_xprv = XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([2;hdwallet::SEED_SIZE]));
+        let other = Wallet::from_root_key(DerivationScheme::V2, RootKey::new(other_xprv, DerivationScheme::V2));
+
+        let outputs = [
+            (tx::TxOut::new(generator.address(&Addressing::new(0, 1)), value), true),
+            (tx::TxOut::new(foreign_address(1), value), false),
+            (tx::TxOut::new(generator.address(&Addressing::new(3, 42)), value), true),
+            (tx::TxOut::new(other.address_generator().address(&Addressing::new(0, 1)), value), false),
+        ];
+        for (n, &(ref output, owned)) in outputs.iter().enumerate() {
+            assert_eq!(wallet.owns_output(output), owned, "output {}", n);
+        }
+    }
+
+    #[test]
+    fn required_funds_close_to_consumed_funds() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let change_addr = generator.address(&Addressing::new(0, 3));
+        let outputs = vec![
+            tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap()),
+            tx::TxOut::new(foreign_address(1), Coin::new(2_000_000).unwrap()),
+        ];
+
+        let required = wallet.required_funds(&outputs, &change_addr).unwrap();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let (_, fee) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &OutputPolicy::One(change_addr)
+        ).unwrap();
+        let consumed = (Coin::new(3_000_000).unwrap() + fee.to_coin()).unwrap();
+
+        let margin = Coin::new(5_000).unwrap();
+        assert!((required + margin).unwrap() >= consumed, "required {} but consumed {}", required, consumed);
+        assert!((consumed + margin).unwrap() >= required, "required {} but consumed {}", required, consumed);
+    }
+
+    #[test]
+    fn required_funds_with_matches_a_single_input_transaction() {
+        use wallet::scheme::{Wallet as WalletScheme};
+        use fee::Milli;
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let change_addr = generator.address(&Addressing::new(0, 3));
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(4_000_000).unwrap())];
+        let alg = fee::LinearFee::new(Milli::integral(200000), Milli::integral(50));
+
+        let required = wallet.required_funds_with(&alg, &outputs, &change_addr).unwrap();
+        assert!(required > wallet.required_funds(&outputs, &change_addr).unwrap());
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let (txaux, fee) = wallet.new_transaction_with(
+            ProtocolMagic::default(),
+            &alg,
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &OutputPolicy::One(change_addr)
+        ).unwrap();
+        assert_eq!(txaux.tx.inputs.len(), 1);
+        // the total input minus the change is what the transaction consumed
+        let change = txaux.tx.outputs[1].value;
+        let consumed = (Coin::new(10_000_000).unwrap() - change).unwrap();
+        assert_eq!(consumed, (Coin::new(4_000_000).unwrap() + fee.to_coin()).unwrap());
+
+        let margin = Coin::new(5_000).unwrap();
+        assert!((required + margin).unwrap() >= consumed, "required {} but consumed {}", required, consumed);
+        assert!((consumed + margin).unwrap() >= required, "required {} but consumed {}", required, consumed);
+    }
+
+    #[test]
+    fn sweep_sends_everything_to_the_destination() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let alg = fee::LinearFee::default();
+
+        let utxos : Vec<_> = [(Addressing::new(0, 1), 1_000_000), (Addressing::new(0, 2), 2_500_000)]
+            .iter().enumerate()
+            .map(|(idx, &(addressing, value))| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(&addressing), Coin::new(value).unwrap());
+                txutils::Input::new(ptr, value, addressing)
+            }).collect();
+
+        let (txaux, fee) = wallet.sweep(ProtocolMagic::default(), &alg, &utxos, &foreign_address(1)).unwrap();
+        assert_eq!(txaux.tx.inputs.len(), 2);
+        assert_eq!(txaux.witness.len(), 2);
+        assert_eq!(txaux.tx.outputs.len(), 1);
+        assert_eq!(txaux.tx.outputs[0].address, foreign_address(1));
+        assert_eq!(txaux.tx.outputs[0].value, (Coin::new(3_500_000).unwrap() - fee.to_coin()).unwrap());
+        assert!(fee.to_coin() >= alg.calculate_for_txaux(&txaux).unwrap().to_coin());
+
+        // the inputs do not cover the fee
+        let dust = vec![txutils::Input::new(utxos[0].ptr.clone(), tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(1_000).unwrap()), Addressing::new(0, 1))];
+        match wallet.sweep(ProtocolMagic::default(), &alg, &dust, &foreign_address(1)) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r.map(|_| ())),
+        }
+        match wallet.sweep(ProtocolMagic::default(), &alg, &[], &foreign_address(1)) {
+            Err(input_selection::Error::NoInputs) => {},
+            r => panic!("expected no inputs, got {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn new_transaction_without_inputs_or_outputs() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(1_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(500_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 2)));
+
+        #[cfg(feature = "logging")]
+        record_logs();
+
+        match wallet.new_transaction(ProtocolMagic::default(), input_selection::SelectionPolicy::default(), inputs[..0].iter(), outputs.clone(), &output_policy) {
+            Err(input_selection::Error::NoInputs) => {},
+            r => panic!("expected no inputs, got {:?}", r.map(|_| ())),
+        }
+        match wallet.new_transaction(ProtocolMagic::default(), input_selection::SelectionPolicy::default(), inputs.iter(), Vec::new(), &output_policy) {
+            Err(input_selection::Error::NoOutputs) => {},
+            r => panic!("expected no outputs, got {:?}", r.map(|_| ())),
+        }
+
+        // nothing was signed
+        #[cfg(feature = "logging")]
+        RECORDS.with(|records| assert!(!records.borrow().iter().any(|r| r.starts_with("signing transaction") || r.starts_with("deriving the key"))));
+    }
+
+    #[test]
+    fn build_payout_batch_pays_every_output_once() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let max_size = 2048;
+
+        let utxos : Vec<_> = (0..4).map(|idx| {
+            let addressing = Addressing::new(0, idx);
+            let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx);
+            let value = tx::TxOut::new(generator.address(&addressing), Coin::new(200_000_000).unwrap());
+            txutils::Input::new(ptr, value, addressing)
+        }).collect();
+        let outputs : Vec<_> = (0..500).map(|i| tx::TxOut::new(foreign_address(1), Coin::new(1_000_000 + i).unwrap())).collect();
+        let change_addressing = Addressing::new(0, 100);
+        let change_addr = generator.address(&change_addressing);
+
+        let batch = wallet.build_payout_batch(ProtocolMagic::default(), &utxos, &outputs, &change_addr, &change_addressing, max_size).unwrap();
+        assert!(batch.len() > 1);
+
+        let mut paid = Vec::new();
+        for (i, (txaux, fee)) in batch.iter().enumerate() {
+            assert!(tx::txaux_serialize_size(&txaux.tx, &txaux.witness) <= max_size);
+            assert_eq!(txaux.witness.len(), txaux.tx.inputs.len());
+            assert!(fee.to_coin() >= fee::LinearFee::default().calculate_for_txaux(txaux).unwrap().to_coin());
+            paid.extend(txaux.tx.outputs.iter().filter(|o| o.address != change_addr).cloned());
+
+            // the change is spent by the next transaction
+            if i > 0 && batch[i - 1].0.tx.outputs.last().map(|o| o.address == change_addr).unwrap_or(false) {
+                assert_eq!(txaux.tx.inputs[0].id, batch[i - 1].0.tx.id());
+            }
+        }
+        assert_eq!(paid, outputs);
+
+        // a single output larger than a transaction
+        match wallet.build_payout_batch(ProtocolMagic::default(), &utxos, &outputs, &change_addr, &change_addressing, 100) {
+            Err(input_selection::Error::TransactionTooLarge { .. }) => {},
+            r => panic!("expected too large transaction, got {:?}", r.map(|_| ())),
+        }
+        match wallet.build_payout_batch(ProtocolMagic::default(), &utxos[..1], &outputs, &change_addr, &change_addressing, max_size) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn max_spendable_plus_fee_is_the_balance() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let alg = fee::LinearFee::default();
+
+        let utxos : Vec<_> = [(Addressing::new(0, 1), 1_000_000), (Addressing::new(0, 2), 2_500_000), (Addressing::new(1, 7), 42_000)]
+            .iter().enumerate()
+            .map(|(idx, &(addressing, value))| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[1;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(&addressing), Coin::new(value).unwrap());
+                txutils::Input::new(ptr, value, addressing)
+            }).collect();
+
+        let max = wallet.max_spendable(&alg, &utxos, &foreign_address(1)).unwrap();
+        let (txaux, fee) = wallet.sweep(ProtocolMagic::default(), &alg, &utxos, &foreign_address(1)).unwrap();
+        assert_eq!(txaux.tx.outputs[0].value, max);
+        assert_eq!((max + fee.to_coin()).unwrap(), Coin::new(3_542_000).unwrap());
+
+        let dust = vec![txutils::Input::new(utxos[0].ptr.clone(), tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(1_000).unwrap()), Addressing::new(0, 1))];
+        match wallet.max_spendable(&alg, &dust, &foreign_address(1)) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn bump_fee_keeps_the_outputs() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let protocol_magic = ProtocolMagic::default();
+
+        let utxos : Vec<_> = [(Addressing::new(0, 1), 10_000_000), (Addressing::new(0, 2), 20_000_000)]
+            .iter().enumerate()
+            .map(|(idx, &(addressing, value))| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(&addressing), Coin::new(value).unwrap());
+                txutils::Input::new(ptr, value, addressing)
+            }).collect();
+        let change_addr = generator.address(&Addressing::new(0, 3));
+        let payment = tx::TxOut::new(foreign_address(1), Coin::new(4_000_000).unwrap());
+
+        let (original, original_fee) = wallet.new_transaction(
+            protocol_magic,
+            input_selection::SelectionPolicy::default(),
+            utxos[..1].iter(), vec![payment.clone()],
+            &OutputPolicy::One(change_addr.clone())
+        ).unwrap();
+        assert_eq!(original.tx.outputs.len(), 2);
+
+        let verify = |txaux: &TxAux| {
+            txaux.witness.len() == txaux.tx.inputs.len() &&
+            txaux.witness.iter().zip(utxos.iter()).all(|(witness, utxo)| witness.verify(protocol_magic, &utxo.value.address, &txaux.tx))
+        };
+
+        // the change covers the extra fee
+        let extra = Coin::new(100_000).unwrap();
+        let (bumped, fee) = wallet.bump_fee(protocol_magic, &original.tx, &utxos, extra, &change_addr).unwrap();
+        assert_eq!(bumped.tx.inputs, original.tx.inputs);
+        assert_eq!(bumped.tx.outputs.len(), 2);
+        assert_eq!(bumped.tx.outputs[0], payment);
+        assert_eq!(fee.to_coin(), (original_fee.to_coin() + extra).unwrap());
+        assert_eq!((bumped.tx.get_output_total().unwrap() + fee.to_coin()).unwrap(), utxos[0].value());
+        assert!(verify(&bumped));
+
+        // the change does not cover the extra fee, the second input is added
+        let extra = Coin::new(9_000_000).unwrap();
+        let (bumped, fee) = wallet.bump_fee(protocol_magic, &original.tx, &utxos, extra, &change_addr).unwrap();
+        assert_eq!(bumped.tx.inputs.len(), 2);
+        assert_eq!(bumped.tx.outputs[0], payment);
+        assert_eq!(bumped.tx.outputs[1].address, change_addr);
+        assert_eq!(fee.to_coin(), (original_fee.to_coin() + extra).unwrap());
+        assert_eq!((bumped.tx.get_output_total().unwrap() + fee.to_coin()).unwrap(), Coin::new(30_000_000).unwrap());
+        assert!(verify(&bumped));
+
+        match wallet.bump_fee(protocol_magic, &original.tx, &utxos[1..], extra, &change_addr) {
+            Err(input_selection::Error::MissingInput(ref ptr)) if *ptr == utxos[0].ptr => {},
+            r => panic!("expected a missing input, got {:?}", r.map(|_| ())),
+        }
+        match wallet.bump_fee(protocol_magic, &original.tx, &utxos[..1], extra, &change_addr) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn new_transaction_from_inputs_spends_exactly_the_selected_input() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let utxos : Vec<_> = [(Addressing::new(0, 1), 1_000_000), (Addressing::new(0, 2), 50_000_000)]
+            .iter().enumerate()
+            .map(|(idx, &(addressing, value))| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(&addressing), Coin::new(value).unwrap());
+                txutils::Input::new(ptr, value, addressing)
+            }).collect();
+        let selected = &utxos[1..];
+
+        let change_addr = generator.address(&Addressing::new(0, 3));
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap())];
+        let (txaux, fee) = wallet.new_transaction_from_inputs(
+            ProtocolMagic::default(),
+            selected,
+            outputs.clone(),
+            &OutputPolicy::One(change_addr.clone())
+        ).unwrap();
+
+        assert_eq!(txaux.tx.inputs, vec![selected[0].ptr.clone()]);
+        assert_eq!(txaux.tx.outputs.len(), 2);
+        assert_eq!(txaux.tx.outputs[0], outputs[0]);
+        assert_eq!(txaux.tx.outputs[1].address, change_addr);
+
+        // the declared fee is what the transaction pays, and it covers the
+        // fee of the signed transaction
+        let total_output = txaux.tx.get_output_total().unwrap();
+        assert_eq!((total_output + fee.to_coin()).unwrap(), selected[0].value());
+        let minimal_fee = fee::LinearFee::default().calculate_for_txaux(&txaux).unwrap();
+        assert!(fee.to_coin() >= minimal_fee.to_coin());
+
+        // the selected input alone does not cover the outputs
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(50_000_000).unwrap())];
+        match wallet.new_transaction_from_inputs(ProtocolMagic::default(), selected, outputs, &OutputPolicy::One(change_addr)) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn explain_transaction_matches_new_transaction() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let utxos : Vec<_> = [(Addressing::new(0, 1), 1_000_000), (Addressing::new(0, 2), 50_000_000)]
+            .iter().enumerate()
+            .map(|(idx, &(addressing, value))| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(&addressing), Coin::new(value).unwrap());
+                txutils::Input::new(ptr, value, addressing)
+            }).collect();
+        let change_addr = generator.address(&Addressing::new(0, 3));
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(10_000_000).unwrap())];
+        let output_policy = OutputPolicy::One(change_addr.clone());
+
+        let plan = wallet.explain_transaction(
+            input_selection::SelectionPolicy::default(),
+            utxos.iter(),
+            outputs.clone(),
+            &output_policy
+        ).unwrap();
+        let (txaux, fee) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            utxos.iter(),
+            outputs.clone(),
+            &output_policy
+        ).unwrap();
+
+        assert_eq!(plan.to_tx(), txaux.tx);
+        assert_eq!(plan.fee, fee);
+        assert_eq!(plan.inputs.iter().map(|i| i.ptr.clone()).collect::<Vec<_>>(), txaux.tx.inputs);
+        assert_eq!(plan.outputs[0], outputs[0]);
+
+        let change = plan.change.clone().unwrap();
+        assert_eq!(change.address, change_addr);
+        assert_eq!(plan.outputs.last(), Some(&change));
+        let total_input = plan.inputs.iter().fold(Coin::zero(), |acc, i| (acc + i.value()).unwrap());
+        assert_eq!(((outputs[0].value + change.value).unwrap() + fee.to_coin()).unwrap(), total_input);
+    }
+
+    #[test]
+    fn estimate_fee_of_signed_transaction() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap())];
+        let (txaux, _) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &OutputPolicy::One(generator.address(&Addressing::new(0, 3)))
+        ).unwrap();
+
+        // the fake witnesses have the same size as the real ones
+        let estimated = wallet.estimate_fee(&txaux.tx, txaux.witness.len()).unwrap();
+        assert_eq!(estimated, fee::LinearFee::default().calculate_for_txaux(&txaux).unwrap());
+        assert!(wallet.estimate_fee(&txaux.tx, 2).unwrap() > estimated);
+    }
+
+    #[test]
+    fn new_transaction_keeps_outputs_to_the_same_address() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+
+        let payee = foreign_address(1);
+        let mut outputs = txutils::Outputs::new();
+        outputs.push(tx::TxOut::new(payee.clone(), Coin::new(1_000_000).unwrap()));
+        outputs.push(tx::TxOut::new(payee.clone(), Coin::new(1_000_000).unwrap()));
+        assert_eq!(outputs.total_value(), Coin::new(2_000_000));
+
+        let (txaux, _) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs.clone().into_vec(),
+            &OutputPolicy::One(generator.address(&Addressing::new(0, 3)))
+        ).unwrap();
+
+        assert_eq!(&txaux.tx.outputs[..2], &outputs[..]);
+        assert_eq!(txaux.tx.outputs.iter().filter(|o| o.address == payee).count(), 2);
+    }
+
+    #[test]
+    fn redeem_address_is_unsupported() {
+        use redeem;
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        // a redeem address carrying one of the wallet's payloads
+        let addressing = Addressing::new(0, 1);
+        let mut address = ExtendedAddr::new_redeem(redeem::PrivateKey::generate(&[3;32]).public());
+        address.attributes.derivation_path = Some(wallet.encrypt_addressing(&addressing));
+
+        assert_eq!(wallet.check_address(&address), None);
+        match generator.compare_address(&address, &addressing) {
+            Err(Error::UnsupportedSpendingData(AddrType::ATRedeem)) => {},
+            r => panic!("expected an unsupported spending data error, got {:?}", r),
+        }
+        assert!(generator.compare_address(&generator.address(&addressing), &addressing).is_ok());
+    }
+
+    #[test]
+    fn new_transaction_with_signer_signs_the_selected_inputs() {
+        use std::cell::RefCell;
+        use wallet::scheme::{Wallet as WalletScheme, TxSigner, WalletSigner};
+
+        struct MockSigner<'a> {
+            signer: WalletSigner<'a, Wallet>,
+            asked: RefCell<Vec<Addressing>>,
+        }
+        impl<'a> TxSigner<Addressing> for MockSigner<'a> {
+            fn sign_input(&self, addressing: &Addressing, tx: &Tx) -> TxInWitness {
+                self.asked.borrow_mut().push(*addressing);
+                self.signer.sign_input(addressing, tx)
+            }
+        }
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let utxos : Vec<_> = [(Addressing::new(0, 1), 1_000_000), (Addressing::new(0, 2), 2_000_000), (Addressing::new(1, 5), 3_000_000)]
+            .iter().enumerate()
+            .map(|(idx, &(addressing, value))| {
+                let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+                let value = tx::TxOut::new(generator.address(&addressing), Coin::new(value).unwrap());
+                txutils::Input::new(ptr, value, addressing)
+            }).collect();
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(4_000_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 3)));
+
+        let signer = MockSigner { signer: WalletSigner::new(&wallet, ProtocolMagic::default()), asked: RefCell::new(Vec::new()) };
+        let (txaux, fee) = wallet.new_transaction_with_signer(
+            input_selection::SelectionPolicy::default(),
+            utxos.iter(),
+            outputs.clone(),
+            &output_policy,
+            &signer
+        ).unwrap();
+
+        let plan = wallet.explain_transaction(input_selection::SelectionPolicy::default(), utxos.iter(), outputs.clone(), &output_policy).unwrap();
+        let selected : Vec<Addressing> = plan.inputs.iter().map(|i| i.addressing).collect();
+        assert_eq!(*signer.asked.borrow(), selected);
+        assert!(selected.len() >= 2);
+
+        // signing with the wallet's keys gives the same transaction
+        let expected = wallet.new_transaction(ProtocolMagic::default(), input_selection::SelectionPolicy::default(), utxos.iter(), outputs, &output_policy).unwrap();
+        assert_eq!((txaux, fee), expected);
+    }
+
+    #[test]
+    fn preview_transaction_agrees_with_new_transaction() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 3)));
+
+        let (tx, fee, change) = wallet.preview_transaction(
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs.clone(),
+            &output_policy
+        ).unwrap();
+        let (txaux, built_fee) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &output_policy
+        ).unwrap();
+
+        assert_eq!(fee, built_fee);
+        assert_eq!(tx, txaux.tx);
+        assert_eq!(Some(change), txaux.tx.outputs.last().map(|o| o.value));
+        assert_eq!(((Coin::new(1_000_000).unwrap() + change).unwrap() + fee.to_coin()).unwrap(), inputs[0].value());
+    }
+
+    #[test]
+    fn new_transaction_excluding_skips_reserved_utxos() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let inputs : Vec<_> = [10_000_000, 300_000, 300_000].iter().enumerate().map(|(idx, value)| {
+            let addressing = Addressing::new(0, idx as u32 + 1);
+            let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), idx as u32);
+            let value = tx::TxOut::new(generator.address(&addressing), Coin::new(*value).unwrap());
+            txutils::Input::new(ptr, value, addressing)
+        }).collect();
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 10)));
+        let new_transaction = |value, exclude: &[tx::TxoPointer]| {
+            let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(value).unwrap())];
+            wallet.new_transaction_excluding(
+                ProtocolMagic::default(),
+                input_selection::SelectionPolicy::default(),
+                inputs.iter(),
+                outputs,
+                &output_policy,
+                exclude
+            )
+        };
+        let spent = |txaux: &tx::TxAux| -> Vec<tx::TxoPointer> { txaux.tx.inputs.clone() };
+
+        // nothing excluded: the large UTxO alone is enough
+        let (txaux, _) = new_transaction(200_000, &[]).unwrap();
+        assert_eq!(spent(&txaux), vec![inputs[0].ptr.clone()]);
+
+        // the large UTxO is reserved: both small ones are needed
+        let (txaux, _) = new_transaction(200_000, &[inputs[0].ptr.clone()]).unwrap();
+        assert_eq!(spent(&txaux), vec![inputs[1].ptr.clone(), inputs[2].ptr.clone()]);
+
+        // the small ones cannot cover a bigger payment and its fee
+        match new_transaction(500_000, &[inputs[0].ptr.clone()]) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r),
+        }
+
+        let all : Vec<_> = inputs.iter().map(|input| input.ptr.clone()).collect();
+        match new_transaction(200_000, &all) {
+            Err(input_selection::Error::NotEnoughInput) => {},
+            r => panic!("expected not enough input, got {:?}", r),
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    thread_local!(static RECORDS: ::std::cell::RefCell<Vec<String>> = ::std::cell::RefCell::new(Vec::new()));
+
+    /// install a logger recording the messages logged by the current
+    /// thread, see `RECORDS`.
+    #[cfg(feature = "logging")]
+    fn record_logs() {
+        use log;
+
+        struct TestLogger;
+        impl log::Log for TestLogger {
+            fn enabled(&self, metadata: &log::Metadata) -> bool { metadata.level() <= log::Level::Debug }
+            fn log(&self, record: &log::Record) {
+                RECORDS.with(|records| records.borrow_mut().push(format!("{}", record.args())));
+            }
+            fn flush(&self) {}
+        }
+        static LOGGER: TestLogger = TestLogger;
+        // another test may have installed the logger already
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+        RECORDS.with(|records| records.borrow_mut().clear());
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn new_transaction_logs_the_selection() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        record_logs();
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let inputs : Vec<_> = (1..4).map(|index| {
+            let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), index);
+            let value = tx::TxOut::new(generator.address(&Addressing::new(0, index)), Coin::new(1_000_000).unwrap());
+            txutils::Input::new(ptr, value, Addressing::new(0, index))
+        }).collect();
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(1_500_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 10)));
+
+        let (txaux, fee) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &output_policy
+        ).unwrap();
+
+        let records = RECORDS.with(|records| records.borrow().clone());
+        assert!(records.iter().any(|r| r.starts_with("input selection: 2 input(s) selected out of 3")), "{:?}", records);
+        assert!(records.iter().any(|r| *r == format!("input selection: fee {}, change {}", fee.to_coin(), txaux.tx.outputs[1].value)), "{:?}", records);
+        assert_eq!(records.iter().filter(|r| r.starts_with("signing transaction")).count(), 2);
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn sign_tx_derives_each_key_once() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let addressings : Vec<_> = (0..20).map(|i| Addressing::new(0, 1 + i % 2)).collect();
+        let txid = TxId::new(&[0;32]);
+
+        record_logs();
+        let witnesses = wallet.sign_tx(ProtocolMagic::default(), &txid, addressings.iter());
+        let records = RECORDS.with(|records| records.borrow().clone());
+
+        assert_eq!(witnesses.len(), 20);
+        assert_eq!(records.iter().filter(|r| r.starts_with("signing transaction")).count(), 20);
+        assert_eq!(records.iter().filter(|r| r.starts_with("deriving the key")).count(), 2, "{:?}", records);
+
+        let generator = wallet.address_generator();
+        for (addressing, witness) in addressings.iter().zip(witnesses.iter()) {
+            assert!(witness.verify_address(&generator.address(addressing)));
+        }
+        assert_eq!(witnesses[0], witnesses[2]);
+    }
+
+    #[test]
+    fn new_transaction_shuffled_moves_the_change() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let outputs : Vec<_> = (1..4).map(|v| tx::TxOut::new(foreign_address(1), Coin::new(v * 1_000_000).unwrap())).collect();
+        let change_addr = generator.address(&Addressing::new(0, 3));
+        let output_policy = OutputPolicy::One(change_addr.clone());
+
+        let mut rng = XorShift::new(0x2545f491);
+        let mut gen = || rng.next_u32();
+
+        let (reference, reference_fee) = wallet.new_transaction(
+            ProtocolMagic::default(), input_selection::SelectionPolicy::default(),
+            inputs.iter(), outputs.clone(), &output_policy
+        ).unwrap();
+        let sorted = |outputs: &[tx::TxOut]| -> Vec<Coin> {
+            let mut values : Vec<_> = outputs.iter().map(|o| o.value).collect();
+            values.sort();
+            values
+        };
+
+        let mut change_positions = Vec::new();
+        for _ in 0..8 {
+            let (txaux, fee) = wallet.new_transaction_shuffled(
+                ProtocolMagic::default(), input_selection::SelectionPolicy::default(),
+                inputs.iter(), outputs.clone(), &output_policy, &mut gen
+            ).unwrap();
+            assert_eq!(fee, reference_fee);
+            assert_eq!(sorted(&txaux.tx.outputs), sorted(&reference.tx.outputs));
+            assert!(txaux.witness[0].verify(ProtocolMagic::default(), &inputs[0].value.address, &txaux.tx));
+            change_positions.push(txaux.tx.outputs.iter().position(|o| o.address == change_addr).unwrap());
+        }
+        assert!(change_positions.iter().any(|p| *p != outputs.len()), "{:?}", change_positions);
+    }
+
+    #[test]
+    fn new_transaction_ordered_by_value() {
+        use wallet::scheme::{Wallet as WalletScheme};
+        use txutils::OutputOrdering;
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(7_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value, Addressing::new(0, 1))];
+        let outputs : Vec<_> = [3u64, 1, 2].iter().map(|v| tx::TxOut::new(foreign_address(1), Coin::new(v * 1_000_000).unwrap())).collect();
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 3)));
+
+        let (given, given_fee) = wallet.new_transaction_ordered(
+            ProtocolMagic::default(), input_selection::SelectionPolicy::default(),
+            inputs.iter(), outputs.clone(), &output_policy, &OutputOrdering::default()
+        ).unwrap();
+        let (reference, _) = wallet.new_transaction(
+            ProtocolMagic::default(), input_selection::SelectionPolicy::default(),
+            inputs.iter(), outputs.clone(), &output_policy
+        ).unwrap();
+        assert_eq!(given, reference);
+
+        let (txaux, fee) = wallet.new_transaction_ordered(
+            ProtocolMagic::default(), input_selection::SelectionPolicy::default(),
+            inputs.iter(), outputs.clone(), &output_policy, &OutputOrdering::ByValue
+        ).unwrap();
+        assert_eq!(fee, given_fee);
+        let values : Vec<_> = txaux.tx.outputs.iter().map(|o| o.value).collect();
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!(values, sorted);
+        // the change is the smallest output
+        assert_eq!(values.len(), 4);
+        assert_eq!(txaux.tx.outputs[0].address, generator.address(&Addressing::new(0, 3)));
+        assert!(txaux.witness[0].verify(ProtocolMagic::default(), &inputs[0].value.address, &txaux.tx));
+    }
+
+    #[test]
+    fn new_transaction_with_sorted_inputs_is_deterministic() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let inputs : Vec<_> = [(3u8, 1u32), (1, 5), (1, 2)].iter().enumerate().map(|(i, &(id, index))| {
+            let addressing = Addressing::new(0, i as u32 + 1);
+            let value = tx::TxOut::new(generator.address(&addressing), Coin::new(1_000_000).unwrap());
+            txutils::Input::new(tx::TxoPointer::new(TxId::new(&[id]), index), value, addressing)
+        }).collect();
+        let mut reversed = inputs.clone();
+        reversed.reverse();
+        // all the inputs are needed
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(2_500_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 10)));
+
+        let build = |inputs: &Vec<txutils::Input<Addressing>>| wallet.new_transaction_with_sorted_inputs(
+            ProtocolMagic::default(), input_selection::SelectionPolicy::default(),
+            inputs.iter(), outputs.clone(), &output_policy
+        ).unwrap();
+        let (txaux, fee) = build(&inputs);
+        let (other, other_fee) = build(&reversed);
+        assert_eq!(fee, other_fee);
+        assert_eq!(cbor!(&txaux).unwrap(), cbor!(&other).unwrap());
+
+        let ptrs : Vec<_> = txaux.tx.inputs.to_vec();
+        let mut sorted = ptrs.clone();
+        sorted.sort();
+        assert_eq!(ptrs.len(), 3);
+        assert_eq!(ptrs, sorted);
+        // every witness is the one of the input at its position
+        for (ptr, witness) in txaux.tx.inputs.iter().zip(txaux.witness.iter()) {
+            let input = inputs.iter().find(|input| &input.ptr == ptr).unwrap();
+            assert!(witness.verify(ProtocolMagic::default(), &input.value.address, &txaux.tx));
+        }
+    }
+
+    #[test]
+    fn diagnose_addresses_outcomes() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let owned = generator.address(&Addressing::new(0, 7));
+
+        let other_seed = hdwallet::Seed::from_bytes([1;hdwallet::SEED_SIZE]);
+        let other_xprv = XPrv::generate_from_seed(&other_seed);
+        let other = Wallet::from_root_key(DerivationScheme::V2, RootKey::new(other_xprv.clone(), DerivationScheme::V2));
+        let other_wallet_address = other.address_generator().address(&Addressing::new(0, 7));
+        // the payload of an owned address with a foreign public key
+        let copied_payload = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(other_xprv.public()), owned.attributes.clone());
+
+        let addrs = vec![owned.clone(), other_wallet_address, foreign_address(1), copied_payload];
+        let diagnosis = wallet.diagnose_addresses(&addrs);
+        assert_eq!(diagnosis.len(), 4);
+        assert_eq!(diagnosis.iter().map(|&(i, _)| i).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        match diagnosis[0].1 { Ok(ref addressing) => assert_eq!(addressing, &Addressing::new(0, 7)), ref r => panic!("{:?}", r) }
+        match diagnosis[1].1 { Err(Error::NotMyPayload) => {}, ref r => panic!("{:?}", r) }
+        match diagnosis[2].1 { Err(Error::NoDerivationPath) => {}, ref r => panic!("{:?}", r) }
+        match diagnosis[3].1 { Err(Error::CannotReconstructAddress) => {}, ref r => panic!("{:?}", r) }
+        assert_eq!(wallet.check_address(&owned), Some(Addressing::new(0, 7)));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn error_display_and_cause() {
+        use std::error::Error as StdError;
+
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 1);
+        let empty : &[u8] = &[];
+        let cbor_error = cbor_event::de::RawCbor::from(empty).unsigned_integer().unwrap_err();
+        let utxo_message = format!("The UTxO {} does not belong to this wallet", ptr);
+        let input_message = format!("The input {} is not spent by the transaction", ptr);
+        let errors = vec![
+            (Error::Bip39Error(bip39::Error::WrongNumberOfWords(11)), "Wallet's Mnemonic Error", true),
+            (Error::DerivationError(hdwallet::Error::InvalidSeedSize(3)), "Invalid key derivation", true),
+            (Error::PayloadError(hdpayload::Error::CannotDecrypt), "Error while decoding an address' payload", true),
+            (Error::CBorEncoding(cbor_error), "Error while encoding address in binary format", true),
+            (Error::InvalidPayloadAddressing, "Payload has been decoded but is corrupted or of unexpected format", false),
+            (Error::CannotReconstructAddress, "The address cannot be reconstructed: the payload has been decoded but the public key hash seems different.", false),
+            (Error::UtxoNotRecognized(ptr.clone()), &utxo_message[..], false),
+            (Error::InputNotInTransaction(ptr.clone()), &input_message[..], false),
+            (Error::UnsupportedSpendingData(AddrType::ATRedeem), "Unsupported address type `Redeem`, only public key addresses are derived from the wallet", false),
+            (Error::NoDerivationPath, "The address has no derivation path", false),
+            (Error::NotMyPayload, "The address' derivation path was not encrypted by this wallet", false),
+        ];
+        for (error, message, has_cause) in errors {
+            assert_eq!(format!("{}", error), message);
+            assert_eq!(error.cause().is_some(), has_cause, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn inputs_from_utxos_for_new_transaction() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+
+        let utxos : Vec<_> = (1..4).map(|index| {
+            let ptr = tx::TxoPointer::new(TxId::new(&[index as u8;32]), index);
+            let value = tx::TxOut::new(generator.address(&Addressing::new(0, index)), Coin::new(1_000_000).unwrap());
+            (ptr, value)
+        }).collect();
+
+        let inputs = wallet.inputs_from_utxos(utxos.clone()).unwrap();
+        assert_eq!(inputs.len(), 3);
+        assert_eq!(inputs.total_value(), Coin::new(3_000_000));
+        for (input, index) in inputs.iter().zip(1..4) {
+            assert_eq!(input.addressing, Addressing::new(0, index));
+        }
+
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(2_500_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 10)));
+        let (txaux, _) = wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs,
+            &output_policy
+        ).unwrap();
+        assert_eq!(txaux.tx.inputs, utxos.iter().map(|(ptr, _)| ptr.clone()).collect::<Vec<_>>());
+
+        let mut foreign = utxos.clone();
+        foreign[1].1 = tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap());
+        match wallet.inputs_from_utxos(foreign) {
+            Err(Error::UtxoNotRecognized(ptr)) => assert_eq!(ptr, utxos[1].0),
+            r => panic!("expected an unrecognized UTxO, got {:?}", r.map(|inputs| inputs.len())),
+        }
+    }
+
+    #[test]
+    fn reconstruct_address_matches_the_generated_address() {
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let stakeholder = XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([7;hdwallet::SEED_SIZE])).public();
+
+        for addressing in [Addressing::new(0, 1), Addressing::new(3, 42)].iter() {
+            let address = generator.address(addressing);
+            assert_eq!(wallet.reconstruct_address(addressing, &address.attributes), address);
+
+            let address = generator.address_with_stake_distribution(addressing, StakeDistribution::new_single_key(&stakeholder));
+            assert_eq!(wallet.reconstruct_address(addressing, &address.attributes), address);
+
+            // the derivation path of the given attributes is not kept
+            let attributes = Attributes::new_bootstrap_era(None);
+            assert_eq!(wallet.reconstruct_address(addressing, &attributes), generator.address(addressing));
+        }
+        let address = generator.address(&Addressing::new(0, 1));
+        assert!(wallet.reconstruct_address(&Addressing::new(0, 2), &address.attributes) != address);
+    }
+
+    #[test]
+    fn address_with_stake_distribution_is_recognized() {
+        let wallet = rindex_wallet();
+        let addressing = Addressing::new(0, 7);
+        let stakeholder = XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([7;hdwallet::SEED_SIZE])).public();
+        let stake_distribution = StakeDistribution::new_single_key(&stakeholder);
+
+        let generator = wallet.address_generator();
+        let address = generator.address_with_stake_distribution(&addressing, stake_distribution);
+        assert_eq!(address.attributes.stake_distribution, stake_distribution);
+        assert!(address.attributes.derivation_path.is_some());
+        assert!(address != generator.address(&addressing));
+
+        // the wallet gives the same address from the attributes
+        let attributes = Attributes { derivation_path: None, stake_distribution };
+        assert_eq!(wallet.new_address_with_attributes(&addressing, attributes), address);
+
+        assert_eq!(wallet.check_address(&address), Some(addressing));
+        assert!(generator.compare_address(&address, &addressing).is_ok());
+        assert_eq!(generator.try_get_addressing(&address).unwrap(), Some(addressing));
+
+        // the payload cannot be moved to an address of another key
+        let mut forged = ExtendedAddr::new_simple(stakeholder);
+        forged.attributes = address.attributes.clone();
+        assert_eq!(wallet.check_address(&forged), None);
+    }
+
+    #[test]
+    fn sign_input_for_multi_party_transaction() {
+        let alice = rindex_wallet();
+        let bob = {
+            let seed = hdwallet::Seed::from_bytes([2;hdwallet::SEED_SIZE]);
+            let xprv = XPrv::generate_from_seed(&seed);
+            Wallet::from_root_key(DerivationScheme::V2, RootKey::new(xprv, DerivationScheme::V2))
+        };
+        let protocol_magic = ProtocolMagic::default();
+
+        let alice_ptr = tx::TxoPointer::new(TxId::new(&[1;32]), 0);
+        let alice_utxo = tx::TxOut::new(alice.address_generator().address(&Addressing::new(0, 1)), Coin::new(1_000_000).unwrap());
+        let bob_ptr = tx::TxoPointer::new(TxId::new(&[2;32]), 3);
+        let bob_utxo = tx::TxOut::new(bob.address_generator().address(&Addressing::new(0, 2)), Coin::new(2_000_000).unwrap());
+
+        let mut tx = Tx::new_with(vec![alice_ptr.clone(), bob_ptr.clone()], vec![]);
+        tx.add_output(tx::TxOut::new(foreign_address(1), Coin::new(2_500_000).unwrap()));
+
+        // every party can only sign its own input
+        match bob.sign_input_for(protocol_magic, &tx, &alice_ptr, &alice_utxo) {
+            Err(Error::UtxoNotRecognized(ref ptr)) => assert_eq!(ptr, &alice_ptr),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let unknown_ptr = tx::TxoPointer::new(TxId::new(&[3;32]), 0);
+        match alice.sign_input_for(protocol_magic, &tx, &unknown_ptr, &alice_utxo) {
+            Err(Error::InputNotInTransaction(ref ptr)) => assert_eq!(ptr, &unknown_ptr),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let alice_witness = alice.sign_input_for(protocol_magic, &tx, &alice_ptr, &alice_utxo).unwrap();
+        let bob_witness = bob.sign_input_for(protocol_magic, &tx, &bob_ptr, &bob_utxo).unwrap();
+
+        let mut txaux = TxAux::new(tx, tx::TxWitness::new());
+        txaux.add_witness(alice_witness);
+        txaux.add_witness(bob_witness);
+
+        assert_eq!(txaux.witness.len(), txaux.tx.inputs.len());
+        assert!(txaux.witness[0].verify(protocol_magic, &alice_utxo.address, &txaux.tx));
+        assert!(txaux.witness[1].verify(protocol_magic, &bob_utxo.address, &txaux.tx));
+        assert!(! txaux.witness[0].verify(protocol_magic, &bob_utxo.address, &txaux.tx));
+    }
+
+    #[test]
+    fn verify_self_outputs_recognizes_the_change() {
+        let wallet = rindex_wallet();
+        let change = wallet.address_generator().address(&Addressing::new(0, 16));
+
+        let mut tx = Tx::new_with(vec![tx::TxoPointer::new(TxId::new(&[1;32]), 0)], vec![]);
+        tx.add_output(tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap()));
+        tx.add_output(tx::TxOut::new(change, Coin::new(300_000).unwrap()));
+        tx.add_output(tx::TxOut::new(foreign_address(1), Coin::new(2_000).unwrap()));
+
+        let outputs = wallet.verify_self_outputs(&tx);
+        assert_eq!(outputs.len(), 3);
+        for &(index, ref result) in outputs.iter() {
+            match (index, result) {
+                (1, Ok(addressing)) => assert_eq!(addressing, &Addressing::new(0, 16)),
+                (_, Err(Error::UtxoNotRecognized(ptr))) if index != 1 => assert_eq!(ptr, &tx::TxoPointer::new(tx.id(), index as u32)),
+                r => panic!("unexpected result: {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn new_transaction_fee_matches_the_serialized_size() {
+        use wallet::scheme::{Wallet as WalletScheme};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let alg = fee::LinearFee::default();
+        let inputs : Vec<_> = (0..3).map(|index| {
+            let ptr = tx::TxoPointer::new(TxId::new(&[index as u8;32]), index);
+            let value = tx::TxOut::new(generator.address(&Addressing::new(0, index)), Coin::new(4_000_000).unwrap());
+            txutils::Input::new(ptr, value, Addressing::new(0, index))
+        }).collect();
+        let change_addr = generator.address(&Addressing::new(0, 10));
+
+        // the values are chosen for the change to be encoded with different sizes
+        for value in [1, 1_000, 3_700_000, 3_800_000, 7_000_000, 11_000_000].iter() {
+            let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(*value).unwrap())];
+            let (txaux, fee) = wallet.new_transaction(
+                ProtocolMagic::default(),
+                input_selection::SelectionPolicy::default(),
+                inputs.iter(),
+                outputs,
+                &OutputPolicy::One(change_addr.clone())
+            ).unwrap();
+
+            let total_input = inputs[..txaux.tx.inputs.len()].iter().fold(Coin::zero(), |acc, input| (acc + input.value()).unwrap());
+            assert_eq!((txaux.tx.get_output_total().unwrap() + fee.to_coin()).unwrap(), total_input);
+
+            // the fee covers the actual size of the transaction, overpaying
+            // at most the bytes of the change's value encoding
+            let size = cbor!(&txaux).unwrap().len();
+            assert!(fee.to_coin() >= alg.estimate(size).unwrap().to_coin(), "fee {} for {} bytes", fee.to_coin(), size);
+            assert!(fee.to_coin() <= alg.estimate(size + 8).unwrap().to_coin(), "fee {} for {} bytes", fee.to_coin(), size);
+        }
+    }
+
+    #[test]
+    fn new_transaction_with_metadata_changes_the_id_and_the_fee() {
+        use wallet::scheme::{Wallet as WalletScheme};
+        use config::{Config, ConfigBuilder};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), 0);
+        let value = tx::TxOut::new(generator.address(&Addressing::new(0, 1)), Coin::new(10_000_000).unwrap());
+        let inputs = [txutils::Input::new(ptr, value.clone(), Addressing::new(0, 1))];
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(1_000_000).unwrap())];
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 3)));
+
+        let build = |config: &Config, metadata: &[u8]| {
+            let mut attributes = tx::TxAttributes::new();
+            if ! metadata.is_empty() { attributes.insert(2, metadata.to_vec()); }
+            wallet.new_transaction_with_metadata(
+                config,
+                input_selection::SelectionPolicy::default(),
+                inputs.iter(),
+                outputs.clone(),
+                &output_policy,
+                attributes
+            ).unwrap()
+        };
+        let config = Config::default();
+        let (plain, plain_fee) = build(&config, &[]);
+        let (short, short_fee) = build(&config, b"invoice 1");
+        let (long, long_fee) = build(&config, b"invoice 1 - order 5th of october");
+
+        // without metadata, this is the usual transaction
+        assert_eq!((plain.clone(), plain_fee), wallet.new_transaction(
+            ProtocolMagic::default(),
+            input_selection::SelectionPolicy::default(),
+            inputs.iter(),
+            outputs.clone(),
+            &output_policy
+        ).unwrap());
+
+        assert_eq!(short.tx.attributes.get(2), Some(&b"invoice 1"[..]));
+        assert!(plain.tx.id() != short.tx.id());
+        assert!(short.tx.id() != long.tx.id());
+        assert!(plain_fee.to_coin() < short_fee.to_coin());
+        assert!(short_fee.to_coin() < long_fee.to_coin());
+
+        // the fees follow the fee policy of the configuration
+        let expensive = ConfigBuilder::new().fee_policy(fee::LinearFee::new(fee::Milli::integral(200000), fee::Milli::integral(50))).build().unwrap();
+        let (_, expensive_fee) = build(&expensive, b"invoice 1");
+        assert!(short_fee.to_coin() < expensive_fee.to_coin());
+
+        for txaux in [short, long].iter() {
+            assert_eq!(txaux.tx.inputs, plain.tx.inputs);
+            assert_eq!(txaux.tx.outputs[0], outputs[0]);
+            // the witness signs the metadata
+            assert!(txaux.witness[0].verify(ProtocolMagic::default(), &value.address, &txaux.tx));
+            let mut stripped = txaux.tx.clone();
+            stripped.set_attributes(tx::TxAttributes::new());
+            assert!(! txaux.witness[0].verify(ProtocolMagic::default(), &value.address, &stripped));
+            // the fee covers the transaction with its metadata
+            assert!(fee::LinearFee::default().calculate_for_txaux(txaux).unwrap().to_coin() <= (value.value - txaux.tx.get_output_total().unwrap()).unwrap());
+        }
+    }
+
+    #[test]
+    fn new_transaction_with_config_too_large() {
+        use wallet::scheme::{Wallet as WalletScheme};
+        use config::{Config, DEFAULT_MAX_TX_SIZE};
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let utxo_address = generator.address(&Addressing::new(0, 1));
+        let inputs : Vec<_> = (0..500).map(|index| {
+            let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), index);
+            let value = tx::TxOut::new(utxo_address.clone(), Coin::new(200_000).unwrap());
+            txutils::Input::new(ptr, value, Addressing::new(0, 1))
+        }).collect();
+        let output_policy = OutputPolicy::One(generator.address(&Addressing::new(0, 3)));
+        let config = Config::mainnet();
+        assert_eq!(config.max_tx_size, DEFAULT_MAX_TX_SIZE);
+
+        let build = |config: &Config, value: u64| {
+            wallet.new_transaction_with_config(
+                config,
+                input_selection::SelectionPolicy::default(),
+                inputs.iter(),
+                vec![tx::TxOut::new(foreign_address(1), Coin::new(value).unwrap())],
+                &output_policy
+            )
+        };
+
+        // about 450 of the tiny UTxOs would be needed
+        match build(&config, 90_000_000) {
+            Err(input_selection::Error::TransactionTooLarge { size, max }) => {
+                assert_eq!(max, DEFAULT_MAX_TX_SIZE);
+                assert!(size > max);
+            },
+            r => panic!("expected a too large transaction, got {:?}", r),
+        }
+
+        // the default configuration applies to `new_transaction`
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(90_000_000).unwrap())];
+        match wallet.new_transaction(ProtocolMagic::default(), input_selection::SelectionPolicy::default(), inputs.iter(), outputs, &output_policy) {
+            Err(input_selection::Error::TransactionTooLarge { max, .. }) => assert_eq!(max, DEFAULT_MAX_TX_SIZE),
+            r => panic!("expected a too large transaction, got {:?}", r),
+        }
+
+        let (txaux, _) = build(&config, 10_000_000).unwrap();
+        assert!(cbor!(&txaux).unwrap().len() <= DEFAULT_MAX_TX_SIZE);
+        assert!(txaux.tx.inputs.len() > 50);
+
+        // a smaller limit is honoured as well
+        let small = Config { max_tx_size: 4096, ..config };
+        match build(&small, 10_000_000) {
+            Err(input_selection::Error::TransactionTooLarge { max: 4096, .. }) => {},
+            r => panic!("expected a too large transaction, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn transaction_variants_too_large() {
+        use wallet::scheme::{Wallet as WalletScheme, WalletSigner};
+        use txutils::OutputOrdering;
+        use wallet::builder::{self, TransactionBuilder};
+        use config::DEFAULT_MAX_TX_SIZE;
+        use fee::LinearFee;
+
+        let wallet = rindex_wallet();
+        let generator = wallet.address_generator();
+        let utxo_address = generator.address(&Addressing::new(0, 1));
+        let inputs : Vec<_> = (0..500).map(|index| {
+            let ptr = tx::TxoPointer::new(TxId::new(&[0;32]), index);
+            let value = tx::TxOut::new(utxo_address.clone(), Coin::new(200_000).unwrap());
+            txutils::Input::new(ptr, value, Addressing::new(0, 1))
+        }).collect();
+        let change_address = generator.address(&Addressing::new(0, 3));
+        let output_policy = OutputPolicy::One(change_address.clone());
+        let outputs = vec![tx::TxOut::new(foreign_address(1), Coin::new(90_000_000).unwrap())];
+        let policy = input_selection::SelectionPolicy::default();
+        let pm = ProtocolMagic::default();
+
+        let check = |r: input_selection::Result<(tx::TxAux, fee::Fee)>| match r {
+            Err(input_selection::Error::TransactionTooLarge { max, .. }) => assert_eq!(max, DEFAULT_MAX_TX_SIZE),
+            r => panic!("expected a too large transaction, got {:?}", r),
+        };
+        check(wallet.new_transaction_with(pm, &LinearFee::default(), policy, inputs.iter(), outputs.clone(), &output_policy));
+        check(wallet.new_transaction_with_signer(policy, inputs.iter(), outputs.clone(), &output_policy, &WalletSigner::new(&wallet, pm)));
+        check(wallet.new_transaction_shuffled(pm, policy, inputs.iter(), outputs.clone(), &output_policy, || 4));
+        check(wallet.new_transaction_ordered(pm, policy, inputs.iter(), outputs.clone(), &output_policy, &OutputOrdering::ByValue));
+        check(wallet.new_transaction_with_sorted_inputs(pm, policy, inputs.iter(), outputs.clone(), &output_policy));
+
+        match wallet.preview_transaction(policy, inputs.iter(), outputs.clone(), &output_policy) {
+            Err(input_selection::Error::TransactionTooLarge { .. }) => {},
+            r => panic!("expected a too large transaction, got {:?}", r),
+        }
+
+        let mut builder = TransactionBuilder::new();
+        for input in inputs.iter() { builder.add_input(input.clone()); }
+        builder.add_output(outputs[0].clone()).set_change_address(change_address);
+        match builder.build(&wallet, pm) {
+            Err(builder::Error::SelectionError(input_selection::Error::TransactionTooLarge { .. })) => {},
+            r => panic!("expected a too large transaction, got {:?}", r),
+        }
+    }
+}
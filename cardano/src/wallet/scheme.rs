@@ -2,14 +2,173 @@
 //! generate addresses and sign transactions.
 //!
 
-use tx::{self, TxId, TxOut, TxInWitness};
-use fee;
+use std::cmp;
+use tx::{self, TxAttributes, TxId, TxOut, TxInWitness};
+use fee::{self, FeeAlgorithm};
 use input_selection::{self, SelectionAlgorithm};
-use txutils::{Input, OutputPolicy};
+use txutils::{self, Input, OutputPolicy, OutputOrdering};
 use coin::Coin;
-use config::{ProtocolMagic};
+use config::{Config, ProtocolMagic};
 use address::{ExtendedAddr};
 
+/// the number of inputs assumed by
+/// [`Wallet::required_funds`](./trait.Wallet.html#method.required_funds)
+pub const REQUIRED_FUNDS_INPUT_COUNT : usize = 1;
+
+/// description of a transaction before it is signed, as returned by
+/// [`Wallet::explain_transaction`](./trait.Wallet.html#method.explain_transaction).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub struct BuildPlan<Addressing> {
+    /// the selected inputs, in the order they appear in the transaction
+    pub inputs: Vec<Input<Addressing>>,
+    /// the outputs of the transaction in order, the change output
+    /// (if any) being the last one unless the outputs were shuffled
+    /// (see [`shuffle_outputs`](#method.shuffle_outputs))
+    pub outputs: Vec<TxOut>,
+    /// the fee paid by the transaction
+    pub fee: fee::Fee,
+    /// the change output, if any
+    pub change: Option<TxOut>,
+    /// the attributes of the transaction (see
+    /// [`TxAttributes`](../../tx/struct.TxAttributes.html)), empty if none.
+    pub attributes: TxAttributes,
+}
+impl<Addressing> BuildPlan<Addressing> {
+    /// the unsigned transaction described by this plan
+    pub fn to_tx(&self) -> tx::Tx {
+        let mut tx = tx::Tx::new_with(
+            self.inputs.iter().map(|input| input.ptr.clone()).collect(),
+            self.outputs.clone()
+        );
+        tx.set_attributes(self.attributes.clone());
+        tx
+    }
+
+    /// randomize the order of the outputs, using `gen` as source of
+    /// randomness, so the change output cannot be identified by its
+    /// position in the transaction.
+    ///
+    /// The plan must be shuffled before being signed: the witnesses sign
+    /// the transaction with its outputs in their final order.
+    ///
+    /// ```
+    /// extern crate rand;
+    /// # extern crate cardano;
+    /// # use cardano::wallet::scheme::BuildPlan;
+    /// # fn shuffle<A>(plan: &mut BuildPlan<A>) {
+    /// plan.shuffle_outputs(rand::random);
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn shuffle_outputs<G>(&mut self, mut gen: G)
+        where G: FnMut() -> u32
+    {
+        // Fisher-Yates, the modulo bias is negligible for the number of
+        // outputs of a transaction.
+        for i in (1..self.outputs.len()).rev() {
+            let j = gen() as usize % (i + 1);
+            self.outputs.swap(i, j);
+        }
+    }
+
+    /// reorder the outputs, including the change, following the given
+    /// ordering.
+    ///
+    /// As with [`shuffle_outputs`](#method.shuffle_outputs) the plan must
+    /// be ordered before being signed.
+    pub fn order_outputs(&mut self, ordering: &OutputOrdering) {
+        ordering.apply(&mut self.outputs)
+    }
+
+    /// sort the inputs by the transaction id and the index of the UTxO
+    /// they spend, so the transaction does not depend on the order the
+    /// inputs were selected in.
+    ///
+    /// Each input keeps its addressing: the witnesses produced by
+    /// [`sign`](#method.sign) follow the sorted inputs.
+    pub fn sort_inputs(&mut self) {
+        self.inputs.sort_by(|a, b| a.ptr.cmp(&b.ptr))
+    }
+
+    /// sign every input of the transaction, in order, with the given signer
+    pub fn sign<S: TxSigner<Addressing>>(&self, signer: &S) -> tx::TxAux {
+        let tx = self.to_tx();
+        let witnesses : Vec<TxInWitness> = self.inputs.iter()
+            .map(|input| signer.sign_input(&input.addressing, &tx))
+            .collect();
+        tx::TxAux::new(tx, tx::TxWitness::from(witnesses))
+    }
+}
+
+/// the plan of a transaction once the input selection is done, see
+/// [`Wallet::explain_transaction_with`](./trait.Wallet.html#method.explain_transaction_with).
+fn build_plan<'a, Addressing: 'a + Clone>( available: usize
+                                         , fee: fee::Fee
+                                         , selected_inputs: Vec<&'a Input<Addressing>>
+                                         , outputs: Vec<TxOut>
+                                         , change: Coin
+                                         , output_policy: &OutputPolicy
+                                         , attributes: TxAttributes
+                                         ) -> BuildPlan<Addressing>
+{
+    log_debug!("input selection: {} input(s) selected out of {}: [{}]",
+        selected_inputs.len(), available,
+        selected_inputs.iter().map(|input| format!("{}", input.ptr)).collect::<Vec<_>>().join(", "));
+    log_debug!("input selection: fee {}, change {}", fee.to_coin(), change);
+
+    let mut outputs = outputs;
+    let change = if change > Coin::zero() {
+        let change = match output_policy {
+            OutputPolicy::One(change_addr) => tx::TxOut::new(change_addr.clone(), change),
+        };
+        outputs.push(change.clone());
+        Some(change)
+    } else {
+        None
+    };
+
+    BuildPlan {
+        inputs: selected_inputs.into_iter().cloned().collect(),
+        outputs,
+        fee,
+        change,
+        attributes,
+    }
+}
+
+/// provide the witness of a transaction's input, one input at a time.
+///
+/// This allows the private keys to be kept outside of the process (e.g.
+/// on a hardware wallet): the signer receives the addressing of the
+/// input to sign and the whole transaction.
+///
+/// See [`WalletSigner`](./struct.WalletSigner.html) for the in memory
+/// implementation.
+pub trait TxSigner<Addressing> {
+    fn sign_input(&self, addressing: &Addressing, tx: &tx::Tx) -> TxInWitness;
+}
+
+/// sign the inputs with the wallet's private keys, see
+/// [`Wallet::sign_tx`](./trait.Wallet.html#tymethod.sign_tx).
+pub struct WalletSigner<'a, W: 'a + ?Sized> {
+    wallet: &'a W,
+    protocol_magic: ProtocolMagic,
+}
+impl<'a, W: 'a + ?Sized + Wallet> WalletSigner<'a, W> {
+    pub fn new(wallet: &'a W, protocol_magic: ProtocolMagic) -> Self {
+        WalletSigner { wallet, protocol_magic }
+    }
+}
+impl<'a, W: 'a + ?Sized + Wallet> TxSigner<W::Addressing> for WalletSigner<'a, W> {
+    fn sign_input(&self, addressing: &W::Addressing, tx: &tx::Tx) -> TxInWitness {
+        let mut witnesses = self.wallet.sign_tx(self.protocol_magic, &tx.id(), ::std::iter::once(addressing));
+        debug_assert!(witnesses.len() == 1, "we expect to sign only one input here...");
+        witnesses.pop().unwrap()
+    }
+}
+
 /// main wallet scheme, provides all the details to manage a wallet:
 /// from managing wallet [`Account`](./trait.Account.html)s and
 /// signing transactions.
@@ -48,6 +207,10 @@ pub trait Wallet {
     /// it select the needed inputs, compute the fee and possible change
     /// signes every TxIn as needed.
     ///
+    /// The limits of the default configuration apply (see
+    /// [`new_transaction_with_config`](./trait.Wallet.html#method.new_transaction_with_config)),
+    /// the transaction cannot be larger than `config::DEFAULT_MAX_TX_SIZE`.
+    ///
     fn new_transaction<'a, I>( &self
                              , protocol_magic: ProtocolMagic
                              , selection_policy: input_selection::SelectionPolicy
@@ -58,30 +221,632 @@ pub trait Wallet {
             -> input_selection::Result<(tx::TxAux, fee::Fee)>
         where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
             , Self::Addressing: 'a
+    {
+        self.new_transaction_with_config(&Config::new(protocol_magic), selection_policy, inputs, outputs, output_policy)
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but with the given fee algorithm instead of the default one.
+    ///
+    fn new_transaction_with<'a, I, F>( &self
+                                     , protocol_magic: ProtocolMagic
+                                     , alg: &F
+                                     , selection_policy: input_selection::SelectionPolicy
+                                     , inputs: I
+                                     , outputs: Vec<TxOut>
+                                     , output_policy: &OutputPolicy
+                                     )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , F : SelectionAlgorithm
+            , Self::Addressing: 'a
+    {
+        let plan = self.explain_transaction_with(alg, selection_policy, inputs, outputs, output_policy)?;
+
+        Ok((plan.sign(&WalletSigner::new(self, protocol_magic)), plan.fee))
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but the inputs are signed by the given signer instead of the
+    /// wallet's private keys.
+    ///
+    fn new_transaction_with_signer<'a, I, S>( &self
+                                            , selection_policy: input_selection::SelectionPolicy
+                                            , inputs: I
+                                            , outputs: Vec<TxOut>
+                                            , output_policy: &OutputPolicy
+                                            , signer: &S
+                                            )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , S : TxSigner<Self::Addressing>
+            , Self::Addressing: 'a
+    {
+        let plan = self.explain_transaction(selection_policy, inputs, outputs, output_policy)?;
+
+        Ok((plan.sign(signer), plan.fee))
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but for the network described by `config`: the transaction is signed
+    /// with the network's protocol magic, the fees are computed with the
+    /// network's fee policy and the transaction cannot be larger than the
+    /// network's maximum transaction size.
+    ///
+    /// Returns `Error::TransactionTooLarge` if the input selection needs
+    /// more inputs than what fits in a transaction (e.g. to spend many
+    /// small UTxOs), and `Error::NotEnoughInput` if the inputs needed are
+    /// more than the `max_inputs` of the configuration.
+    ///
+    fn new_transaction_with_config<'a, I>( &self
+                                         , config: &Config
+                                         , selection_policy: input_selection::SelectionPolicy
+                                         , inputs: I
+                                         , outputs: Vec<TxOut>
+                                         , output_policy: &OutputPolicy
+                                         )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , Self::Addressing: 'a
+    {
+        self.new_transaction_with_metadata(config, selection_policy, inputs, outputs, output_policy, TxAttributes::new())
+    }
+
+    /// same as [`new_transaction_with_config`](./trait.Wallet.html#method.new_transaction_with_config)
+    /// but the given attributes are attached to the transaction (see
+    /// [`TxAttributes`](../../tx/struct.TxAttributes.html)), e.g. to tag a
+    /// payment. The fee accounts for the size of the attributes.
+    ///
+    fn new_transaction_with_metadata<'a, I>( &self
+                                           , config: &Config
+                                           , selection_policy: input_selection::SelectionPolicy
+                                           , inputs: I
+                                           , outputs: Vec<TxOut>
+                                           , output_policy: &OutputPolicy
+                                           , attributes: TxAttributes
+                                           )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , Self::Addressing: 'a
+    {
+        let available = inputs.len();
+        let (fee, selected_inputs, change)
+            = config.fee_policy.compute_with_limits(selection_policy, inputs, outputs.iter(), output_policy, &attributes, config)?;
+        let plan = build_plan(available, fee, selected_inputs, outputs, change, output_policy, attributes);
+
+        let txaux = plan.sign(&WalletSigner::new(self, config.protocol_magic));
+        let size = tx::txaux_serialize_size(&txaux.tx, &txaux.witness);
+        if size > config.max_tx_size {
+            return Err(input_selection::Error::TransactionTooLarge { size, max: config.max_tx_size });
+        }
+        Ok((txaux, plan.fee))
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but the outputs, including the change, are put in a random order
+    /// using `gen` as source of randomness (e.g. `rand::random`).
+    ///
+    /// see [`BuildPlan::shuffle_outputs`](./struct.BuildPlan.html#method.shuffle_outputs).
+    ///
+    fn new_transaction_shuffled<'a, I, G>( &self
+                                         , protocol_magic: ProtocolMagic
+                                         , selection_policy: input_selection::SelectionPolicy
+                                         , inputs: I
+                                         , outputs: Vec<TxOut>
+                                         , output_policy: &OutputPolicy
+                                         , gen: G
+                                         )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , G : FnMut() -> u32
+            , Self::Addressing: 'a
+    {
+        let mut plan = self.explain_transaction(selection_policy, inputs, outputs, output_policy)?;
+        plan.shuffle_outputs(gen);
+
+        Ok((plan.sign(&WalletSigner::new(self, protocol_magic)), plan.fee))
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but the outputs, including the change, are put in the order given by
+    /// `ordering` before the transaction is signed.
+    ///
+    /// see [`BuildPlan::order_outputs`](./struct.BuildPlan.html#method.order_outputs).
+    ///
+    fn new_transaction_ordered<'a, I>( &self
+                                     , protocol_magic: ProtocolMagic
+                                     , selection_policy: input_selection::SelectionPolicy
+                                     , inputs: I
+                                     , outputs: Vec<TxOut>
+                                     , output_policy: &OutputPolicy
+                                     , ordering: &OutputOrdering
+                                     )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , Self::Addressing: 'a
+    {
+        let mut plan = self.explain_transaction(selection_policy, inputs, outputs, output_policy)?;
+        plan.order_outputs(ordering);
+
+        Ok((plan.sign(&WalletSigner::new(self, protocol_magic)), plan.fee))
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but the selected inputs are sorted by the UTxO they spend before the
+    /// transaction is signed: selecting the same inputs, in any order,
+    /// builds the same transaction.
+    ///
+    /// see [`BuildPlan::sort_inputs`](./struct.BuildPlan.html#method.sort_inputs).
+    ///
+    fn new_transaction_with_sorted_inputs<'a, I>( &self
+                                                , protocol_magic: ProtocolMagic
+                                                , selection_policy: input_selection::SelectionPolicy
+                                                , inputs: I
+                                                , outputs: Vec<TxOut>
+                                                , output_policy: &OutputPolicy
+                                                )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , Self::Addressing: 'a
+    {
+        let mut plan = self.explain_transaction(selection_policy, inputs, outputs, output_policy)?;
+        plan.sort_inputs();
+
+        Ok((plan.sign(&WalletSigner::new(self, protocol_magic)), plan.fee))
+    }
+
+    /// same as [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// but the inputs pointing to one of the `exclude` UTxOs are never
+    /// selected (e.g. UTxOs reserved for another pending transaction).
+    ///
+    /// Returns `NotEnoughInput` if the remaining inputs do not cover the
+    /// outputs and the fee, including when all the inputs are excluded.
+    ///
+    fn new_transaction_excluding<'a, I>( &self
+                                       , protocol_magic: ProtocolMagic
+                                       , selection_policy: input_selection::SelectionPolicy
+                                       , inputs: I
+                                       , outputs: Vec<TxOut>
+                                       , output_policy: &OutputPolicy
+                                       , exclude: &[tx::TxoPointer]
+                                       )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>>
+            , Self::Addressing: 'a
+    {
+        let inputs : Vec<&'a Input<Self::Addressing>> = inputs.filter(|input| !exclude.contains(&input.ptr)).collect();
+        if inputs.is_empty() { return Err(input_selection::Error::NotEnoughInput); }
+
+        self.new_transaction(protocol_magic, selection_policy, inputs.iter().cloned(), outputs, output_policy)
+    }
+
+    /// describe the transaction [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// would build, without signing it: the selected inputs, the fee,
+    /// the change and the order of the outputs.
+    ///
+    /// The limits of the default configuration apply, as for
+    /// `new_transaction`: `Error::TransactionTooLarge` is returned if the
+    /// signed transaction would be larger than `config::DEFAULT_MAX_TX_SIZE`.
+    ///
+    fn explain_transaction<'a, I>( &self
+                                 , selection_policy: input_selection::SelectionPolicy
+                                 , inputs: I
+                                 , outputs: Vec<TxOut>
+                                 , output_policy: &OutputPolicy
+                                 )
+            -> input_selection::Result<BuildPlan<Self::Addressing>>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , Self::Addressing: 'a
     {
         let alg = fee::LinearFee::default();
 
+        self.explain_transaction_with(&alg, selection_policy, inputs, outputs, output_policy)
+    }
+
+    /// perform the input selection, the fee and change computation of
+    /// [`new_transaction`](./trait.Wallet.html#method.new_transaction)
+    /// without signing: return the unsigned transaction, its fee and the
+    /// change value (zero if there is no change output).
+    ///
+    /// This is useful to preview a transaction without requiring the
+    /// spending keys.
+    ///
+    fn preview_transaction<'a, I>( &self
+                                 , selection_policy: input_selection::SelectionPolicy
+                                 , inputs: I
+                                 , outputs: Vec<TxOut>
+                                 , output_policy: &OutputPolicy
+                                 )
+            -> input_selection::Result<(tx::Tx, fee::Fee, Coin)>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , Self::Addressing: 'a
+    {
+        let plan = self.explain_transaction(selection_policy, inputs, outputs, output_policy)?;
+        let change = plan.change.as_ref().map(|change| change.value).unwrap_or(Coin::zero());
+
+        Ok((plan.to_tx(), plan.fee, change))
+    }
+
+    /// same as [`explain_transaction`](./trait.Wallet.html#method.explain_transaction)
+    /// but with the given fee algorithm instead of the default one. The
+    /// limits of the default configuration still apply (see
+    /// [`SelectionAlgorithm::compute_with_config`](../../input_selection/trait.SelectionAlgorithm.html#tymethod.compute_with_config)).
+    ///
+    fn explain_transaction_with<'a, I, F>( &self
+                                         , alg: &F
+                                         , selection_policy: input_selection::SelectionPolicy
+                                         , inputs: I
+                                         , outputs: Vec<TxOut>
+                                         , output_policy: &OutputPolicy
+                                         )
+            -> input_selection::Result<BuildPlan<Self::Addressing>>
+        where I : 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator
+            , F : SelectionAlgorithm
+            , Self::Addressing: 'a
+    {
+        let available = inputs.len();
         let (fee, selected_inputs, change)
-            = alg.compute(selection_policy, inputs, outputs.iter(), output_policy)?;
+            = alg.compute_with_config(selection_policy, inputs, outputs.iter(), output_policy, &Config::default())?;
 
-        let addressings : Vec<Self::Addressing>
-            = selected_inputs.iter().map(|si| si.addressing.clone()).collect();
+        Ok(build_plan(available, fee, selected_inputs, outputs, change, output_policy, TxAttributes::new()))
+    }
+
+    /// estimate the fee of an already built, unsigned, transaction once
+    /// signed with `num_witnesses` witnesses, using the default fee
+    /// algorithm. No input selection is performed.
+    ///
+    fn estimate_fee(&self, tx: &tx::Tx, num_witnesses: usize) -> fee::Result<fee::Fee> {
+        fee::LinearFee::default().estimate_for_tx(tx, num_witnesses)
+    }
 
+    /// estimate the funds needed to send the given outputs: the total of the
+    /// outputs plus the fee of a transaction spending
+    /// `REQUIRED_FUNDS_INPUT_COUNT` inputs and with a change output to
+    /// `change_addr`.
+    ///
+    /// As a transaction needs at least one input this is a lower bound of
+    /// what the wallet will need to have, transactions with more inputs
+    /// will be more expensive.
+    ///
+    fn required_funds(&self, outputs: &[TxOut], change_addr: &ExtendedAddr) -> input_selection::Result<Coin> {
+        let alg = fee::LinearFee::default();
+
+        self.required_funds_with(&alg, outputs, change_addr)
+    }
+
+    /// same as [`required_funds`](./trait.Wallet.html#method.required_funds)
+    /// but with the given fee algorithm instead of the default one.
+    ///
+    /// This remains an estimate: the fee grows with the number of inputs
+    /// the input selection actually needs.
+    ///
+    fn required_funds_with<F>(&self, alg: &F, outputs: &[TxOut], change_addr: &ExtendedAddr) -> input_selection::Result<Coin>
+        where F: FeeAlgorithm
+    {
+        let output_value = outputs.iter().try_fold(Coin::zero(), |acc, o| acc.checked_add(o.value))
+                                  .ok_or(input_selection::Error::CoinOverflow)?;
+
+        let inputs = (0..REQUIRED_FUNDS_INPUT_COUNT)
+            .map(|index| tx::TxoPointer::new(TxId::new(&[0;32]), index as u32))
+            .collect();
+        let mut tx = tx::Tx::new_with(inputs, outputs.to_vec());
+        // the change value is not known yet, the total of the outputs is
+        // used as an estimation of its size.
+        tx.add_output(TxOut::new(change_addr.clone(), output_value));
+        let fake_witnesses : Vec<TxInWitness> = (0..REQUIRED_FUNDS_INPUT_COUNT).map(|_| TxInWitness::fake()).collect();
+
+        let fee = alg.calculate_for_txaux_component(&tx, &fake_witnesses)?;
+        output_value.checked_add(fee.to_coin()).ok_or(input_selection::Error::CoinOverflow)
+    }
+
+    /// create a ready to send transaction spending exactly the `selected`
+    /// inputs, bypassing the input selection.
+    ///
+    /// This is useful to consolidate a set of UTxOs or to spend a specific
+    /// UTxO. The fee is computed over all the selected inputs and the
+    /// surplus, if any, is sent to the change address of the output policy.
+    /// If the surplus does not cover the cost of the change output it is
+    /// left to the fee.
+    ///
+    /// Returns `NotEnoughInput` if the selected inputs do not cover the
+    /// outputs and the fee.
+    ///
+    fn new_transaction_from_inputs( &self
+                                  , protocol_magic: ProtocolMagic
+                                  , selected: &[Input<Self::Addressing>]
+                                  , outputs: Vec<TxOut>
+                                  , output_policy: &OutputPolicy
+                                  )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+    {
+        if selected.is_empty() { return Err(input_selection::Error::NoInputs); }
+        if outputs.is_empty() { return Err(input_selection::Error::NoOutputs); }
+        input_selection::check_duplicate_inputs(selected.iter())?;
+
+        let alg = fee::LinearFee::default();
+
+        let input_value = selected.iter().try_fold(Coin::zero(), |acc, i| acc.checked_add(i.value()))
+                                  .ok_or(input_selection::Error::CoinOverflow)?;
+        let output_value = outputs.iter().try_fold(Coin::zero(), |acc, o| acc.checked_add(o.value))
+                                  .ok_or(input_selection::Error::CoinOverflow)?;
+
+        let fake_witnesses : Vec<TxInWitness> = selected.iter().map(|_| TxInWitness::fake()).collect();
         let mut tx = tx::Tx::new_with(
-            selected_inputs.iter().map(|input| input.ptr.clone()).collect(),
+            selected.iter().map(|input| input.ptr.clone()).collect(),
             outputs
         );
 
-        if change > Coin::zero() {
-            match output_policy {
-                OutputPolicy::One(change_addr) =>
-                    tx.add_output(tx::TxOut::new(change_addr.clone(), change)),
+        let fee = alg.calculate_for_txaux_component(&tx, &fake_witnesses)?;
+        let surplus = input_value.checked_sub(output_value)
+                                 .and_then(|v| v.checked_sub(fee.to_coin()))
+                                 .ok_or(input_selection::Error::NotEnoughInput)?;
+
+        let mut fee = fee.to_coin();
+        if surplus > Coin::zero() {
+            let change_addr = match output_policy {
+                OutputPolicy::One(change_addr) => change_addr.clone(),
             };
+
+            // the fee of the transaction with the change output, the
+            // change being at most `surplus` its encoding cannot be bigger.
+            let mut with_change = tx.clone();
+            with_change.add_output(TxOut::new(change_addr.clone(), surplus));
+            let change_fee = alg.calculate_for_txaux_component(&with_change, &fake_witnesses)?.to_coin();
+
+            match input_value.checked_sub(output_value).and_then(|v| v.checked_sub(change_fee)) {
+                Some(change) if change > Coin::zero() => {
+                    tx.add_output(TxOut::new(change_addr, change));
+                    fee = change_fee;
+                },
+                _ => {
+                    // not worth a change output
+                    fee = (fee + surplus)?;
+                }
+            }
+        }
+
+        let addressings : Vec<Self::Addressing>
+            = selected.iter().map(|si| si.addressing.clone()).collect();
+        let witnesses = self.sign_tx(protocol_magic, &tx.id(), addressings.iter());
+
+        Ok((tx::TxAux::new(tx, tx::TxWitness::from(witnesses)), fee::Fee::new(fee)))
+    }
+
+    /// rebuild the `original` transaction, e.g. stuck because of a too low
+    /// fee, paying `extra_fee` more than it did.
+    ///
+    /// The outputs of the original transaction are kept, except for the
+    /// change output (the last output to `change_addr`, if any) which is
+    /// reduced by the fee increase. If the change does not cover it, more
+    /// inputs are taken from `inputs` in order. The fee is at least the
+    /// one of the default fee algorithm for the rebuilt transaction, and
+    /// a change too small to be worth its output is left to the fee.
+    ///
+    /// `inputs` must contain the inputs spent by `original` (an
+    /// `Error::MissingInput` is returned otherwise) and may contain other
+    /// inputs to add. Returns `NotEnoughInput` if all the inputs do not
+    /// cover the outputs and the new fee.
+    ///
+    fn bump_fee( &self
+               , protocol_magic: ProtocolMagic
+               , original: &tx::Tx
+               , inputs: &[Input<Self::Addressing>]
+               , extra_fee: Coin
+               , change_addr: &ExtendedAddr
+               )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+    {
+        input_selection::check_duplicate_inputs(inputs.iter())?;
+        let alg = fee::LinearFee::default();
+
+        let mut selected : Vec<&Input<Self::Addressing>> = Vec::with_capacity(original.inputs.len());
+        for ptr in original.inputs.iter() {
+            match inputs.iter().find(|input| &input.ptr == ptr) {
+                Some(input) => selected.push(input),
+                None => return Err(input_selection::Error::MissingInput(ptr.clone())),
+            }
         }
+        let mut candidates = inputs.iter().filter(|input| !original.inputs.contains(&input.ptr));
+
+        let change_index = original.outputs.iter().rposition(|output| &output.address == change_addr);
+        let mut payments = original.outputs.clone();
+        if let Some(index) = change_index { payments.remove(index); }
+
+        let sum = |selected: &[&Input<Self::Addressing>]| {
+            selected.iter().try_fold(Coin::zero(), |acc, i| acc.checked_add(i.value()))
+                    .ok_or(input_selection::Error::CoinOverflow)
+        };
+        let original_input_value = sum(&selected)?;
+        let original_output_value = txutils::output_sum(original.outputs.iter()).map_err(|_| input_selection::Error::CoinOverflow)?;
+        let payment_value = txutils::output_sum(payments.iter()).map_err(|_| input_selection::Error::CoinOverflow)?;
+        let target_fee = original_input_value.checked_sub(original_output_value)
+                                             .ok_or(input_selection::Error::NotEnoughInput)?
+                                             .checked_add(extra_fee)
+                                             .ok_or(input_selection::Error::CoinOverflow)?;
 
+        let (tx, fee) = loop {
+            let input_value = sum(&selected)?;
+            let fake_witnesses : Vec<TxInWitness> = selected.iter().map(|_| TxInWitness::fake()).collect();
+            let mut tx = tx::Tx::new_with(selected.iter().map(|input| input.ptr.clone()).collect(), payments.clone());
+            tx.set_attributes(original.attributes.clone());
+
+            // the change is at most `input_value`, its encoding cannot be bigger.
+            let mut with_change = tx.clone();
+            with_change.add_output(TxOut::new(change_addr.clone(), input_value));
+            let change_fee = cmp::max(target_fee, alg.calculate_for_txaux_component(&with_change, &fake_witnesses)?.to_coin());
+            match input_value.checked_sub(payment_value).and_then(|v| v.checked_sub(change_fee)) {
+                Some(change) if change > Coin::zero() => {
+                    let change = TxOut::new(change_addr.clone(), change);
+                    match change_index {
+                        Some(index) if index <= tx.outputs.len() => tx.outputs.insert(index, change),
+                        _ => tx.add_output(change),
+                    }
+                    break (tx, change_fee);
+                },
+                _ => {},
+            }
+
+            let fee = cmp::max(target_fee, alg.calculate_for_txaux_component(&tx, &fake_witnesses)?.to_coin());
+            if let Some(leftover) = input_value.checked_sub(payment_value) {
+                // not worth a change output
+                if leftover >= fee { break (tx, leftover); }
+            }
+
+            match candidates.next() {
+                Some(input) => selected.push(input),
+                None => return Err(input_selection::Error::NotEnoughInput),
+            }
+        };
+
+        let addressings : Vec<Self::Addressing>
+            = selected.iter().map(|input| input.addressing.clone()).collect();
         let witnesses = self.sign_tx(protocol_magic, &tx.id(), addressings.iter());
 
-        Ok((tx::TxAux::new(tx, tx::TxWitness::from(witnesses)), fee))
+        Ok((tx::TxAux::new(tx, tx::TxWitness::from(witnesses)), fee::Fee::new(fee)))
+    }
+
+    /// the largest value a transaction spending all the given inputs can
+    /// send to `destination`, i.e. the total of the inputs minus the fee
+    /// (computed with the given fee algorithm) of a transaction with this
+    /// single output and no change, see
+    /// [`sweep`](./trait.Wallet.html#method.sweep).
+    ///
+    /// Returns `NoInputs` if there are no inputs, `DuplicateInput` if an
+    /// input is given twice and `NotEnoughInput` if the inputs do not cover
+    /// the fee.
+    ///
+    fn max_spendable<F>( &self
+                       , alg: &F
+                       , inputs: &[Input<Self::Addressing>]
+                       , destination: &ExtendedAddr
+                       )
+            -> input_selection::Result<Coin>
+        where F: FeeAlgorithm
+    {
+        if inputs.is_empty() { return Err(input_selection::Error::NoInputs); }
+        input_selection::check_duplicate_inputs(inputs.iter())?;
+
+        let input_value = inputs.iter().try_fold(Coin::zero(), |acc, i| acc.checked_add(i.value()))
+                                .ok_or(input_selection::Error::CoinOverflow)?;
+
+        let fake_witnesses : Vec<TxInWitness> = inputs.iter().map(|_| TxInWitness::fake()).collect();
+        let tx = tx::Tx::new_with(
+            inputs.iter().map(|input| input.ptr.clone()).collect(),
+            vec![TxOut::new(destination.clone(), input_value)]
+        );
+
+        // the spendable value is at most `input_value`: its encoding cannot
+        // be bigger than the one used to compute the fee.
+        let fee = alg.calculate_for_txaux_component(&tx, &fake_witnesses)?;
+        match input_value.checked_sub(fee.to_coin()) {
+            Some(value) if value > Coin::zero() => Ok(value),
+            _ => Err(input_selection::Error::NotEnoughInput),
+        }
+    }
+
+    /// create a ready to send transaction spending all the given inputs
+    /// to `destination`: the only output is the total of the inputs minus
+    /// the fee, computed with the given fee algorithm (see
+    /// [`max_spendable`](./trait.Wallet.html#method.max_spendable)).
+    /// There is no change output.
+    ///
+    /// Returns `NoInputs` if there are no inputs, `DuplicateInput` if an
+    /// input is given twice and `NotEnoughInput` if the inputs do not cover
+    /// the fee.
+    ///
+    fn sweep<F>( &self
+               , protocol_magic: ProtocolMagic
+               , alg: &F
+               , inputs: &[Input<Self::Addressing>]
+               , destination: &ExtendedAddr
+               )
+            -> input_selection::Result<(tx::TxAux, fee::Fee)>
+        where F: FeeAlgorithm
+    {
+        let value = self.max_spendable(alg, inputs, destination)?;
+        let input_value = inputs.iter().try_fold(Coin::zero(), |acc, i| acc.checked_add(i.value()))
+                                .ok_or(input_selection::Error::CoinOverflow)?;
+        let fee = input_value.checked_sub(value).ok_or(input_selection::Error::NotEnoughInput)?;
+
+        let tx = tx::Tx::new_with(
+            inputs.iter().map(|input| input.ptr.clone()).collect(),
+            vec![TxOut::new(destination.clone(), value)]
+        );
+
+        let addressings : Vec<Self::Addressing>
+            = inputs.iter().map(|input| input.addressing.clone()).collect();
+        let witnesses = self.sign_tx(protocol_magic, &tx.id(), addressings.iter());
+
+        Ok((tx::TxAux::new(tx, tx::TxWitness::from(witnesses)), fee::Fee::new(fee)))
+    }
+
+    /// split a payout to many `outputs` into ready to send transactions of
+    /// at most `max_size` bytes each.
+    ///
+    /// The outputs are packed in order, as many as fit in each transaction.
+    /// Each transaction selects its inputs among the remaining `inputs`,
+    /// its change (if any) goes to `change_addr` and is spent first by the
+    /// next transaction: `change_addressing` is the addressing of
+    /// `change_addr` used to sign it. Every output is paid by exactly one
+    /// transaction, the transactions are to be sent in order.
+    ///
+    /// Returns `TransactionTooLarge` if one output alone does not fit in a
+    /// transaction and `NotEnoughInput` if the inputs do not cover all the
+    /// outputs and fees.
+    ///
+    fn build_payout_batch( &self
+                         , protocol_magic: ProtocolMagic
+                         , inputs: &[Input<Self::Addressing>]
+                         , outputs: &[TxOut]
+                         , change_addr: &ExtendedAddr
+                         , change_addressing: &Self::Addressing
+                         , max_size: usize
+                         )
+            -> input_selection::Result<Vec<(tx::TxAux, fee::Fee)>>
+    {
+        if inputs.is_empty() { return Err(input_selection::Error::NoInputs); }
+        if outputs.is_empty() { return Err(input_selection::Error::NoOutputs); }
+        input_selection::check_duplicate_inputs(inputs.iter())?;
+
+        let alg = fee::LinearFee::default();
+        let output_policy = OutputPolicy::One(change_addr.clone());
+        let mut available : Vec<Input<Self::Addressing>> = inputs.to_vec();
+        let mut batch = Vec::new();
+        let mut start = 0;
+
+        while start < outputs.len() {
+            // add the outputs one by one while the transaction can be built
+            let mut best = None;
+            for end in (start + 1)..(outputs.len() + 1) {
+                let payments = &outputs[start..end];
+                match alg.compute_with_max_size(input_selection::SelectionPolicy::default(), available.iter(), payments.iter(), &output_policy, &TxAttributes::new(), max_size) {
+                    Ok((fee, selected_inputs, change)) => {
+                        best = Some((end, build_plan(available.len(), fee, selected_inputs, payments.to_vec(), change, &output_policy, TxAttributes::new())));
+                    },
+                    Err(err) => match (err, &best) {
+                        (input_selection::Error::TransactionTooLarge { .. }, &Some(_)) => break,
+                        (input_selection::Error::NotEnoughInput, &Some(_)) => break,
+                        (err, _) => return Err(err),
+                    },
+                }
+            }
+            let (end, plan) = best.expect("at least one output is paid");
+            start = end;
+
+            let txaux = plan.sign(&WalletSigner::new(self, protocol_magic));
+            let size = tx::txaux_serialize_size(&txaux.tx, &txaux.witness);
+            if size > max_size {
+                return Err(input_selection::Error::TransactionTooLarge { size, max: max_size });
+            }
+
+            available.retain(|input| !txaux.tx.inputs.contains(&input.ptr));
+            if let Some(change) = plan.change {
+                let ptr = tx::TxoPointer::new(txaux.tx.id(), (txaux.tx.outputs.len() - 1) as u32);
+                available.insert(0, Input::new(ptr, change, change_addressing.clone()));
+            }
+            batch.push((txaux, plan.fee));
+        }
+        Ok(batch)
     }
 }
 
@@ -22,6 +22,7 @@
 ///
 
 use std::iter::repeat;
+use std::cmp::Ordering;
 use std::io::{Write,Read,Seek,SeekFrom};
 use std::fs;
 use std::path::Path;
@@ -237,7 +238,7 @@ pub fn dump_file(file: &mut fs::File) -> Result<(Lookup, Vec<BlockHash>)> {
     let mut v = Vec::new();
     let FanoutTotal(total) = lookup.fanout.get_total();
 
-    file.seek(SeekFrom::Start(HEADER_SIZE as u64)).unwrap();
+    file.seek(SeekFrom::Start(offset_hashes(lookup.params.bloom_size)))?;
     for _ in 0..total {
         let h = file_read_hash(file);
         v.push(h);
@@ -279,7 +280,8 @@ impl Reader<fs::File> {
 
     // conduct a search in the index file, returning the offset index of a found element
     //
-    // TODO switch to bilinear search with n > something
+    // the hashes are stored ordered, so above 2 elements a binary search is
+    // conducted in the fanout's window.
     pub fn search(&mut self, params: &Params, blk: &BlockHash, start_elements: FanoutStart, hier_elements: FanoutNb) -> Option<IndexOffset> {
         let hsz = offset_hashes(params.bloom_size);
         match hier_elements.0 {
@@ -300,17 +302,18 @@ impl Reader<fs::File> {
                 if &hash == blk { Some(ofs_element) } else if &hash2 == blk { Some(ofs_element+1) } else { None }
             },
             n => {
-                let start = start_elements.0;
-                let end = start_elements.0 + n;
-                let mut ofs_element = start;
-                let ofs = ofs_element as u64 * HASH_SIZE as u64;
-                self.handle.seek(SeekFrom::Start(hsz + ofs)).unwrap();
-                while ofs_element < end {
+                let mut low = start_elements.0;
+                let mut high = start_elements.0 + n;
+                while low < high {
+                    let ofs_element = low + (high - low) / 2;
+                    let ofs = ofs_element as u64 * HASH_SIZE as u64;
+                    self.handle.seek(SeekFrom::Start(hsz + ofs)).unwrap();
                     let hash = file_read_hash(&mut self.handle);
-                    if &hash == blk {
-                        return Some(ofs_element)
+                    match hash.cmp(blk) {
+                        Ordering::Equal => return Some(ofs_element),
+                        Ordering::Less => low = ofs_element + 1,
+                        Ordering::Greater => high = ofs_element,
                     }
-                    ofs_element += 1
                 }
                 None
             },
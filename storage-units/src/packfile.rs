@@ -21,8 +21,8 @@ use cryptoxide::blake2b;
 use cryptoxide::digest::Digest;
 use indexfile;
 
-const FILE_TYPE: magic::FileType = 0x5041434b; // = PACK
-const VERSION: magic::Version = 1;
+pub const FILE_TYPE: magic::FileType = 0x5041434b; // = PACK
+pub const VERSION: magic::Version = 1;
 
 /// A Stream Reader that also computes the hash of the sum of all data read
 pub struct Reader<R> {
@@ -64,6 +64,9 @@ impl<R: Seek> From<R> for Seeker<R> {
     }
 }
 
+/// the size above which a block read from a pack file is considered corrupt
+pub const MAX_BLOCK_SIZE: Size = 20000000;
+
 // a block in a pack file is:
 // * a 32 bit size in big endian
 // * data of the size above
@@ -73,7 +76,7 @@ pub fn read_next_block<R: Read>(mut file: R) -> io::Result<Vec<u8>> {
     file.read_exact(&mut sz_buf)?;
     let sz = read_size(&sz_buf);
     // don't potentially consume all memory when reading a corrupt file
-    assert!(sz < 20000000, "read block of size: {}", sz);
+    assert!(sz < MAX_BLOCK_SIZE, "read block of size: {}", sz);
     let mut v : Vec<u8> = repeat(0).take(sz as usize).collect();
     file.read_exact(v.as_mut_slice())?;
     if (v.len() % 4) != 0 {
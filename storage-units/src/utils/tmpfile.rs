@@ -29,6 +29,12 @@ impl TmpFile {
             .map(|file| TmpFile { file: file, path: path })
     }
 
+    /// flush the content written so far to the disk (see `fs::File::sync_all`),
+    /// e.g. before rendering the file permanent.
+    pub fn sync_all(&self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+
     pub fn render_permanent(&self, path: &PathBuf) -> io::Result<()> {
         // NOTE: we need to consider what is being written, in a case of a tag we want rename
         // to error out correctly in every cases rename fail, however in a case of a hash, since the hash is suppose
@@ -65,7 +71,29 @@ impl io::Write for TmpFile {
 // created
 pub fn atomic_write_simple(path: &PathBuf, buf: &[u8]) -> io::Result<()> {
     let mut tmpfile = TmpFile::create(path.parent().unwrap().to_path_buf())?;
-    tmpfile.write(buf)?;
+    tmpfile.write_all(buf)?;
     tmpfile.render_permanent(path)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn atomic_write_simple_writes_the_whole_buffer() {
+        let dir = env::temp_dir().join(template_create_temp("atomic-write-", ""));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file");
+
+        // a regular file rarely returns a short write, the buffer is
+        // large to give it a chance (e.g. on network file systems)
+        let buf : Vec<u8> = (0..(64 << 20)).map(|i : u32| (i % 251) as u8).collect();
+        atomic_write_simple(&path, &buf).unwrap();
+        assert!(fs::read(&path).unwrap() == buf);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
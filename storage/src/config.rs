@@ -1,20 +1,129 @@
-use std::path::{PathBuf};
-use std::{fs};
-use cardano::block::EpochId;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
+use cardano::block::{EpochId, RawBlock};
+use cardano::config::ProtocolMagic;
 
-use cardano::util::hex;
+use storage_units::{indexfile, packfile};
+use storage_units::utils::error::StorageError;
+use storage_units::utils::magic;
+use storage_units::utils::serialize::{OFF_SIZE, SIZE_SIZE, read_offset, read_size, write_offset};
+use storage_units::utils::tmpfile::{self, TmpFile};
 
 use types::*;
+use epoch::{self, EpochReport};
+use pack;
+use index::IndexFile;
+use super::{Error, blob};
 
 #[derive(Clone)]
 pub struct StorageConfig {
-    pub root_path: PathBuf
+    pub root_path: PathBuf,
+    durable_writes: bool,
+}
+
+/// the writer lock of a storage, see
+/// [`StorageConfig::lock_exclusive`](./struct.StorageConfig.html#method.lock_exclusive).
+///
+/// The lock is released when dropped.
+#[derive(Debug)]
+pub struct StorageLock {
+    path: PathBuf,
+}
+impl Drop for StorageLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn create_lock_file(path: &Path) -> io::Result<StorageLock> {
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{}", ::std::process::id())?;
+    Ok(StorageLock { path: path.to_path_buf() })
+}
+
+// the lock is stale if the process which wrote it is not running anymore,
+// which is only known where the processes are listed in `/proc`. A lock
+// being written (no process id yet) is not stale.
+fn is_stale_lock(path: &Path) -> bool {
+    let proc_dir = Path::new("/proc");
+    if !proc_dir.join("self").exists() { return false; }
+    match fs::read_to_string(path) {
+        Ok(content) => match content.trim().parse::<u32>() {
+            Ok(pid) => !proc_dir.join(pid.to_string()).exists(),
+            Err(_) => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// interval between two attempts of
+/// [`StorageConfig::lock_exclusive_blocking`](./struct.StorageConfig.html#method.lock_exclusive_blocking)
+const LOCK_RETRY_DELAY_MS: u64 = 10;
+
+/// version of the on-disk format of the storage
+pub const STORAGE_VERSION: u32 = 1;
+
+/// content of the storage's `config.yml` file, see
+/// [`StorageConfig::read_config_file`](./struct.StorageConfig.html#method.read_config_file).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StoredConfig {
+    /// the protocol magic of the network the blocks are from
+    pub network_magic: ProtocolMagic,
+    /// the version of the on-disk format, see `STORAGE_VERSION`
+    pub storage_version: u32,
+}
+impl Default for StoredConfig {
+    fn default() -> Self {
+        StoredConfig { network_magic: ProtocolMagic::default(), storage_version: STORAGE_VERSION }
+    }
+}
+impl StoredConfig {
+    fn to_yaml(self) -> String {
+        format!("network_magic: {}\nstorage_version: {}\n", self.network_magic, self.storage_version)
+    }
+
+    fn from_yaml(content: &str) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut network_magic = None;
+        let mut storage_version = None;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (key, value) = match line.find(':') {
+                None => return Err(invalid(format!("invalid line in config.yml: {}", line))),
+                Some(colon) => (line[..colon].trim(), line[colon + 1..].trim()),
+            };
+            let value : u32 = value.parse().map_err(|_| invalid(format!("invalid value for {} in config.yml: {}", key, value)))?;
+            match key {
+                "network_magic" => network_magic = Some(ProtocolMagic::from(value)),
+                "storage_version" => storage_version = Some(value),
+                _ => return Err(invalid(format!("unknown key in config.yml: {}", key))),
+            }
+        }
+        match (network_magic, storage_version) {
+            (Some(network_magic), Some(storage_version)) => Ok(StoredConfig { network_magic, storage_version }),
+            _ => Err(invalid("missing network_magic or storage_version in config.yml".to_owned())),
+        }
+    }
 }
 
 impl StorageConfig {
     pub fn new(path_buf: &PathBuf) -> Self {
-        StorageConfig { root_path: path_buf.clone() }
+        StorageConfig { root_path: path_buf.clone(), durable_writes: false }
+    }
+
+    /// make the blobs and the packs (and their index) be synced to the disk
+    /// before they are renamed to their final path, so a crash cannot
+    /// leave a truncated file behind the final name.
+    ///
+    /// This is off by default: syncing every file waits for the disk and
+    /// makes writing many small blobs much slower.
+    pub fn set_durable_writes(&mut self, durable: bool) {
+        self.durable_writes = durable;
     }
+    pub fn durable_writes(&self) -> bool { self.durable_writes }
     pub fn get_path(&self) -> PathBuf {
         self.root_path.clone()
     }
@@ -35,19 +144,93 @@ impl StorageConfig {
         p.push("config.yml");
         p
     }
+    pub fn get_lock_file(&self) -> PathBuf {
+        let mut p = self.get_path();
+        p.push("lock");
+        p
+    }
+
+    /// take the storage's writer lock (see `get_lock_file`), held until
+    /// the returned `StorageLock` is dropped.
+    ///
+    /// This does not wait: if another writer (in this process or another
+    /// one) holds the lock this fails with `io::ErrorKind::WouldBlock`, see
+    /// [`lock_exclusive_blocking`](./struct.StorageConfig.html#method.lock_exclusive_blocking)
+    /// to wait for it. Every writer of packs, indexes and epochs takes the
+    /// lock (`pack_blobs`, `import_epoch_range`, the crate's `pack_blobs`
+    /// and `epoch::epoch_create`), so two compactions of the same storage
+    /// cannot clobber each other's files.
+    ///
+    /// The lock is a file created in the storage's root, containing the id
+    /// of the process holding it. If a writer is killed before releasing
+    /// it, the lock is stale: where the running processes can be looked up
+    /// (`/proc` on Linux) a lock whose process is gone is taken over,
+    /// elsewhere the file has to be removed manually.
+    pub fn lock_exclusive(&self) -> io::Result<StorageLock> {
+        fs::create_dir_all(self.get_path())?;
+        let path = self.get_lock_file();
+        match create_lock_file(&path) {
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && is_stale_lock(&path) => {
+                warn!("taking over the stale storage lock {}", path.display());
+                fs::remove_file(&path)?;
+                create_lock_file(&path)
+            },
+            r => r,
+        }.map_err(|err| {
+            if err.kind() == io::ErrorKind::AlreadyExists {
+                io::Error::new(io::ErrorKind::WouldBlock, format!("storage is locked by another writer ({})", path.display()))
+            } else {
+                err
+            }
+        })
+    }
+
+    /// same as [`lock_exclusive`](./struct.StorageConfig.html#method.lock_exclusive)
+    /// but wait for the other writer to release the lock.
+    pub fn lock_exclusive_blocking(&self) -> io::Result<StorageLock> {
+        loop {
+            match self.lock_exclusive() {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    ::std::thread::sleep(::std::time::Duration::from_millis(LOCK_RETRY_DELAY_MS));
+                },
+                r => return r,
+            }
+        }
+    }
+
+    /// read the storage's `config.yml` (see `get_config_file`), the default
+    /// `StoredConfig` is returned if the file does not exist.
+    pub fn read_config_file(&self) -> io::Result<StoredConfig> {
+        let mut content = String::new();
+        match fs::File::open(self.get_config_file()) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(StoredConfig::default()),
+            Err(err) => return Err(err),
+            Ok(mut file) => file.read_to_string(&mut content)?,
+        };
+        StoredConfig::from_yaml(&content)
+    }
+
+    /// write the storage's `config.yml` (see `get_config_file`).
+    ///
+    /// The file is written in a temporary file first and then renamed, so
+    /// the `config.yml` is never partially written.
+    pub fn write_config_file(&self, cfg: &StoredConfig) -> io::Result<()> {
+        fs::create_dir_all(self.get_path())?;
+        tmpfile::atomic_write_simple(&self.get_config_file(), cfg.to_yaml().as_bytes())
+    }
     pub fn get_pack_filepath(&self, packhash: &PackHash) -> PathBuf {
         let mut p = self.get_filetype_dir(StorageFileType::Pack);
-        p.push(hex::encode(packhash));
+        p.push(packhash_to_hex(packhash));
         p
     }
     pub fn get_index_filepath(&self, packhash: &PackHash) -> PathBuf {
         let mut p = self.get_filetype_dir(StorageFileType::Index);
-        p.push(hex::encode(packhash));
+        p.push(packhash_to_hex(packhash));
         p
     }
     pub fn get_blob_filepath(&self, blockhash: &BlockHash) -> PathBuf {
         let mut p = self.get_filetype_dir(StorageFileType::Blob);
-        p.push(hex::encode(blockhash));
+        p.push(blockhash_to_hex(blockhash));
         p
     }
     pub fn get_tag_filepath<P: AsRef<str>>(&self, s: P) -> PathBuf {
@@ -77,46 +260,705 @@ impl StorageConfig {
         p
     }
 
-    pub fn list_indexes(&self) -> Vec<PackHash> {
+    /// list the epochs with a pack in the storage, in increasing order.
+    pub fn list_epochs(&self) -> io::Result<Vec<EpochId>> {
+        let mut epochs = Vec::new();
+        let entries = match fs::read_dir(self.get_filetype_dir(StorageFileType::Epoch)) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(epochs),
+            Err(err) => return Err(err),
+            Ok(entries) => entries,
+        };
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() { continue; }
+            if let Some(epochid) = entry.file_name().to_str().and_then(|s| s.parse::<EpochId>().ok()) {
+                if self.get_epoch_pack_filepath(epochid).is_file() {
+                    epochs.push(epochid);
+                }
+            }
+        }
+        epochs.sort();
+        Ok(epochs)
+    }
+
+    /// the highest epoch `N` such that all the epochs from `0` to `N`
+    /// (included) have a pack in the storage, `None` if epoch `0` is missing.
+    ///
+    /// This is where a sync can be resumed from.
+    pub fn highest_complete_epoch(&self) -> io::Result<Option<EpochId>> {
+        let mut highest = None;
+        for (expected, epochid) in self.list_epochs()?.into_iter().enumerate() {
+            if epochid != expected as EpochId { break; }
+            highest = Some(epochid);
+        }
+        Ok(highest)
+    }
+
+    /// verify the blocks of the epoch's pack against the epoch's refpack,
+    /// see [`epoch::epoch_verify`](../epoch/fn.epoch_verify.html).
+    ///
+    /// This allows to detect on-disk corruption before serving the epoch.
+    pub fn verify_epoch(&self, epoch: EpochId) -> io::Result<EpochReport> {
+        epoch::epoch_verify(self, epoch).map_err(error_to_io_error)
+    }
+
+    /// read the block framed at `offset` in the pack of the given epoch.
+    ///
+    /// `offset` is the offset in the pack file (including the pack's header),
+    /// as stored in the pack's index. Only the requested block is read: an
+    /// offset that does not point to a frame fitting in the pack (typically
+    /// an offset in the middle of a block) is reported as `InvalidData`.
+    pub fn read_block_at(&self, epoch: EpochId, offset: u64) -> io::Result<Vec<u8>> {
+        let packhash = epoch::epoch_read_pack(self, epoch).map_err(error_to_io_error)?;
+        let mut file = fs::File::open(self.get_pack_filepath(&packhash))?;
+        let len = file.metadata()?.len();
+        if offset < magic::HEADER_SIZE as u64 || offset + SIZE_SIZE as u64 > len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("offset {} is out of the pack of epoch {}", offset, epoch)));
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        let mut sz_buf = [0u8;SIZE_SIZE];
+        file.read_exact(&mut sz_buf)?;
+        let sz = read_size(&sz_buf) as u64;
+        if sz > len - offset - SIZE_SIZE as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("block of {} bytes at offset {} does not fit in the pack of epoch {}", sz, offset, epoch)));
+        }
+
+        let mut block = vec![0u8; sz as usize];
+        file.read_exact(&mut block)?;
+        Ok(block)
+    }
+
+    /// bundle the given loose blobs into a new pack, named by its content
+    /// hash, and write the pack's index.
+    ///
+    /// The blobs are packed in the given order. If `delete_blobs` is set
+    /// the loose blobs are removed once the pack and its index are written.
+    ///
+    /// Note that an already initialised `Storage` does not know about the
+    /// new pack, it is only loaded by `Storage::init`.
+    pub fn pack_blobs(&self, blobs: &[BlockHash], delete_blobs: bool) -> io::Result<PackHash> {
+        let _lock = self.lock_exclusive()?;
+        let mut writer = pack::packwriter_init(self).map_err(error_to_io_error)?;
+        for hash in blobs {
+            let blob = blob::read_raw_at(self, hash).map_err(error_to_io_error)?;
+            writer.append(hash, &blob[..])?;
+        }
+        let (packhash, index) = pack::packwriter_finalize(self, writer);
+        self.write_pack_index(&packhash, &index)?;
+
+        if delete_blobs {
+            for hash in blobs { blob::remove_at(self, hash); }
+        }
+        Ok(packhash)
+    }
+
+    /// remove the loose blobs that are not in the `reachable` set, e.g.
+    /// the blobs that have been packed since they were written.
+    ///
+    /// A blob removed in the meantime is not an error. Returns the number
+    /// of blobs removed.
+    pub fn gc_blobs(&self, reachable: &HashSet<BlockHash>) -> io::Result<usize> {
+        let mut removed = 0;
+        for hash in self.list_blob(None) {
+            if reachable.contains(&hash) { continue; }
+            match fs::remove_file(self.get_blob_filepath(&hash)) {
+                Ok(()) => removed += 1,
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(removed)
+    }
+
+    fn write_pack_index(&self, packhash: &PackHash, index: &indexfile::Index) -> io::Result<()> {
+        let mut tmpfile = TmpFile::create(self.get_filetype_dir(StorageFileType::Index))?;
+        index.write_to_tmpfile(&mut tmpfile).map_err(storage_to_io_error)?;
+        if self.durable_writes { tmpfile.sync_all()?; }
+        tmpfile.render_permanent(&self.get_index_filepath(packhash))
+    }
+
+    /// export the packs of the epochs `from` (included) to `to` (excluded)
+    /// in one stream, e.g. to bootstrap a new node from a trusted peer.
+    ///
+    /// Every epoch is written as its epoch id and the length of its pack
+    /// (both as 64 bits BE) followed by the content of the pack file.
+    /// The stream is read back with
+    /// [`import_epoch_range`](./struct.StorageConfig.html#method.import_epoch_range).
+    ///
+    /// Nothing is written if one of the epochs of the range is missing.
+    /// Returns the number of bytes written.
+    pub fn export_epoch_range(&self, from: EpochId, to: EpochId, out: &mut dyn Write) -> io::Result<u64> {
         let mut packs = Vec::new();
-        let p = self.get_filetype_dir(StorageFileType::Index);
-        for entry in fs::read_dir(p).unwrap() {
-            let entry = entry.unwrap();
-            if entry.file_type().unwrap().is_file() {
-                if let Ok(s) = entry.file_name().into_string() {
-                    if s.len() == 64 {
-                        let v = hex::decode(s.as_ref()).unwrap();
-                        let mut packref = [0;HASH_SIZE];
-                        packref.clone_from_slice(&v[..]);
-                        packs.push(packref);
-                    }
+        for epochid in from..to {
+            if ! epoch::epoch_exists(self, epochid).map_err(error_to_io_error)? {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("epoch {} is not in the storage", epochid)));
+            }
+            packs.push((epochid, epoch::epoch_read_pack(self, epochid).map_err(error_to_io_error)?));
+        }
+
+        let mut written = 0;
+        for (epochid, packhash) in packs {
+            let mut file = fs::File::open(self.get_pack_filepath(&packhash))?;
+            let len = file.metadata()?.len();
+            let mut hdr = [0u8;2 * OFF_SIZE];
+            write_offset(&mut hdr[..OFF_SIZE], epochid);
+            write_offset(&mut hdr[OFF_SIZE..], len);
+            out.write_all(&hdr)?;
+            let copied = io::copy(&mut (&mut file).take(len), out)?;
+            if copied != len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("pack of epoch {} is truncated", epochid)));
+            }
+            written += hdr.len() as u64 + len;
+        }
+        Ok(written)
+    }
+
+    /// import the epochs of a stream written by
+    /// [`export_epoch_range`](./struct.StorageConfig.html#method.export_epoch_range).
+    ///
+    /// The blocks of every epoch are written in a new pack (and its index)
+    /// and the epoch's refpack is rebuilt from the blocks, a block that
+    /// does not belong to its epoch is an error.
+    ///
+    /// Returns the imported epochs, in the order of the stream.
+    pub fn import_epoch_range(&self, input: &mut dyn Read) -> io::Result<Vec<EpochId>> {
+        let lock = self.lock_exclusive()?;
+        let mut epochs = Vec::new();
+        loop {
+            // the end of the stream is only expected between two epochs
+            let mut hdr = [0u8;2 * OFF_SIZE];
+            let read = loop {
+                match input.read(&mut hdr[..1]) {
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    res => break res?,
                 }
+            };
+            if read == 0 { break; }
+            input.read_exact(&mut hdr[1..])?;
+            let epochid = read_offset(&hdr[..OFF_SIZE]);
+            let len = read_offset(&hdr[OFF_SIZE..]);
+            let mut bytes = Vec::new();
+            if (&mut *input).take(len).read_to_end(&mut bytes)? as u64 != len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("pack of epoch {} is truncated", epochid)));
+            }
+            epochs.push(self.import_epoch(&lock, epochid, &bytes[..])?);
+        }
+        Ok(epochs)
+    }
+
+    fn import_epoch(&self, lock: &StorageLock, epochid: EpochId, mut bytes: &[u8]) -> io::Result<EpochId> {
+        magic::check_header(&mut bytes, packfile::FILE_TYPE, packfile::VERSION, packfile::VERSION).map_err(storage_to_io_error)?;
+
+        let mut writer = pack::packwriter_init(self).map_err(error_to_io_error)?;
+        while ! bytes.is_empty() {
+            // the stream is not trusted: check the size of the block before
+            // reading it, `read_next_block` asserts it is not corrupt
+            if bytes.len() < SIZE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("truncated block size in epoch {}", epochid)));
             }
+            let sz = read_size(&bytes[..SIZE_SIZE]);
+            if sz >= packfile::MAX_BLOCK_SIZE || sz as usize > bytes.len() - SIZE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid block size {} in epoch {}", sz, epochid)));
+            }
+            let data = packfile::read_next_block(&mut bytes)?;
+            let hdr = match RawBlock::from_dat(data.clone()).decode() {
+                Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid block in epoch {}: {}", epochid, err))),
+                Ok(blk) => blk.get_header(),
+            };
+            let block_epochid = hdr.get_blockdate().get_epochid();
+            if block_epochid != epochid {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("block of epoch {} in the pack of epoch {}", block_epochid, epochid)));
+            }
+            writer.append(&header_to_blockhash(&hdr.compute_hash()), &data[..])?;
+        }
+        let (packhash, index) = pack::packwriter_finalize(self, writer);
+        self.write_pack_index(&packhash, &index)?;
+        epoch::epoch_create_locked(self, lock, &packhash, epochid);
+        Ok(epochid)
+    }
+
+    /// iterate over the `(BlockHash, block)` stored in the given pack,
+    /// without loading the whole pack in memory.
+    ///
+    /// see [`PackBlockIterator`](../pack/struct.PackBlockIterator.html).
+    pub fn iter_pack(&self, packhash: &PackHash) -> io::Result<pack::PackBlockIterator> {
+        pack::PackBlockIterator::open(self, packhash)
+    }
+
+    /// number of bytes used by the files of each file type directory of the
+    /// storage, the directories being walked recursively.
+    ///
+    /// A directory that cannot be read is skipped (with a warning) instead
+    /// of failing the whole computation, a missing directory counts as 0.
+    pub fn disk_usage(&self) -> io::Result<HashMap<StorageFileType, u64>> {
+        let filetypes = [ StorageFileType::Pack, StorageFileType::Index, StorageFileType::Blob
+                        , StorageFileType::Tag, StorageFileType::RefPack, StorageFileType::Epoch ];
+        let mut usage = HashMap::new();
+        for ft in filetypes.iter() {
+            usage.insert(*ft, dir_usage(&self.get_filetype_dir(*ft)));
         }
+        Ok(usage)
+    }
+
+    pub fn list_indexes(&self) -> Vec<PackHash> {
+        let (packs, skipped) = self.scan_indexes().unwrap();
+        if skipped > 0 { warn!("{} non UTF-8 file name(s) skipped in {}", skipped, self.get_filetype_dir(StorageFileType::Index).display()); }
         packs
     }
 
-    pub fn list_blob(&self, limits: Option<u32>) -> Vec<BlockHash> {
-        let mut blobs = Vec::new();
-        let p = self.get_filetype_dir(StorageFileType::Blob);
-        for entry in fs::read_dir(p).unwrap() {
-            let entry = entry.unwrap();
-            if entry.file_type().unwrap().is_file() {
-                if let Ok(s) = entry.file_name().into_string() {
-                    if s.len() == 64 {
-                        let v = hex::decode(s.as_ref()).unwrap();
-                        let mut blobref = [0;HASH_SIZE];
-                        blobref.clone_from_slice(&v[..]);
-                        blobs.push(blobref);
-                        if blobs.len() == 0xffffffff { break };
-                        match limits {
-                            None => {},
-                            Some(l) => if blobs.len() as u32 >= l { break }
-                        }
-                    }
-                }
+    /// list the indexes of the storage, along with the number of file
+    /// names that are not valid UTF-8 and then cannot be a pack hash.
+    ///
+    /// Such a file name is unexpected in the index directory and may be
+    /// a sign of corruption.
+    pub fn scan_indexes(&self) -> io::Result<(Vec<PackHash>, usize)> {
+        self.scan_hashes(StorageFileType::Index, None, |_| true)
+    }
+
+    /// find the pack containing the given block, returning the hash of the
+    /// pack and the offset of the block in the pack file.
+    ///
+    /// Each index is only searched if the block's hash is present in its
+    /// fanout and bloom filter, the ordered hashes are then binary searched.
+    /// Loose blobs are not looked up.
+    pub fn lookup_block(&self, hash: &BlockHash) -> io::Result<Option<(PackHash, u64)>> {
+        for packhash in self.list_indexes() {
+            let index = IndexFile::open(self.get_index_filepath(&packhash))?;
+            if let Some(offset) = index.lookup(hash) {
+                return Ok(Some((packhash, offset)));
             }
         }
+        Ok(None)
+    }
+
+    pub fn list_blob(&self, limits: Option<u32>) -> Vec<BlockHash> {
+        let (blobs, skipped) = self.scan_blobs(limits).unwrap();
+        if skipped > 0 { warn!("{} non UTF-8 file name(s) skipped in {}", skipped, self.get_filetype_dir(StorageFileType::Blob).display()); }
         blobs
     }
+
+    /// same as [`scan_indexes`](./struct.StorageConfig.html#method.scan_indexes)
+    /// for the loose blobs, at most `limits` blobs are listed.
+    pub fn scan_blobs(&self, limits: Option<u32>) -> io::Result<(Vec<BlockHash>, usize)> {
+        let limit = match limits {
+            None => 0xffffffff,
+            Some(l) => l as usize,
+        };
+        self.scan_hashes(StorageFileType::Blob, Some(limit), |_| true)
+    }
+
+    /// list the loose blobs for which `pred` returns true, the predicate
+    /// being applied while the blob directory is scanned.
+    pub fn list_blob_where<F>(&self, pred: F) -> io::Result<Vec<BlockHash>>
+        where F: Fn(&BlockHash) -> bool
+    {
+        let (blobs, skipped) = self.scan_hashes(StorageFileType::Blob, None, pred)?;
+        if skipped > 0 { warn!("{} non UTF-8 file name(s) skipped in {}", skipped, self.get_filetype_dir(StorageFileType::Blob).display()); }
+        Ok(blobs)
+    }
+
+    fn scan_hashes<F>(&self, ft: StorageFileType, limit: Option<usize>, pred: F) -> io::Result<(Vec<[u8;HASH_SIZE]>, usize)>
+        where F: Fn(&[u8;HASH_SIZE]) -> bool
+    {
+        let mut hashes = Vec::new();
+        let mut skipped = 0;
+        for entry in fs::read_dir(self.get_filetype_dir(ft))? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() { continue; }
+            match entry.file_name().into_string() {
+                // the temporary files are not valid hashes either but are expected
+                Ok(s) => if let Ok(hash) = hash_from_hex(&s) {
+                    if !pred(&hash) { continue; }
+                    hashes.push(hash);
+                    if limit.map(|l| hashes.len() >= l).unwrap_or(false) { break; }
+                },
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok((hashes, skipped))
+    }
+}
+
+fn dir_usage(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return 0,
+        Err(err) => {
+            warn!("cannot read {}, skipped: {}", dir.display(), err);
+            return 0;
+        }
+    };
+    let mut total = 0;
+    for entry in entries {
+        let metadata = match entry.and_then(|entry| entry.metadata().map(|m| (entry.path(), m))) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                warn!("cannot read an entry of {}, skipped: {}", dir.display(), err);
+                continue;
+            }
+        };
+        let (path, metadata) = metadata;
+        if metadata.is_dir() {
+            total += dir_usage(&path);
+        } else {
+            total += metadata.len();
+        }
+    }
+    total
+}
+
+pub(crate) fn storage_to_io_error(err: StorageError) -> io::Error {
+    match err {
+        StorageError::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+fn error_to_io_error(err: Error) -> io::Error {
+    match err {
+        Error::StorageError(err) => storage_to_io_error(err),
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage_units::{packfile, reffile};
+    use super::super::{Storage, PackParameters, blob, pack_blobs, block_read};
+    use test_helpers::{temp_path, test_storage};
+
+    #[test]
+    fn config_file_round_trip() {
+        let path = temp_path("config-file");
+        let config = StorageConfig::new(&path);
+
+        let stored = StoredConfig { network_magic: ProtocolMagic::from(1097911063), storage_version: 7 };
+        config.write_config_file(&stored).unwrap();
+        assert_eq!(config.read_config_file().unwrap(), stored);
+
+        // overwriting the file replaces its content
+        config.write_config_file(&StoredConfig::default()).unwrap();
+        assert_eq!(config.read_config_file().unwrap(), StoredConfig::default());
+
+        fs::write(config.get_config_file(), "network_magic: 42\n").unwrap();
+        assert_eq!(config.read_config_file().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn config_file_missing() {
+        let storage = test_storage("config-file-missing");
+        let config = storage.config.clone();
+
+        assert!(!config.get_config_file().exists());
+        let stored = config.read_config_file().unwrap();
+        assert_eq!(stored, StoredConfig::default());
+        assert_eq!(stored.network_magic, ProtocolMagic::default());
+        assert_eq!(stored.storage_version, STORAGE_VERSION);
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn lookup_block_in_pack() {
+        let mut storage = test_storage("lookup-block");
+
+        // most of the hashes share the same fanout bucket
+        let mut hashes : Vec<BlockHash> = (0..6u8).map(|i| { let mut h = [0x42;HASH_SIZE]; h[1] = i * 3; h }).collect();
+        hashes.push([0x01;HASH_SIZE]);
+        for (i, hash) in hashes.iter().enumerate() {
+            blob::write(&storage, hash, &vec![i as u8; 10 + i]).unwrap();
+        }
+        let packhash = pack_blobs(&mut storage, &PackParameters::default());
+
+        let config = storage.config.clone();
+        for (i, hash) in hashes.iter().enumerate() {
+            let (found, offset) = config.lookup_block(hash).unwrap().expect("block is in the pack");
+            assert_eq!(found, packhash);
+            let mut seeker = packfile::Seeker::init(config.get_pack_filepath(&packhash)).unwrap();
+            assert_eq!(seeker.get_at_offset(offset).unwrap(), vec![i as u8; 10 + i]);
+        }
+
+        let mut missing = [0x42;HASH_SIZE];
+        missing[1] = 1;
+        assert_eq!(config.lookup_block(&missing).unwrap(), None);
+        assert_eq!(config.lookup_block(&[0x02;HASH_SIZE]).unwrap(), None);
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn read_block_at_offset_of_epoch_pack() {
+        let storage = test_storage("read-block-at");
+        let blocks = [vec![1u8; 13], vec![2u8; 40]];
+        let hashes = vec![[0x10;HASH_SIZE], [0x20;HASH_SIZE]];
+        for (hash, block) in hashes.iter().zip(blocks.iter()) {
+            blob::write(&storage, hash, block).unwrap();
+        }
+
+        let config = storage.config.clone();
+        let packhash = config.pack_blobs(&hashes, true).unwrap();
+        epoch::epoch_create_with_refpack(&config, &packhash, &reffile::Lookup::new(), 3);
+
+        for (hash, block) in hashes.iter().zip(blocks.iter()) {
+            let (_, offset) = config.lookup_block(hash).unwrap().expect("block is in the pack");
+            assert_eq!(&config.read_block_at(3, offset).unwrap(), block);
+        }
+
+        let (_, offset) = config.lookup_block(&hashes[0]).unwrap().unwrap();
+        // in the middle of the first block: its bytes read as a huge frame length
+        assert_eq!(config.read_block_at(3, offset + 4).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(config.read_block_at(3, 0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(config.read_block_at(3, 1 << 20).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(config.read_block_at(4, offset).is_err());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn disk_usage_per_filetype() {
+        let storage = test_storage("disk-usage");
+        let config = storage.config.clone();
+
+        blob::write(&storage, &[0x01;HASH_SIZE], &[0u8;100]).unwrap();
+        blob::write(&storage, &[0x02;HASH_SIZE], &[0u8;50]).unwrap();
+        let tag_dir = config.get_filetype_dir(StorageFileType::Tag).join("nested");
+        fs::create_dir_all(&tag_dir).unwrap();
+        fs::write(tag_dir.join("a"), [0u8;7]).unwrap();
+        fs::write(config.get_tag_filepath("b"), [0u8;3]).unwrap();
+
+        let usage = config.disk_usage().unwrap();
+        assert_eq!(usage.len(), 6);
+        // every blob is prefixed by the magic header
+        assert_eq!(usage[&StorageFileType::Blob], 150 + 2 * magic::HEADER_SIZE as u64);
+        assert_eq!(usage[&StorageFileType::Tag], 10);
+        assert_eq!(usage[&StorageFileType::Pack], 0);
+        assert_eq!(usage[&StorageFileType::Epoch], 0);
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn durable_writes_of_blobs_and_packs() {
+        let path = temp_path("durable");
+        let mut config = StorageConfig::new(&path);
+        assert!(!config.durable_writes());
+        config.set_durable_writes(true);
+        let storage = Storage::init(&config).unwrap();
+        assert!(storage.config.durable_writes());
+
+        let hashes = vec![[0x10;HASH_SIZE], [0x20;HASH_SIZE]];
+        for (i, hash) in hashes.iter().enumerate() {
+            blob::write(&storage, hash, &[i as u8; 20]).unwrap();
+        }
+        let packhash = config.pack_blobs(&hashes, true).unwrap();
+        for (i, hash) in hashes.iter().enumerate() {
+            let (found, offset) = config.lookup_block(hash).unwrap().expect("block is in the pack");
+            assert_eq!(found, packhash);
+            let mut seeker = packfile::Seeker::init(config.get_pack_filepath(&packhash)).unwrap();
+            assert_eq!(seeker.get_at_offset(offset).unwrap(), vec![i as u8; 20]);
+        }
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn lock_exclusive_is_exclusive() {
+        use std::thread;
+
+        let storage = test_storage("lock");
+        let config = storage.config.clone();
+        blob::write(&storage, &[1;HASH_SIZE], &[1, 2, 3]).unwrap();
+
+        let lock = config.lock_exclusive().unwrap();
+        let other = config.clone();
+        let attempt = thread::spawn(move || {
+            let locked = other.lock_exclusive().map(|_| ());
+            let packed = other.pack_blobs(&[[1;HASH_SIZE]], false).map(|_| ());
+            (locked, packed)
+        });
+        let (locked, packed) = attempt.join().unwrap();
+        assert_eq!(locked.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(packed.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert!(config.list_indexes().is_empty());
+
+        drop(lock);
+        assert!(!config.get_lock_file().exists());
+        config.pack_blobs(&[[1;HASH_SIZE]], false).unwrap();
+        drop(config.lock_exclusive().unwrap());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn epoch_writers_wait_for_the_lock() {
+        use std::{thread, time::Duration};
+
+        let storage = test_storage("lock-wait");
+        let config = storage.config.clone();
+
+        let lock = config.lock_exclusive().unwrap();
+        let other = config.clone();
+        let writer = thread::spawn(move || {
+            epoch::epoch_create_with_refpack(&other, &[7;HASH_SIZE], &reffile::Lookup::new(), 3)
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert!(!config.get_epoch_pack_filepath(3).exists());
+
+        drop(lock);
+        writer.join().unwrap();
+        assert!(config.get_epoch_pack_filepath(3).exists());
+        assert!(!config.get_lock_file().exists());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over() {
+        let storage = test_storage("lock-stale");
+        let config = storage.config.clone();
+
+        // a lock held by a running process is not stale
+        fs::write(config.get_lock_file(), format!("{}\n", ::std::process::id())).unwrap();
+        assert_eq!(config.lock_exclusive().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        // no process has this id
+        fs::write(config.get_lock_file(), "4294967295\n").unwrap();
+        if Path::new("/proc/self").exists() {
+            drop(config.lock_exclusive().unwrap());
+            assert!(!config.get_lock_file().exists());
+        } else {
+            assert_eq!(config.lock_exclusive().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        }
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn pack_blobs_readable_back() {
+        let storage = test_storage("pack-blobs");
+        let config = storage.config.clone();
+
+        let blobs : Vec<(BlockHash, Vec<u8>)> = (1..4u8).map(|i| ([i;HASH_SIZE], (0..i * 7).collect())).collect();
+        for (hash, data) in blobs.iter() {
+            blob::write(&storage, hash, data).unwrap();
+        }
+        let hashes : Vec<BlockHash> = blobs.iter().map(|b| b.0).collect();
+        let packhash = config.pack_blobs(&hashes, true).unwrap();
+
+        assert_eq!(config.list_indexes(), vec![packhash]);
+        let storage = Storage::init(&config).unwrap();
+        for (hash, data) in blobs.iter() {
+            assert!(!blob::exist(&storage, hash));
+            assert_eq!(&block_read(&storage, hash).expect("block is in the pack").0, data);
+            assert_eq!(config.lookup_block(hash).unwrap().map(|l| l.0), Some(packhash));
+        }
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn gc_blobs_keeps_reachable_blobs() {
+        let storage = test_storage("gc-blobs");
+        let config = storage.config.clone();
+
+        let hashes : Vec<BlockHash> = (1..6u8).map(|i| [i;HASH_SIZE]).collect();
+        for hash in hashes.iter() {
+            blob::write(&storage, hash, &hash[..]).unwrap();
+        }
+        let reachable : HashSet<BlockHash> = vec![hashes[1], hashes[3]].into_iter().collect();
+
+        assert_eq!(config.gc_blobs(&reachable).unwrap(), 3);
+        for hash in hashes.iter() {
+            assert_eq!(blob::exist(&storage, hash), reachable.contains(hash));
+        }
+        assert_eq!(config.gc_blobs(&reachable).unwrap(), 0);
+        assert_eq!(config.gc_blobs(&HashSet::new()).unwrap(), 2);
+        assert!(config.list_blob(None).is_empty());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn list_blob_where_filters_the_blobs() {
+        let storage = test_storage("list-blob-where");
+        let config = storage.config.clone();
+
+        let hashes : Vec<BlockHash> = (0..8u8).map(|i| { let mut h = [i;HASH_SIZE]; h[0] = i % 2; h }).collect();
+        for hash in hashes.iter() {
+            blob::write(&storage, hash, &hash[..]).unwrap();
+        }
+
+        let mut found = config.list_blob_where(|hash| hash[0] == 1).unwrap();
+        found.sort();
+        let expected : Vec<BlockHash> = hashes.iter().filter(|h| h[0] == 1).cloned().collect();
+        assert_eq!(found, expected);
+        assert_eq!(found.len(), 4);
+
+        assert!(config.list_blob_where(|_| false).unwrap().is_empty());
+        assert_eq!(config.list_blob_where(|_| true).unwrap().len(), hashes.len());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn scan_skips_non_utf8_file_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let storage = test_storage("non-utf8");
+        let config = storage.config.clone();
+        for i in 1..3u8 {
+            blob::write(&storage, &[i;HASH_SIZE], &[i]).unwrap();
+        }
+        let mut invalid = config.get_filetype_dir(StorageFileType::Blob);
+        invalid.push(OsStr::from_bytes(&[0x66, 0x6f, 0xff, 0xfe]));
+        fs::write(&invalid, b"garbage").unwrap();
+        fs::write(config.get_filetype_dir(StorageFileType::Blob).join("not-a-hash"), b"").unwrap();
+
+        let (mut blobs, skipped) = config.scan_blobs(None).unwrap();
+        blobs.sort();
+        assert_eq!(blobs, vec![[1;HASH_SIZE], [2;HASH_SIZE]]);
+        assert_eq!(skipped, 1);
+        assert_eq!(config.list_blob(None).len(), 2);
+        assert_eq!(config.scan_blobs(Some(1)).unwrap().0.len(), 1);
+        assert_eq!(config.scan_indexes().unwrap(), (Vec::<PackHash>::new(), 0));
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn iter_pack_in_order() {
+        let storage = test_storage("iter-pack");
+        let config = storage.config.clone();
+
+        // the blobs are not written in the order of their hashes
+        let blobs : Vec<(BlockHash, Vec<u8>)> = (1..6u8).rev().map(|i| ([i;HASH_SIZE], (0..i * 5).collect())).collect();
+        for (hash, data) in blobs.iter() {
+            blob::write(&storage, hash, data).unwrap();
+        }
+        let hashes : Vec<BlockHash> = blobs.iter().map(|b| b.0).collect();
+        let packhash = config.pack_blobs(&hashes, false).unwrap();
+
+        let read : Vec<(BlockHash, Vec<u8>)> = config.iter_pack(&packhash).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(read, blobs);
+
+        // truncate the last block: the previous blocks are read, then an error
+        let path = config.get_pack_filepath(&packhash);
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+
+        let mut iter = config.iter_pack(&packhash).unwrap();
+        for expected in blobs[..blobs.len() - 1].iter() {
+            assert_eq!(&iter.next().unwrap().unwrap(), expected);
+        }
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
 }
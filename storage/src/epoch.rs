@@ -1,15 +1,20 @@
 use std::fs;
+use std::collections::BTreeSet;
 use std::io::{Read};
 use cardano::util::{hex};
 use cardano;
 
 use super::{Result, Error, StorageConfig, PackHash, packreader_init, packreader_block_next, header_to_blockhash};
-use storage_units::utils::tmpfile;
+use config::StorageLock;
+use storage_units::utils::{tmpfile, magic};
 use storage_units::utils::tmpfile::{TmpFile};
 use storage_units::utils::error::StorageError;
 use storage_units::{packfile, reffile};
 
+/// write the epoch's pack pointer and refpack, waiting for the storage's
+/// writer lock (see `StorageConfig::lock_exclusive`).
 pub fn epoch_create_with_refpack(config: &StorageConfig, packref: &PackHash, refpack: &reffile::Lookup, epochid: cardano::block::EpochId) {
+    let _lock = config.lock_exclusive_blocking().unwrap();
     let dir = config.get_epoch_dir(epochid);
     fs::create_dir_all(dir).unwrap();
 
@@ -21,7 +26,15 @@ pub fn epoch_create_with_refpack(config: &StorageConfig, packref: &PackHash, ref
     tmpfile.render_permanent(&config.get_epoch_refpack_filepath(epochid)).unwrap();
 }
 
+/// create the epoch from its pack, waiting for the storage's writer lock
+/// (see `StorageConfig::lock_exclusive`).
 pub fn epoch_create(config: &StorageConfig, packref: &PackHash, epochid: cardano::block::EpochId) {
+    let lock = config.lock_exclusive_blocking().unwrap();
+    epoch_create_locked(config, &lock, packref, epochid)
+}
+
+/// same as `epoch_create`, for a writer already holding the storage's lock.
+pub fn epoch_create_locked(config: &StorageConfig, _lock: &StorageLock, packref: &PackHash, epochid: cardano::block::EpochId) {
     // read the pack and append the block hash as we find them in the refpack.
     let mut rp = reffile::Lookup::new();
     let mut reader = packreader_init(config, packref);
@@ -116,3 +129,248 @@ pub fn epoch_exists(config: &StorageConfig, epochid: cardano::block::EpochId) ->
         Err(err) => Err(err)
     }
 }
+
+/// Issue found while verifying an epoch, see [`epoch_verify`](./fn.epoch_verify.html).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EpochCorruption {
+    /// the block at the given offset in the pack file cannot be decoded
+    UndecodableBlock(u64),
+    /// the block at the given offset in the pack file is not the one
+    /// referenced at its slot in the refpack
+    ReferenceMismatch(u64),
+    /// the block referenced at the given index of the refpack is not in the pack
+    MissingBlock(usize),
+    /// the content of the pack does not hash to the epoch's pack hash
+    PackHashMismatch,
+}
+
+/// Result of the verification of an epoch, see [`epoch_verify`](./fn.epoch_verify.html).
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct EpochReport {
+    pub corruptions: Vec<EpochCorruption>,
+}
+impl EpochReport {
+    pub fn is_valid(&self) -> bool { self.corruptions.is_empty() }
+
+    /// offsets, in the pack file, of the blocks found to be corrupted
+    pub fn bad_offsets(&self) -> Vec<u64> {
+        self.corruptions.iter().filter_map(|c| match c {
+            EpochCorruption::UndecodableBlock(ofs) => Some(*ofs),
+            EpochCorruption::ReferenceMismatch(ofs) => Some(*ofs),
+            _ => None,
+        }).collect()
+    }
+}
+
+/// Read the pack of an epoch and check every block against the epoch's refpack.
+///
+/// Each block's hash is recomputed and compared to the reference of its
+/// slot, the refpack's references are all expected in the pack and the
+/// content of the pack is expected to hash to the pack's hash.
+pub fn epoch_verify(config: &StorageConfig, epochid: cardano::block::EpochId) -> Result<EpochReport> {
+    let packhash = epoch_read_pack(config, epochid)?;
+    let refpack = reffile::Lookup::from_path(config.get_epoch_refpack_filepath(epochid))?;
+    let mut reader = packfile::Reader::open(config.get_pack_filepath(&packhash))?;
+
+    let mut report = EpochReport::default();
+    let mut found = vec![false; refpack.len()];
+    loop {
+        let offset = magic::HEADER_SIZE as u64 + reader.pos;
+        let data = match reader.get_next() {
+            None => break,
+            Some(data) => data,
+        };
+        let hdr = match cardano::block::RawBlock::from_dat(data).decode() {
+            Err(_) => { report.corruptions.push(EpochCorruption::UndecodableBlock(offset)); continue; },
+            Ok(blk) => blk.get_header(),
+        };
+        let index = match hdr.get_blockdate() {
+            cardano::block::BlockDate::Genesis(epoch) if epoch == epochid => Some(0),
+            cardano::block::BlockDate::Normal(ref slot) if slot.epoch == epochid => Some(slot.slotid as usize + 1),
+            _ => None,
+        };
+        let hash = header_to_blockhash(&hdr.compute_hash());
+        match index {
+            Some(index) if refpack.get(index) == Some(&hash) => found[index] = true,
+            _ => report.corruptions.push(EpochCorruption::ReferenceMismatch(offset)),
+        }
+    }
+
+    for (index, hash) in refpack.iter().enumerate() {
+        if !found[index] && hash.iter().any(|b| *b != 0) {
+            report.corruptions.push(EpochCorruption::MissingBlock(index));
+        }
+    }
+    if reader.finalize() != packhash {
+        report.corruptions.push(EpochCorruption::PackHashMismatch);
+    }
+    Ok(report)
+}
+
+/// Sorted list of the distinct epochs the given blocks belong to.
+///
+/// This is handy to decide how to group blocks into epoch packs.
+pub fn epochs_spanned(blocks: &[cardano::block::Block]) -> Vec<cardano::block::EpochId> {
+    let epochs : BTreeSet<_> = blocks.iter()
+        .map(|blk| blk.get_header().get_blockdate().get_epochid())
+        .collect();
+    epochs.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cbor_event::{self, se::Serializer};
+    use cardano::block::{Block, RawBlock};
+    use std::io::{Seek, SeekFrom, Write};
+    use storage_units::utils::serialize::OFF_SIZE;
+    use super::super::{Storage, pack};
+    use test_helpers::{temp_path, test_storage};
+
+    // minimal genesis block of the given epoch
+    fn genesis_block_bytes(epoch: cardano::block::EpochId) -> Vec<u8> {
+        Serializer::new_vec()
+            .write_array(cbor_event::Len::Len(2)).unwrap()
+            .write_unsigned_integer(0).unwrap()
+            .write_array(cbor_event::Len::Len(3)).unwrap()
+                // header
+                .write_array(cbor_event::Len::Len(5)).unwrap()
+                    .write_unsigned_integer(764824073).unwrap()
+                    .write_bytes([0u8;32]).unwrap()
+                    .write_bytes([0u8;32]).unwrap()
+                    .write_array(cbor_event::Len::Len(2)).unwrap()
+                        .write_unsigned_integer(epoch).unwrap()
+                        .write_array(cbor_event::Len::Len(1)).unwrap()
+                            .write_unsigned_integer(epoch * 21600).unwrap()
+                    .write_map(cbor_event::Len::Len(0)).unwrap()
+                // body
+                .write_array(cbor_event::Len::Indefinite).unwrap()
+                .write_special(cbor_event::Special::Break).unwrap()
+                // extra
+                .write_array(cbor_event::Len::Len(1)).unwrap()
+                    .write_map(cbor_event::Len::Len(0)).unwrap()
+            .finalize()
+    }
+
+    fn genesis_block(epoch: cardano::block::EpochId) -> Block {
+        RawBlock::from_dat(genesis_block_bytes(epoch)).decode().unwrap()
+    }
+
+    #[test]
+    fn epochs_spanned_two_epochs() {
+        let blocks = vec![genesis_block(3), genesis_block(2), genesis_block(3), genesis_block(2)];
+        assert_eq!(epochs_spanned(&blocks), vec![2, 3]);
+        assert_eq!(epochs_spanned(&[]), Vec::<cardano::block::EpochId>::new());
+    }
+
+    #[test]
+    fn epoch_verify_detects_corrupted_block() {
+        let path = temp_path("epoch-verify");
+        let storage = Storage::init(&StorageConfig::new(&path)).unwrap();
+        let config = &storage.config;
+
+        let bytes = genesis_block_bytes(0);
+        let hash = header_to_blockhash(&genesis_block(0).get_header().compute_hash());
+        let mut writer = pack::packwriter_init(config).unwrap();
+        writer.append(&hash, &bytes).unwrap();
+        let (packhash, _) = pack::packwriter_finalize(config, writer);
+        epoch_create(config, &packhash, 0);
+
+        assert_eq!(epoch_verify(config, 0).unwrap(), EpochReport::default());
+        assert!(config.verify_epoch(0).unwrap().is_valid());
+
+        // corrupt the previous hash of the block's header: the block still
+        // decodes but does not match its reference anymore.
+        let mut file = fs::OpenOptions::new().write(true).open(config.get_pack_filepath(&packhash)).unwrap();
+        file.seek(SeekFrom::Start(magic::HEADER_SIZE as u64 + 4 + 11)).unwrap();
+        file.write_all(&[0xff]).unwrap();
+        drop(file);
+
+        let report = epoch_verify(config, 0).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.bad_offsets(), vec![magic::HEADER_SIZE as u64]);
+        assert_eq!(report.corruptions,
+                   vec![ EpochCorruption::ReferenceMismatch(magic::HEADER_SIZE as u64)
+                       , EpochCorruption::MissingBlock(0)
+                       , EpochCorruption::PackHashMismatch
+                       ]);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn export_import_epoch_range() {
+        let source = test_storage("epoch-export");
+        let config = &source.config;
+
+        let mut packhashes = Vec::new();
+        for epochid in 0..2 {
+            let hash = header_to_blockhash(&genesis_block(epochid).get_header().compute_hash());
+            let mut writer = pack::packwriter_init(config).unwrap();
+            writer.append(&hash, &genesis_block_bytes(epochid)).unwrap();
+            let (packhash, _) = pack::packwriter_finalize(config, writer);
+            epoch_create(config, &packhash, epochid);
+            packhashes.push(packhash);
+        }
+
+        let mut stream = Vec::new();
+        assert_eq!(config.export_epoch_range(0, 2, &mut stream).unwrap(), stream.len() as u64);
+
+        // nothing is written when an epoch of the range is missing
+        let mut partial = Vec::new();
+        let err = config.export_epoch_range(0, 3, &mut partial).unwrap_err();
+        assert_eq!(err.kind(), ::std::io::ErrorKind::NotFound);
+        assert!(partial.is_empty());
+
+        let destination = test_storage("epoch-import");
+        let imported = &destination.config;
+        assert_eq!(imported.import_epoch_range(&mut &stream[..]).unwrap(), vec![0, 1]);
+        for epochid in 0..2 {
+            assert_eq!(epoch_read_pack(imported, epochid).unwrap(), packhashes[epochid as usize]);
+            assert!(imported.verify_epoch(epochid).unwrap().is_valid());
+        }
+
+        // a truncated stream is an error
+        assert!(imported.import_epoch_range(&mut &stream[..stream.len() - 1]).is_err());
+
+        // a corrupt block size is an error, not an abort
+        let mut corrupt = stream.clone();
+        let sz_offset = 2 * OFF_SIZE + magic::HEADER_SIZE;
+        corrupt[sz_offset..sz_offset + 4].copy_from_slice(&[0xff;4]);
+        let err = imported.import_epoch_range(&mut &corrupt[..]).unwrap_err();
+        assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+        fs::remove_dir_all(imported.get_path()).unwrap();
+    }
+
+    #[test]
+    fn highest_complete_epoch_stops_at_the_first_gap() {
+        let path = temp_path("complete-epoch");
+        let storage = Storage::init(&StorageConfig::new(&path)).unwrap();
+        let config = &storage.config;
+
+        assert_eq!(config.list_epochs().unwrap(), Vec::<cardano::block::EpochId>::new());
+        assert_eq!(config.highest_complete_epoch().unwrap(), None);
+
+        for epochid in [4, 1, 2, 0].iter() {
+            let hash = header_to_blockhash(&genesis_block(*epochid).get_header().compute_hash());
+            let mut writer = pack::packwriter_init(config).unwrap();
+            writer.append(&hash, &genesis_block_bytes(*epochid)).unwrap();
+            let (packhash, _) = pack::packwriter_finalize(config, writer);
+            epoch_create(config, &packhash, *epochid);
+
+            if *epochid == 2 {
+                // epoch 0 is not there yet
+                assert_eq!(config.highest_complete_epoch().unwrap(), None);
+            }
+        }
+        // an epoch directory without a pack is not an epoch of the storage
+        fs::create_dir_all(config.get_epoch_dir(3)).unwrap();
+
+        assert_eq!(config.list_epochs().unwrap(), vec![0, 1, 2, 4]);
+        assert_eq!(config.highest_complete_epoch().unwrap(), Some(2));
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
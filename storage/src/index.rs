@@ -0,0 +1,145 @@
+//! index files associate the hash of every block of a pack with the offset
+//! of the block in the pack file.
+//!
+//! The entries are stored sorted by hash behind a fanout table and a bloom
+//! filter (see `storage_units::indexfile`), so a lookup only reads the
+//! header once and then binary searches the slice of hashes sharing the
+//! first byte of the searched hash.
+
+use std::{fs, io, path::{Path, PathBuf}};
+use cardano::hash::Blake2b256;
+use storage_units::indexfile;
+use storage_units::utils::tmpfile::TmpFile;
+
+use types::{BlockHash, PackHash, HASH_SIZE};
+use config::storage_to_io_error;
+
+/// an opened index file, only the fanout and the bloom filter are kept
+/// in memory.
+pub struct IndexFile {
+    path: PathBuf,
+    lookup: indexfile::Lookup,
+}
+
+impl IndexFile {
+    /// write the given entries (in any order) as an index file at `path`.
+    ///
+    /// The file is written atomically. The returned hash is the blake2b-256
+    /// hash of the content of the index file.
+    pub fn write<P: AsRef<Path>>(path: P, entries: &[(BlockHash, u64)]) -> io::Result<PackHash> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut index = indexfile::Index::new();
+        for &(ref hash, offset) in entries {
+            index.append(hash, offset);
+        }
+
+        let mut tmpfile = TmpFile::create(dir)?;
+        index.write_to_tmpfile(&mut tmpfile).map_err(storage_to_io_error)?;
+        tmpfile.render_permanent(&path.to_path_buf())?;
+
+        let digest = Blake2b256::new(&fs::read(path)?);
+        let mut hash = [0u8;HASH_SIZE];
+        hash.clone_from_slice(digest.as_ref());
+        Ok(hash)
+    }
+
+    /// open the index file at `path`, reading its fanout and bloom filter.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let lookup = indexfile::Lookup::read_from_file(&mut fs::File::open(&path)?).map_err(storage_to_io_error)?;
+        Ok(IndexFile { path, lookup })
+    }
+
+    /// return the offset associated to the given hash, if it is in the index.
+    ///
+    /// Any error reading the file is reported as the hash not being found.
+    pub fn lookup(&self, hash: &BlockHash) -> Option<u64> {
+        let (start, nb) = self.lookup.fanout.get_indexer_by_hash(hash);
+        if nb.0 == 0 || !self.lookup.bloom.search(hash) { return None; }
+
+        let mut reader = indexfile::Reader::init(&self.path).ok()?;
+        let index_offset = reader.search(&self.lookup.params, hash, start, nb)?;
+        Some(reader.resolve_index_offset(index_offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_helpers::temp_path;
+
+    fn temp_dir() -> PathBuf {
+        let path = temp_path("index");
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn hash(first: u8, n: u32) -> BlockHash {
+        let mut h = [0u8;HASH_SIZE];
+        h[0] = first;
+        h[1] = (n >> 24) as u8;
+        h[2] = (n >> 16) as u8;
+        h[3] = (n >> 8) as u8;
+        h[4] = n as u8;
+        h
+    }
+
+    #[test]
+    fn write_and_open_unsorted_entries() {
+        let dir = temp_dir();
+        let path = dir.join("index");
+        let entries = vec![(hash(0xf0, 1), 300), (hash(0x01, 2), 12), (hash(0x80, 3), 1024)];
+
+        let digest = IndexFile::write(&path, &entries).unwrap();
+        assert_eq!(digest, IndexFile::write(dir.join("index2"), &entries).unwrap());
+
+        let index = IndexFile::open(&path).unwrap();
+        for &(ref h, offset) in entries.iter() {
+            assert_eq!(index.lookup(h), Some(offset));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lookup_hits_and_misses() {
+        let dir = temp_dir();
+        let path = dir.join("index");
+        // all but one entry share the same fanout bucket
+        let entries : Vec<_> = (0..50).map(|n| (hash(0x42, n * 2), n as u64 * 100)).chain(Some((hash(0x10, 0), 7))).collect();
+        IndexFile::write(&path, &entries).unwrap();
+
+        let index = IndexFile::open(&path).unwrap();
+        for &(ref h, offset) in entries.iter() {
+            assert_eq!(index.lookup(h), Some(offset));
+        }
+        // same bucket, not in the index
+        assert_eq!(index.lookup(&hash(0x42, 1)), None);
+        assert_eq!(index.lookup(&hash(0x42, 1000)), None);
+        // empty bucket
+        assert_eq!(index.lookup(&hash(0x43, 0)), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lookup_in_large_index() {
+        let dir = temp_dir();
+        let path = dir.join("index");
+        let entries : Vec<_> = (0..20000u32).map(|n| (hash((n % 7) as u8, n), n as u64 * 4)).collect();
+        IndexFile::write(&path, &entries).unwrap();
+
+        let index = IndexFile::open(&path).unwrap();
+        for &(ref h, offset) in entries.iter().step_by(97) {
+            assert_eq!(index.lookup(h), Some(offset));
+        }
+        assert_eq!(index.lookup(&hash(3, 20001)), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,6 +4,15 @@ extern crate cbor_event;
 extern crate storage_units;
 extern crate cardano;
 extern crate rand;
+#[cfg(feature = "generic-serialization")]
+extern crate serde;
+#[cfg(all(test, feature = "generic-serialization"))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(all(test, feature = "generic-serialization"))]
+extern crate serde_json;
+#[cfg(all(test, feature = "generic-serialization"))]
+extern crate bincode;
 
 pub mod block;
 pub mod types;
@@ -12,6 +21,12 @@ pub mod pack;
 pub mod tag;
 pub mod epoch;
 pub mod refpack;
+pub mod index;
+pub mod restore;
+
+#[cfg(test)]
+mod test_helpers;
+
 use std::{fs, io, result};
 
 pub use config::StorageConfig;
@@ -156,7 +171,7 @@ fn tmpfile_create_type(storage: &Storage, filetype: StorageFileType) -> TmpFile
 pub mod blob {
     use std::fs;
     use std::io::{Read,Write};
-    use super::{Result};
+    use super::{Result, StorageConfig};
     use cardano::block::RawBlock;
     use magic;
 
@@ -168,13 +183,19 @@ pub mod blob {
         let mut tmp_file = super::tmpfile_create_type(storage, super::StorageFileType::Blob);
         magic::write_header(&mut tmp_file, FILE_TYPE, VERSION)?;
         tmp_file.write_all(block)?;
+        if storage.config.durable_writes() { tmp_file.sync_all()?; }
         tmp_file.render_permanent(&path)?;
         Ok(())
     }
 
     pub fn read_raw(storage: &super::Storage, hash: &super::BlockHash) -> Result<Vec<u8>> {
+        read_raw_at(&storage.config, hash)
+    }
+
+    /// same as `read_raw` but only needs the storage's configuration
+    pub fn read_raw_at(config: &StorageConfig, hash: &super::BlockHash) -> Result<Vec<u8>> {
         let mut content = Vec::new();
-        let path = storage.config.get_blob_filepath(&hash);
+        let path = config.get_blob_filepath(hash);
 
         let mut file = fs::File::open(path)?;
         magic::check_header(&mut file, FILE_TYPE, VERSION, VERSION)?;
@@ -192,7 +213,12 @@ pub mod blob {
     }
 
     pub fn remove(storage: &super::Storage, hash: &super::BlockHash) {
-        let p = storage.config.get_blob_filepath(hash);
+        remove_at(&storage.config, hash)
+    }
+
+    /// same as `remove` but only needs the storage's configuration
+    pub fn remove_at(config: &StorageConfig, hash: &super::BlockHash) {
+        let p = config.get_blob_filepath(hash);
         match fs::remove_file(p) {
             Ok(()) => {},
             Err(_) => {},
@@ -277,7 +303,11 @@ impl Default for PackParameters {
     }
 }
 
+/// pack the loose blobs, waiting for the storage's writer lock (see
+/// `StorageConfig::lock_exclusive`) so concurrent compactions do not
+/// clobber each other's pack and index files.
 pub fn pack_blobs(storage: &mut Storage, params: &PackParameters) -> PackHash {
+    let _lock = storage.config.lock_exclusive_blocking().unwrap();
     let mut writer = pack::packwriter_init(&storage.config).unwrap();
     let mut blob_packed = Vec::new();
 
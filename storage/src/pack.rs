@@ -1,5 +1,9 @@
-use std::fs;
+use std::{fs, io};
+use std::io::{Read, Seek};
+use std::collections::BTreeMap;
 use storage_units::utils::tmpfile::{TmpFile};
+use storage_units::utils::magic;
+use storage_units::utils::serialize::{read_size, offset_align4, SIZE_SIZE};
 use cardano;
 use super::Result;
 
@@ -41,6 +45,7 @@ pub fn packwriter_init(cfg: &super::StorageConfig) -> Result<packfile::Writer> {
 
 pub fn packwriter_finalize(cfg: &super::StorageConfig, writer: packfile::Writer) -> (super::PackHash, indexfile::Index) {
     let (tmpfile, packhash, index) = writer.finalize().unwrap();
+    if cfg.durable_writes() { tmpfile.sync_all().unwrap(); }
     let path = cfg.get_pack_filepath(&packhash);
     tmpfile.render_permanent(&path).unwrap();
     (packhash, index)
@@ -53,3 +58,77 @@ pub fn packreader_init(cfg: &super::StorageConfig, packhash: &super::PackHash) -
 pub fn packreader_block_next(reader: &mut packfile::Reader<fs::File>) -> Option<cardano::block::RawBlock> {
     reader.get_next().and_then(|x| Some(cardano::block::RawBlock(x)))
 }
+
+/// Iterator over the blocks of a pack file, in the order they were written.
+///
+/// The hash of each block is found through the pack's index. Reading a
+/// truncated or corrupted pack yields an error, after which the iterator
+/// stops.
+pub struct PackBlockIterator {
+    file: fs::File,
+    file_len: u64,
+    offset: u64,
+    hashes: BTreeMap<u64, super::BlockHash>,
+    failed: bool,
+}
+
+fn invalid_data<S: Into<String>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+impl PackBlockIterator {
+    pub fn open(storage_config: &super::StorageConfig, pack: &super::PackHash) -> io::Result<Self> {
+        let index_filepath = storage_config.get_index_filepath(pack);
+        let (lookup, hashes) = indexfile::dump_file(&mut fs::File::open(&index_filepath)?)
+            .map_err(|err| invalid_data(format!("cannot read index: {}", err)))?;
+        let mut index = indexfile::ReaderNoLookup::init(&index_filepath)
+            .map_err(|err| invalid_data(format!("cannot read index: {}", err)))?;
+        let hashes = hashes.into_iter().enumerate()
+            .map(|(i, hash)| (index.resolve_index_offset(&lookup, i as indexfile::IndexOffset), hash))
+            .collect();
+
+        let mut file = fs::File::open(storage_config.get_pack_filepath(pack))?;
+        let file_len = file.metadata()?.len();
+        magic::check_header(&mut file, packfile::FILE_TYPE, packfile::VERSION, packfile::VERSION)
+            .map_err(|err| invalid_data(format!("invalid pack: {}", err)))?;
+        let offset = file.stream_position()?;
+
+        Ok(PackBlockIterator { file, file_len, offset, hashes, failed: false })
+    }
+
+    fn read_next(&mut self) -> io::Result<Option<(super::BlockHash, Vec<u8>)>> {
+        if self.offset == self.file_len { return Ok(None); }
+
+        let mut sz_buf = [0u8;SIZE_SIZE];
+        self.file.read_exact(&mut sz_buf)?;
+        let sz = read_size(&sz_buf) as u64;
+        let next_offset = self.offset + SIZE_SIZE as u64 + offset_align4(sz);
+        if next_offset > self.file_len {
+            return Err(invalid_data(format!("truncated block at offset {}", self.offset)));
+        }
+
+        let mut data = vec![0u8; offset_align4(sz) as usize];
+        self.file.read_exact(&mut data)?;
+        data.truncate(sz as usize);
+
+        let hash = match self.hashes.get(&self.offset) {
+            None => return Err(invalid_data(format!("block at offset {} is not in the index", self.offset))),
+            Some(hash) => *hash,
+        };
+        self.offset = next_offset;
+        Ok(Some((hash, data)))
+    }
+}
+
+impl Iterator for PackBlockIterator {
+    type Item = io::Result<(super::BlockHash, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed { return None; }
+        match self.read_next() {
+            Ok(None) => None,
+            Ok(Some(block)) => Some(Ok(block)),
+            Err(err) => { self.failed = true; Some(Err(err)) },
+        }
+    }
+}
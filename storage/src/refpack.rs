@@ -1,9 +1,20 @@
 //! pack of references, in a certain order
 
+use std::{fs, io, path::Path};
 use config::{StorageConfig};
 use storage_units::reffile;
+use storage_units::utils::magic;
+use storage_units::utils::serialize::{read_size, write_size, SIZE_SIZE};
+use storage_units::utils::tmpfile;
+use types::{BlockHash, HASH_SIZE};
+use config::storage_to_io_error;
 use super::Result;
 
+// the hash list of `read` and `write` is not a `reffile`: it has its own
+// file type so one cannot be read as the other.
+const FILE_TYPE: magic::FileType = 0x484c5354; // = HLST
+const VERSION: magic::Version = 1;
+
 pub use std::collections::vec_deque::{Iter};
 
 pub fn read_refpack<P: AsRef<str>>(storage_config: &StorageConfig, name: P) -> Result<reffile::Lookup> {
@@ -16,3 +27,92 @@ pub fn write_refpack<P: AsRef<str>>(storage_config: &StorageConfig, name: P, rf:
     rf.to_path(path)?;
     Ok(())
 }
+
+/// read the ordered list of block hashes written by `write`.
+///
+/// The file is the storage's magic header (of its own file type, this is
+/// not the format of the `reffile` read by `read_refpack`), the number of
+/// hashes (4 bytes, big endian) and the concatenated hashes. A file of
+/// another type, or shorter (or longer) than announced by its length
+/// prefix, is reported as `InvalidData`.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<BlockHash>> {
+    let bytes = fs::read(path)?;
+    if bytes.len() < magic::HEADER_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "refpack is missing its header"));
+    }
+    magic::check_header(&mut &bytes[..magic::HEADER_SIZE], FILE_TYPE, VERSION, VERSION).map_err(storage_to_io_error)?;
+    let bytes = &bytes[magic::HEADER_SIZE..];
+    if bytes.len() < SIZE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "refpack is missing its length prefix"));
+    }
+    let nb = read_size(&bytes[0..SIZE_SIZE]) as usize;
+    let content = &bytes[SIZE_SIZE..];
+    if content.len() != nb * HASH_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            format!("refpack announces {} hashes but contains {} bytes", nb, content.len())));
+    }
+    Ok(content.chunks(HASH_SIZE).map(|chunk| {
+        let mut hash = [0u8;HASH_SIZE];
+        hash.clone_from_slice(chunk);
+        hash
+    }).collect())
+}
+
+/// atomically write the ordered list of block hashes at the given path,
+/// see `read` for the format.
+pub fn write<P: AsRef<Path>>(path: P, hashes: &[BlockHash]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(magic::HEADER_SIZE + SIZE_SIZE + hashes.len() * HASH_SIZE);
+    magic::write_header(&mut bytes, FILE_TYPE, VERSION).map_err(storage_to_io_error)?;
+    let mut size = [0u8;SIZE_SIZE];
+    write_size(&mut size, hashes.len() as u32);
+    bytes.extend_from_slice(&size);
+    for hash in hashes {
+        bytes.extend_from_slice(&hash[..]);
+    }
+    tmpfile::atomic_write_simple(&path.as_ref().to_path_buf(), &bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_helpers::temp_path;
+
+    #[test]
+    fn write_read_round_trip() {
+        let path = temp_path("refpack");
+        let hashes : Vec<BlockHash> = (0..10u8).map(|i| [i;HASH_SIZE]).collect();
+        write(&path, &hashes).unwrap();
+        assert_eq!(read(&path).unwrap(), hashes);
+
+        write(&path, &[]).unwrap();
+        assert!(read(&path).unwrap().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_truncated_refpack_fails() {
+        let path = temp_path("refpack");
+        let hashes : Vec<BlockHash> = (0..3u8).map(|i| [i;HASH_SIZE]).collect();
+        write(&path, &hashes).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        for truncated_len in &[0, 2, magic::HEADER_SIZE + 2, magic::HEADER_SIZE + SIZE_SIZE + HASH_SIZE, bytes.len() - 1] {
+            fs::write(&path, &bytes[..*truncated_len]).unwrap();
+            assert_eq!(read(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_reffile_fails() {
+        let path = temp_path("refpack");
+        let mut lookup = reffile::Lookup::new();
+        lookup.append_hash([1;HASH_SIZE]);
+        lookup.to_path(&path).unwrap();
+        assert_eq!(read(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        write(&path, &[[1;HASH_SIZE]]).unwrap();
+        assert!(reffile::Lookup::from_path(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
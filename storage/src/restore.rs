@@ -0,0 +1,117 @@
+//! restore the progress of a BIP44 account from the addresses found in the
+//! blocks of the storage's epochs.
+
+use std::io;
+use cardano::address::ExtendedAddr;
+use cardano::block::EpochId;
+use cardano::wallet::scheme;
+use cardano::wallet::bip44::{Account, AddrType, GapLimits, Index};
+
+use config::StorageConfig;
+
+/// result of [`restore_from_chain`](./fn.restore_from_chain.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// the epochs the addresses were fetched from, in increasing order
+    pub epochs: Vec<EpochId>,
+    /// the highest receive (external) index found on the chain
+    pub highest_external: Option<Index>,
+    /// the highest change (internal) index found on the chain
+    pub highest_internal: Option<Index>,
+    /// the number of distinct addresses of the account found on the chain
+    pub owned_addresses: usize,
+}
+
+/// restore the last known indices of the account from the addresses used
+/// in the epochs stored in `storage` (see `StorageConfig::list_epochs`).
+///
+/// `fetch_block_addresses` returns the addresses of the blocks of the given
+/// epoch (e.g. the outputs of its transactions). The account's chains are
+/// then scanned with the given gap limits, see
+/// [`Account::full_scan`](../../cardano/wallet/bip44/struct.Account.html#method.full_scan),
+/// so only the addresses within the gap limits are recovered.
+///
+/// Fails on the first error listing the epochs or fetching their addresses,
+/// the account is not modified in this case.
+pub fn restore_from_chain<K, F>( account: &mut Account<K>
+                               , storage: &StorageConfig
+                               , gap_limits: GapLimits
+                               , fetch_block_addresses: F
+                               ) -> io::Result<RestoreReport>
+    where F: Fn(EpochId) -> io::Result<Vec<ExtendedAddr>>
+        , Account<K>: scheme::Account<Addressing = (AddrType, u32)>
+{
+    let epochs = storage.list_epochs()?;
+    let mut addresses = Vec::new();
+    for &epochid in epochs.iter() {
+        let found = fetch_block_addresses(epochid)?;
+        debug!("restore: {} address(es) in epoch {}", found.len(), epochid);
+        addresses.extend(found);
+    }
+
+    let result = account.full_scan(&addresses, &addresses, gap_limits);
+    Ok(RestoreReport {
+        epochs,
+        highest_external: result.external_found.last().map(|&(index, _)| index),
+        highest_internal: result.internal_found.last().map(|&(index, _)| index),
+        owned_addresses: result.external_found.len() + result.internal_found.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use test_helpers::temp_path;
+    use cardano::hdwallet::{self, XPrv, DerivationScheme};
+    use cardano::wallet::bip44::Wallet;
+    use cardano::wallet::scheme::Wallet as WalletScheme;
+
+    #[test]
+    fn restore_from_synthetic_epochs() {
+        let path = temp_path("restore");
+        let config = StorageConfig::new(&path);
+        // only the pack pointer of an epoch is needed to list it
+        for epochid in 0..3 {
+            fs::create_dir_all(config.get_epoch_dir(epochid)).unwrap();
+            fs::write(config.get_epoch_pack_filepath(epochid), b"").unwrap();
+        }
+
+        let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+        let mut wallet = Wallet::from_root_key(XPrv::generate_from_seed(&seed), DerivationScheme::V2);
+        let mut account = wallet.create_account("test", 0);
+        let foreign = {
+            let seed = hdwallet::Seed::from_bytes([1;hdwallet::SEED_SIZE]);
+            Wallet::from_root_key(XPrv::generate_from_seed(&seed), DerivationScheme::V2)
+                .create_account("test", 0).derive_address_at(AddrType::External, 0).unwrap()
+        };
+        let blocks = {
+            let address = |addr_type, index| account.derive_address_at(addr_type, index).unwrap();
+            vec![
+                vec![address(AddrType::External, 0), foreign.clone(), address(AddrType::External, 3)],
+                vec![address(AddrType::Internal, 2), address(AddrType::External, 3)],
+                // the external address 40 is beyond the gap limit of the address 15
+                vec![address(AddrType::External, 15), address(AddrType::External, 40)],
+            ]
+        };
+
+        let report = restore_from_chain(&mut account, &config, GapLimits::default(), |epochid| {
+            Ok(blocks[epochid as usize].clone())
+        }).unwrap();
+        assert_eq!(report, RestoreReport {
+            epochs: vec![0, 1, 2],
+            highest_external: Some(Index::new(15).unwrap()),
+            highest_internal: Some(Index::new(2).unwrap()),
+            owned_addresses: 4,
+        });
+        assert_eq!(account.last_known_address(), Some(Index::new(15).unwrap()));
+        assert_eq!(account.last_known_change(), Some(Index::new(2).unwrap()));
+
+        let failing = restore_from_chain(&mut account, &config, GapLimits::default(), |_| {
+            Err(io::Error::other("no block"))
+        });
+        assert!(failing.is_err());
+
+        fs::remove_dir_all(config.get_path()).unwrap();
+    }
+}
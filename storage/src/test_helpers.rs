@@ -0,0 +1,19 @@
+//! fixtures shared by the unit tests of the storage modules.
+
+use std::env;
+use std::path::PathBuf;
+use rand;
+use super::Storage;
+use config::StorageConfig;
+
+/// a path, not created, with a random suffix in the temporary directory
+pub fn temp_path(name: &str) -> PathBuf {
+    let mut path = env::temp_dir();
+    path.push(format!("cardano-storage-{}-{}", name, rand::random::<u64>()));
+    path
+}
+
+/// a new storage initialized in a temporary directory
+pub fn test_storage(name: &str) -> Storage {
+    Storage::init(&StorageConfig::new(&temp_path(name))).unwrap()
+}
@@ -1,4 +1,6 @@
+use std::{fmt, error};
 use cardano::block::HeaderHash;
+use cardano::util::hex;
 pub use storage_units::hash::*;
 
 pub fn header_to_blockhash(header_hash: &HeaderHash) -> BlockHash {
@@ -7,7 +9,126 @@ pub fn header_to_blockhash(header_hash: &HeaderHash) -> BlockHash {
     bh
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+/// error when parsing a hexadecimal encoded `BlockHash` or `PackHash`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HashError {
+    /// the string is not valid hexadecimal
+    InvalidHex(hex::Error),
+    /// the string decoded to the given number of bytes instead of `HASH_SIZE`
+    InvalidSize(usize),
+}
+impl From<hex::Error> for HashError {
+    fn from(e: hex::Error) -> Self { HashError::InvalidHex(e) }
+}
+impl fmt::Display for HashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HashError::InvalidHex(_) => write!(f, "invalid hexadecimal hash"),
+            HashError::InvalidSize(sz) => write!(f, "invalid hash size, expected {} bytes but got {}", HASH_SIZE, sz),
+        }
+    }
+}
+impl error::Error for HashError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            HashError::InvalidHex(ref err) => Some(err),
+            HashError::InvalidSize(_) => None,
+        }
+    }
+}
+
+/// decode a hexadecimal string into a hash of exactly `HASH_SIZE` bytes.
+///
+/// `BlockHash` and `PackHash` are both aliases of the same array type, see
+/// [`blockhash_from_hex`](./fn.blockhash_from_hex.html) and
+/// [`packhash_from_hex`](./fn.packhash_from_hex.html).
+///
+/// ```
+/// use cardano_storage::types::{hash_from_hex, hash_to_hex, HashError};
+///
+/// let s = "0000000000000000000000000000000000000000000000000000000000000001";
+/// let hash = hash_from_hex(s).unwrap();
+/// assert_eq!(hash[31], 1);
+/// assert_eq!(hash_to_hex(&hash), s);
+/// assert_eq!(hash_from_hex("0001"), Err(HashError::InvalidSize(2)));
+/// ```
+pub fn hash_from_hex(s: &str) -> Result<[u8;HASH_SIZE], HashError> {
+    let v = hex::decode(s)?;
+    if v.len() != HASH_SIZE { return Err(HashError::InvalidSize(v.len())); }
+    let mut hash = [0u8;HASH_SIZE];
+    hash.clone_from_slice(&v[..]);
+    Ok(hash)
+}
+
+pub fn hash_to_hex(hash: &[u8;HASH_SIZE]) -> String { hex::encode(hash) }
+
+pub fn blockhash_from_hex(s: &str) -> Result<BlockHash, HashError> { hash_from_hex(s) }
+pub fn blockhash_to_hex(blockhash: &BlockHash) -> String { hash_to_hex(blockhash) }
+
+pub fn packhash_from_hex(s: &str) -> Result<PackHash, HashError> { hash_from_hex(s) }
+pub fn packhash_to_hex(packhash: &PackHash) -> String { hash_to_hex(packhash) }
+
+/// serde support for `BlockHash` and `PackHash`, to use with
+/// `#[serde(with = "cardano_storage::types::serde_hash")]`.
+///
+/// The hashes are hexadecimal strings in human readable formats (JSON,
+/// YAML...) and raw bytes in the other formats.
+#[cfg(feature = "generic-serialization")]
+pub mod serde_hash {
+    use std::{fmt, result};
+    use serde;
+    use super::{hash_from_hex, hash_to_hex, HashError, HASH_SIZE};
+
+    pub fn serialize<S>(hash: &[u8;HASH_SIZE], serializer: S) -> result::Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hash_to_hex(hash))
+        } else {
+            serializer.serialize_bytes(&hash[..])
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> result::Result<[u8;HASH_SIZE], D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        struct HashVisitor;
+        impl<'de> serde::de::Visitor<'de> for HashVisitor {
+            type Value = [u8;HASH_SIZE];
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                write!(fmt, "Expecting a {} bytes hash", HASH_SIZE)
+            }
+
+            fn visit_str<'a, E>(self, v: &'a str) -> result::Result<Self::Value, E>
+                where E: serde::de::Error
+            {
+                match hash_from_hex(v) {
+                    Err(HashError::InvalidSize(sz)) => Err(E::invalid_length(sz, &"32 bytes")),
+                    Err(err) => Err(E::custom(format!("{}", err))),
+                    Ok(h) => Ok(h)
+                }
+            }
+
+            fn visit_bytes<'a, E>(self, v: &'a [u8]) -> result::Result<Self::Value, E>
+                where E: serde::de::Error
+            {
+                if v.len() != HASH_SIZE { return Err(E::invalid_length(v.len(), &"32 bytes")); }
+                let mut hash = [0u8;HASH_SIZE];
+                hash.clone_from_slice(v);
+                Ok(hash)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HashVisitor)
+        } else {
+            deserializer.deserialize_bytes(HashVisitor)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum StorageFileType {
     Pack,
     Index,
@@ -16,3 +137,73 @@ pub enum StorageFileType {
     RefPack,
     Epoch,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "89d9b5a5b8ddc8d7e5a6795e9774d97faf1efea59b2caf7eaf9f8c5b32059df4";
+
+    #[test]
+    fn hash_hex_roundtrip() {
+        let blockhash = blockhash_from_hex(HASH).unwrap();
+        assert_eq!(blockhash[0], 0x89);
+        assert_eq!(blockhash[HASH_SIZE - 1], 0xf4);
+        assert_eq!(blockhash_to_hex(&blockhash), HASH);
+
+        let packhash = packhash_from_hex(&HASH.to_uppercase()).unwrap();
+        assert_eq!(packhash, blockhash);
+        assert_eq!(packhash_to_hex(&packhash), HASH);
+    }
+
+    #[test]
+    fn hash_hex_wrong_length() {
+        assert_eq!(hash_from_hex(""), Err(HashError::InvalidSize(0)));
+        assert_eq!(hash_from_hex(&HASH[..62]), Err(HashError::InvalidSize(31)));
+        assert_eq!(hash_from_hex(&format!("{}00", HASH)), Err(HashError::InvalidSize(33)));
+    }
+
+    #[test]
+    fn hash_hex_invalid_symbol() {
+        let s = format!("{}zz", &HASH[..62]);
+        assert_eq!(blockhash_from_hex(&s), Err(HashError::InvalidHex(hex::Error::UnknownSymbol(62))));
+        assert!(packhash_from_hex("tag-name").is_err());
+    }
+
+    #[cfg(feature = "generic-serialization")]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Hashes {
+        #[serde(with = "serde_hash")]
+        block: BlockHash,
+        #[serde(with = "serde_hash")]
+        pack: PackHash,
+    }
+
+    #[cfg(feature = "generic-serialization")]
+    #[test]
+    fn hash_serde_json_is_hex() {
+        use serde_json;
+
+        let hashes = Hashes { block: blockhash_from_hex(HASH).unwrap(), pack: [0;HASH_SIZE] };
+        let json = serde_json::to_string(&hashes).unwrap();
+        assert_eq!(json, format!("{{\"block\":\"{}\",\"pack\":\"{}\"}}", HASH, "00".repeat(HASH_SIZE)));
+        assert_eq!(serde_json::from_str::<Hashes>(&json).unwrap(), hashes);
+
+        let short = format!("{{\"block\":\"{}\",\"pack\":\"{}\"}}", &HASH[..62], HASH);
+        assert!(serde_json::from_str::<Hashes>(&short).is_err());
+        let invalid = format!("{{\"block\":\"{}zz\",\"pack\":\"{}\"}}", &HASH[..62], HASH);
+        assert!(serde_json::from_str::<Hashes>(&invalid).is_err());
+    }
+
+    #[cfg(feature = "generic-serialization")]
+    #[test]
+    fn hash_serde_bincode_is_raw_bytes() {
+        use bincode;
+
+        let hashes = Hashes { block: blockhash_from_hex(HASH).unwrap(), pack: [7;HASH_SIZE] };
+        let bytes = bincode::serialize(&hashes).unwrap();
+        // each hash is a length prefix followed by its bytes
+        assert_eq!(bytes.len(), 2 * (8 + HASH_SIZE));
+        assert_eq!(bincode::deserialize::<Hashes>(&bytes).unwrap(), hashes);
+    }
+}